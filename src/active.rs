@@ -0,0 +1,102 @@
+//! Shows which parts of a cycle configuration would be shown at a given
+//! point in time, without connecting to any device.
+
+use crate::args::{Active, Cycle, Invocation};
+use crate::cycle::is_active;
+use crate::error_kind::ErrorKind;
+use crate::slot::{parse_slot_time, ParseSlotError};
+use chrono::{DateTime, Duration as ChronoDuration, Local, TimeZone};
+use serde_yaml::from_reader;
+use std::fs::File;
+use thiserror::Error;
+
+pub fn active(opts: Active) -> Result<(), ActiveError> {
+    let file = File::open(&opts.config)?;
+    let invocation: Invocation = from_reader(file)?;
+    let cycle = match invocation {
+        Invocation::Cycle(cycle) => cycle,
+        _ => return Err(ActiveError::NotCycle),
+    };
+    let now = match opts.at {
+        Some(ref at) => parse_slot_time(at)?.in_zone(&Local),
+        None => Local::now(),
+    };
+
+    let active = active_plans(&cycle, &now);
+    if active.is_empty() {
+        println!("Nothing to show at {}", now);
+    }
+    for plan in active {
+        println!("{}", plan);
+    }
+    Ok(())
+}
+
+/// Formats the plans of the cycle configuration that are active at `now`,
+/// in the order they would be shown.
+fn active_plans<Tz: TimeZone>(cycle: &Cycle, now: &DateTime<Tz>) -> Vec<String> {
+    let lookahead = ChronoDuration::hours(cycle.lookahead as i64);
+    cycle
+        .plan
+        .iter()
+        .filter(|plan| is_active(plan.slots(), lookahead, now))
+        .map(|plan| plan.to_string())
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum ActiveError {
+    #[error("Could not open specified YAML configuration file: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Could not parse specified YAML configuration file: {0}")]
+    Deserialize(#[from] serde_yaml::Error),
+    #[error("Only cycle configurations have plans that can be active")]
+    NotCycle,
+    #[error("Could not parse time to check: {0}")]
+    At(#[from] ParseSlotError),
+}
+
+impl ActiveError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ActiveError::IO(_) | ActiveError::Deserialize(_) | ActiveError::NotCycle => {
+                ErrorKind::Config
+            }
+            ActiveError::At(_) => ErrorKind::Usage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use serde_yaml::from_str;
+
+    fn at(source: &str) -> DateTime<Utc> {
+        parse_slot_time(source).unwrap().in_zone(&Utc)
+    }
+
+    fn example() -> Cycle {
+        match from_str(include_str!("../examples/cycle.yaml")).unwrap() {
+            Invocation::Cycle(cycle) => cycle,
+            _ => panic!("Expected cycle example"),
+        }
+    }
+
+    #[test]
+    fn during_slot() {
+        let now = at("2021-09-09T20:30:00");
+        assert_eq!(
+            active_plans(&example(), &now),
+            vec!["0", "6@2021-09-09T18:00:00/2021-09-10T00:00:00"]
+        );
+    }
+
+    #[test]
+    fn after_slot() {
+        let now = at("2021-09-10 00:00");
+        assert_eq!(active_plans(&example(), &now), vec!["0"]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Expansion of `@file` arguments into the arguments listed in the file, e.g.
+//! to keep long lists of plans for `cycle` in a file without writing a full
+//! configuration:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! 0
+//! 6@2021-09-09T18:00:00/2021-09-10T00:00:00
+//! --serial /dev/ttyUSB0
+//! ```
+//!
+//! Each line holds one or more arguments separated by whitespace. Arguments
+//! with spaces, e.g. slots like `6@2021-09-09 18:00/2021-09-10 00:00`, can be
+//! put in double quotes, which are not part of the argument. There is no way
+//! to escape a double quote. Arguments in the file are not expanded again.
+//!
+//! Arguments after `--` are left as they are, so that values starting with
+//! `@`, e.g. a telegram for `send`, can be passed with `ibisibi send -- @foo`.
+
+use std::fs::read_to_string;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, ArgFileError>;
+
+/// Replaces each argument that starts with `@` with the arguments in the file
+/// at the path after the `@`, leaving other arguments and any arguments after
+/// `--` as they are.
+pub fn expand<I>(args: I) -> Result<Vec<String>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut expanded = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            // argh also needs to see it to take the rest as positionals
+            expanded.push(arg);
+            expanded.extend(args);
+            break;
+        }
+        match arg.strip_prefix('@') {
+            Some(path) if !path.is_empty() => {
+                let source = read_to_string(path).map_err(|source| ArgFileError {
+                    source,
+                    path: path.into(),
+                })?;
+                expanded.extend(parse(&source));
+            }
+            _ => expanded.push(arg),
+        }
+    }
+    Ok(expanded)
+}
+
+fn parse(source: &str) -> impl Iterator<Item = String> + '_ {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(split)
+}
+
+/// Splits a line at whitespace outside of double quotes, dropping the quotes.
+///
+/// A quote that is not closed runs until the end of the line.
+fn split(line: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                // so that "" is an empty argument
+                arg.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+#[derive(Error, Debug)]
+#[error("Could not read arguments from {path}: {source}")]
+pub struct ArgFileError {
+    source: std::io::Error,
+    path: PathBuf,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::args::{Cycle, Invocation, TopLevel};
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn leaves_other_args() {
+        let args = strings(&[
+            "cycle",
+            "0",
+            "2@2021-09-09T20:00:00/2021-09-09T21:00:00",
+            "@",
+        ]);
+        assert_eq!(expand(args.clone()).unwrap(), args);
+    }
+
+    #[test]
+    fn expand_example_into_cycle() {
+        let args = expand(strings(&[
+            "cycle",
+            "@examples/cycle.args",
+            "--lookahead",
+            "3",
+        ]))
+        .unwrap();
+        let args: Vec<&str> = args.iter().map(|a| &a[..]).collect();
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &args).unwrap();
+        match args.invocation {
+            Invocation::Cycle(Cycle {
+                plan,
+                interval_secs,
+                lookahead,
+                serial,
+                ..
+            }) => {
+                assert_eq!(
+                    plan,
+                    vec![
+                        "0".parse().unwrap(),
+                        "6@2021-09-09T18:00:00/2021-09-10T00:00:00".parse().unwrap()
+                    ]
+                );
+                assert_eq!(interval_secs, 9.0);
+                assert_eq!(lookahead, 3);
+                assert_eq!(serial.as_deref(), Some("/dev/ttyUSB0"));
+            }
+            _ => panic!("unexpected subcommand"),
+        }
+    }
+
+    #[test]
+    fn leaves_args_after_double_dash() {
+        let args = strings(&["send", "--", "@examples/cycle.args"]);
+        let expanded = expand(args.clone()).unwrap();
+        assert_eq!(expanded, args);
+        let expanded: Vec<&str> = expanded.iter().map(|a| &a[..]).collect();
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &expanded).unwrap();
+        match args.invocation {
+            Invocation::Send(send) => assert_eq!(send.payload, strings(&["@examples/cycle.args"])),
+            _ => panic!("unexpected subcommand"),
+        }
+    }
+
+    #[test]
+    fn quoted_args_keep_spaces() {
+        let args: Vec<String> =
+            parse("\"6@2021-09-09 18:00/2021-09-10 00:00\" --serial \"\"\n0 \"unclosed quote")
+                .collect();
+        assert_eq!(
+            args,
+            strings(&[
+                "6@2021-09-09 18:00/2021-09-10 00:00",
+                "--serial",
+                "",
+                "0",
+                "unclosed quote"
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_file() {
+        let err = expand(strings(&["cycle", "@examples/missing.args"])).unwrap_err();
+        assert_eq!(err.path, PathBuf::from("examples/missing.args"));
+    }
+}
@@ -1,15 +1,65 @@
+use crate::interval::Interval;
 use crate::plan::Plan;
+use crate::range::Range;
+use crate::telegram::Telegram;
 use argh::FromArgs;
 use serde::Deserialize;
+use std::convert::TryFrom;
 use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::Level;
 
 /// Write IBIS telegrams to serial ports or list available serial ports.
 #[derive(FromArgs)]
 pub struct TopLevel {
+    /// how often to retry opening the serial port if it is not available,
+    /// e.g. when a USB adapter is still being set up after boot, defaults
+    /// to 0 for a single attempt.
+    #[argh(option, default = "0")]
+    pub open_retries: u32,
+    /// time to wait before retrying to open the serial port, doubled after
+    /// each further failed attempt up to 1m, e.g. 500ms, defaults to 1s.
+    #[argh(option)]
+    pub open_retry_delay: Option<Interval>,
+    /// print each telegram and record to stderr as it is sent.
+    #[argh(switch)]
+    pub echo: bool,
+    /// append every byte sent and received to this file with timestamps, in
+    /// hex, regardless of the log level.
+    #[argh(option)]
+    pub trace_file: Option<PathBuf>,
+    /// most detailed level of log messages to write to stderr, one of error,
+    /// warn, info, debug or trace, defaults to warn.
+    #[argh(option, short = 'v', default = "Level::WARN")]
+    pub log_level: Level,
+    /// on failure, print a JSON object with the kind of error and its message
+    /// to stderr instead of the plain message, for scripts to read.
+    #[argh(switch)]
+    pub json_errors: bool,
     #[argh(subcommand)]
     pub invocation: Invocation,
 }
 
+/// Parses the given command line arguments, starting with the program name,
+/// like [`argh::from_env`] does for the unexpanded arguments.
+///
+/// Exits the process after printing help, and returns the output of the
+/// parser as the error if parsing failed.
+pub fn from_strings(args: &[String]) -> Result<TopLevel, String> {
+    let program = std::path::Path::new(&args[0])
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&args[0]);
+    let args: Vec<&str> = args[1..].iter().map(|a| &a[..]).collect();
+    TopLevel::from_args(&[program], &args).map_err(|early_exit| match early_exit.status {
+        Ok(()) => {
+            println!("{}", early_exit.output);
+            std::process::exit(0)
+        }
+        Err(()) => early_exit.output.trim_end().to_string(),
+    })
+}
+
 /// Inner top-level command.
 #[derive(FromArgs, Deserialize)]
 #[argh(subcommand)]
@@ -23,6 +73,18 @@ pub enum Invocation {
     Scan(Scan),
     Destination(Destination),
     Cycle(Cycle),
+    #[serde(skip)]
+    Checksum(Checksum),
+    SendScript(SendScript),
+    Send(SendTelegram),
+    Line(Line),
+    Blank(Blank),
+    Monitor(Monitor),
+    #[serde(skip)]
+    Active(Active),
+    Version(Version),
+    Dump(Dump),
+    Status(Status),
 }
 
 /// Take run parameters from a specified YAML configuration file.
@@ -31,54 +93,289 @@ pub enum Invocation {
 pub struct Run {
     #[argh(positional)]
     pub config: PathBuf,
+    /// print what the configuration would do and exit without executing it.
+    #[argh(switch)]
+    pub summary: bool,
+    /// check the configuration for values that cannot be sent, e.g.
+    /// destination indexes above 999, and print what it would do without
+    /// executing it.
+    #[argh(switch)]
+    pub check: bool,
 }
 
 /// List available serial ports.
 #[derive(FromArgs, Deserialize)]
 #[argh(subcommand, name = "list")]
-pub struct List {}
+pub struct List {
+    /// also print how each port is connected and, for USB adapters, vendor
+    /// and product ID and product name, to tell several adapters apart.
+    #[argh(switch)]
+    #[serde(default)]
+    pub verbose: bool,
+    /// only list ports where a display device responds to a status query,
+    /// which takes a few seconds per port.
+    #[argh(switch)]
+    #[serde(default)]
+    pub probe: bool,
+}
 
 /// Finds available addresses of display devices on the specified serial port.
 #[derive(FromArgs)]
 #[argh(subcommand, name = "scan")]
 pub struct Scan {
-    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows.
+    /// flag devices that respond at addresses the standard does not allow,
+    /// such as address 0, which some installations use anyway.
+    #[argh(switch)]
+    pub include_reserved: bool,
+    /// query the status once more when a response has a parity error, to
+    /// tell one-off glitches on a noisy bus from persistent corruption.
+    #[argh(switch)]
+    pub retry_parity: bool,
+    /// addresses to probe instead of all of them, e.g. 0-3 on a small bus
+    /// or 5 for a single device, ignoring addresses above 15.
+    #[argh(option, from_str_fn(parse_addresses))]
+    pub addresses: Option<Range>,
+    /// output format, either text, json for an array of objects with
+    /// address, status and version, or csv with the same columns, defaults
+    /// to text.
+    #[argh(option, default = "Format::Text")]
+    pub format: Format,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 1.
+    #[argh(option, default = "1")]
+    pub timeout_secs: u64,
+    /// wait only this long for each device in a quick first pass, e.g.
+    /// 100ms, then query the ones that responded again with --timeout-secs.
+    #[argh(option)]
+    pub quick_timeout: Option<Interval>,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
     #[argh(option, short = 's')]
-    pub serial: String,
+    pub serial: Option<String>,
+}
+
+/// How to print the results of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable text.
+    Text,
+    /// JSON, for other programs to read.
+    Json,
+    /// Comma-separated values with a header row, e.g. for spreadsheets.
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!(
+                "unknown format {}, expected text, json or csv",
+                source
+            )),
+        }
+    }
+}
+
+/// Format of a sign database to flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbFormat {
+    /// Intel HEX, as exported by sign database editors.
+    Hex,
+    /// Raw bytes written one after another from the base address.
+    Bin,
+}
+
+impl FromStr for DbFormat {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "hex" => Ok(DbFormat::Hex),
+            "bin" => Ok(DbFormat::Bin),
+            _ => Err(format!(
+                "unknown database format {}, expected hex or bin",
+                source
+            )),
+        }
+    }
 }
 
 /// Set the currently shown destination to the one with the given index
 /// using telegram DS003.
+///
+/// When given a range of indexes, shows each destination once in order.
 #[derive(FromArgs, Deserialize)]
 #[argh(subcommand, name = "destination")]
 pub struct Destination {
-    /// index of the destination to set, in range 0-999.
-    #[argh(positional)]
-    pub index: u16,
+    /// index of the destination to set, in range 0-999, or a range of
+    /// indexes like 1-3 to step through once.
+    #[argh(positional, from_str_fn(parse_destinations))]
+    pub index: Range,
     /// optional line number, in range 1-999.
-    #[argh(option, short = 'l')]
+    #[argh(option, short = 'l', from_str_fn(parse_line))]
     pub line: Option<u16>,
-    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows.
+    /// time to wait before switching to the next destination when stepping
+    /// through a range, e.g. 1s or 500ms, defaults to 5s.
+    #[argh(option)]
+    pub interval: Option<Interval>,
+    /// IBIS address of the display, required for --status-gated.
+    #[argh(option, short = 'a', from_str_fn(parse_address))]
+    pub address: Option<u8>,
+    /// name of a group of display addresses to check with --status-gated or
+    /// --verify, instead of a single address.
+    #[argh(option)]
+    pub group: Option<String>,
+    /// YAML file that defines address groups, required for --group.
+    #[argh(option)]
+    pub groups: Option<PathBuf>,
+    /// check every device that responds to a status query instead of
+    /// --address or --group, reporting the ones that fail the checks.
+    #[argh(switch)]
+    #[serde(default)]
+    pub all_addresses: bool,
+    /// query the display status first and wait until it is ready to show
+    /// destinations before sending them.
+    #[argh(switch)]
+    #[serde(default)]
+    pub status_gated: bool,
+    /// query the display status first and refuse to send destinations if no
+    /// device responds, to catch mistyped addresses.
+    #[argh(switch)]
+    #[serde(default)]
+    pub verify: bool,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// how often to try again if the port cannot be opened or written to,
+    /// e.g. because it is busy, re-opening it each time, defaults to 0.
+    #[argh(option, default = "0")]
+    #[serde(default)]
+    pub retry: u32,
+    /// time to wait before the first retry, doubled before each further
+    /// one up to 1m, e.g. 500ms, defaults to 1s.
+    #[argh(option)]
+    pub retry_delay: Option<Interval>,
+    /// write the telegrams to this file instead of a serial port, or to
+    /// stdout with -, e.g. to capture the exact bytes sent.
+    #[argh(option)]
+    pub output: Option<PathBuf>,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
+}
+
+/// Set only the line number using telegram DS001, leaving the destination as
+/// it is.
+///
+/// When given a range of lines, sets each line once in order.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "line")]
+pub struct Line {
+    /// line number to set, in range 1-999, or a range of lines like 1-3 to
+    /// step through once.
+    #[argh(positional, from_str_fn(parse_lines))]
+    pub line: Range,
+    /// time to wait before switching to the next line when stepping through
+    /// a range, e.g. 1s or 500ms, defaults to 5s.
+    #[argh(option)]
+    pub interval: Option<Interval>,
+    /// write the telegrams to this file instead of a serial port, or to
+    /// stdout with -, e.g. to capture the exact bytes sent.
+    #[argh(option)]
+    pub output: Option<PathBuf>,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
+}
+
+/// Show the destination that is agreed to be blank using telegram DS003, e.g.
+/// to clear the signs at the end of service.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "blank")]
+pub struct Blank {
+    /// index of the blank destination, in range 0-999, defaults to 0.
+    #[argh(option, default = "0", from_str_fn(parse_destination))]
+    #[serde(default)]
+    pub blank_index: u16,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
     #[argh(option, short = 's')]
-    pub serial: String,
+    pub serial: Option<String>,
+}
+
+/// Print telegrams received on the bus without sending anything, e.g. to see
+/// what other masters send.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "monitor")]
+pub struct Monitor {
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
 }
 
 /// Flash a new sign database in .hex format to a BS210 sign.
 #[derive(FromArgs, Deserialize, Debug)]
 #[argh(subcommand, name = "flash")]
 pub struct Flash {
-    /// path to a BS210-compatible sign database in `.hex` format.
+    /// path to a BS210-compatible sign database in `.hex` format, or a raw
+    /// binary image ending in `.bin`, or - after -- to read it from stdin.
     #[argh(positional)]
     pub sign_db_hex: PathBuf,
-    /// IBIS address to flash to in range 0..15.
-    #[argh(option, short = 'a')]
-    pub address: u8,
-    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows.
+    /// format of the database, either hex or bin for a raw binary image,
+    /// defaults to bin for files ending in `.bin` and to hex otherwise.
+    #[argh(option)]
+    pub format: Option<DbFormat>,
+    /// offset on the device to write a binary image to, e.g. 0x100,
+    /// defaults to 0.
+    #[argh(option, default = "0", from_str_fn(parse_base_address))]
+    #[serde(default)]
+    pub base_address: u16,
+    /// IBIS address to flash to in range 0..15, in decimal or hex like 0x0a.
+    #[argh(option, short = 'a', from_str_fn(parse_address))]
+    pub address: Option<u8>,
+    /// flash the first device that responds to a status query, e.g. when
+    /// only one sign is attached, instead of --address or --group.
+    #[argh(switch)]
+    #[serde(default)]
+    pub any_address: bool,
+    /// flash every device that responds to a status query, continuing with
+    /// the next device if flashing one fails.
+    #[argh(switch)]
+    #[serde(default)]
+    pub all_addresses: bool,
+    /// name of a group of addresses to flash to one after another, instead
+    /// of a single address.
+    #[argh(option)]
+    pub group: Option<String>,
+    /// YAML file that defines address groups, required for --group.
+    #[argh(option)]
+    pub groups: Option<PathBuf>,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
     #[argh(option, short = 's')]
-    pub serial: String,
-    /// the receive timeout.
+    pub serial: Option<String>,
+    /// the receive timeout in seconds.
     #[argh(option, short = 't', default = "5")]
     pub timeout: u64,
+    /// receive timeout while clearing the database, e.g. 10s, defaults to
+    /// the receive timeout.
+    #[argh(option)]
+    pub clear_timeout: Option<Interval>,
+    /// receive timeout for acknowledgements of database chunks, e.g. 500ms,
+    /// defaults to the receive timeout.
+    #[argh(option)]
+    pub chunk_ack_timeout: Option<Interval>,
     /// data bits as any of 5, 6, 7, 8
     #[argh(option, default = "7")]
     pub data_bits: u8,
@@ -94,6 +391,174 @@ pub struct Flash {
     /// flow control as s (software) or h (hardware) or n (none)
     #[argh(option, default = "'n'")]
     pub flow_control: char,
+    /// query the software version before flashing and refuse to flash
+    /// devices that do not identify as a supported sign.
+    #[argh(switch)]
+    #[serde(default)]
+    pub check_version: bool,
+    /// refuse to flash devices that do not report being ready for data in
+    /// response to the status query.
+    #[argh(switch)]
+    #[serde(default)]
+    pub require_ready: bool,
+    /// flash even if the device does not pass the sanity checks.
+    #[argh(switch)]
+    #[serde(default)]
+    pub force: bool,
+    /// how many empty telegrams to send before selecting the address, for
+    /// signs that do not react to a single one, defaults to 1.
+    #[argh(option, default = "1")]
+    #[serde(default = "default_wake_count")]
+    pub wake_count: u32,
+    /// how many times to send a chunk again if the device does not
+    /// acknowledge it, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_chunk_retries")]
+    pub chunk_retries: u32,
+    /// abort flashing a device that takes longer than this, e.g. 5m.
+    #[argh(option)]
+    pub max_flash_time: Option<Interval>,
+    /// refuse to flash databases with missing chunks instead of warning.
+    #[argh(switch)]
+    #[serde(default)]
+    pub strict: bool,
+    /// read back the cleared region after clearing and abort if it does not
+    /// hold the expected fill, using an experimental read query.
+    #[argh(switch)]
+    #[serde(default)]
+    pub verify_clear: bool,
+    /// read each chunk back after flashing and compare it to the database,
+    /// using an experimental read query.
+    #[argh(switch)]
+    #[serde(default)]
+    pub verify: bool,
+    /// query the status after flashing and fail unless the device reports
+    /// being ok, as a sign that it accepted the database.
+    #[argh(switch)]
+    #[serde(default)]
+    pub check_status_after: bool,
+    /// read and discard a response to the last finishing step, for signs
+    /// that reply to it, at the cost of waiting for the receive timeout.
+    #[argh(switch)]
+    #[serde(default)]
+    pub drain_finish: bool,
+    /// do not print the percentage of the database written so far to
+    /// stderr.
+    #[argh(switch, short = 'q')]
+    #[serde(default)]
+    pub quiet: bool,
+    /// print the chunks that would be written instead of flashing, only
+    /// opening the port to check compatibility if --serial is given.
+    #[argh(switch)]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Parses a line number, refusing numbers that cannot be sent.
+fn parse_line(value: &str) -> Result<u16, String> {
+    let line = value
+        .parse()
+        .map_err(|_| format!("not a line number: {}", value))?;
+    Telegram::try_line(line).map_err(|e| e.to_string())?;
+    Ok(line)
+}
+
+/// Parses line numbers, refusing ranges that contain lines that cannot be
+/// sent.
+fn parse_lines(value: &str) -> Result<Range, String> {
+    let range = value.parse().map_err(|e| format!("{}", e))?;
+    crate::line::check_lines(&range).map_err(|e| e.to_string())?;
+    Ok(range)
+}
+
+/// Parses a destination index, refusing indexes that cannot be sent.
+fn parse_destination(value: &str) -> Result<u16, String> {
+    let index = value
+        .parse()
+        .map_err(|_| format!("not a destination index: {}", value))?;
+    Telegram::try_destination(index).map_err(|e| e.to_string())?;
+    Ok(index)
+}
+
+/// Parses destination indexes, refusing ranges that go beyond the highest
+/// index that can be sent.
+fn parse_destinations(value: &str) -> Result<Range, String> {
+    let range = value.parse().map_err(|e| format!("{}", e))?;
+    crate::destination::check_sendable(&range, None).map_err(|e| e.to_string())?;
+    Ok(range)
+}
+
+/// Parses a plan, refusing destination indexes and lines that cannot be sent.
+fn parse_plan(value: &str) -> Result<Plan, String> {
+    let plan: Plan = value.parse().map_err(|e| format!("{}", e))?;
+    for destination in plan.destinations() {
+        crate::destination::check_sendable(destination.range(), destination.line())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(plan)
+}
+
+/// Parses a range of IBIS addresses to scan, clamped to the highest address,
+/// refusing ranges that contain no address at all.
+fn parse_addresses(value: &str) -> Result<Range, String> {
+    let range: Range = value.parse().map_err(|e| format!("{}", e))?;
+    range
+        .clamp_max(crate::scan::ADDRESS_MAX.into())
+        .ok_or_else(|| format!("no address in range 0-15: {}", value))
+}
+
+/// Parses a number in decimal or, with a `0x` prefix, in hex.
+fn parse_number(value: &str) -> Result<u32, std::num::ParseIntError> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+}
+
+/// Parses the offset to write a binary database to, e.g. `256` or `0x100`.
+fn parse_base_address(value: &str) -> Result<u16, String> {
+    parse_number(value)
+        .ok()
+        .and_then(|address| u16::try_from(address).ok())
+        .ok_or_else(|| format!("not a database offset in range 0-0xFFFF: {}", value))
+}
+
+/// Parses the number of bytes to dump, which has to fit into the database.
+fn parse_dump_len(value: &str) -> Result<u32, String> {
+    parse_number(value)
+        .ok()
+        .filter(|&len| len > 0 && len <= 0x1_0000)
+        .ok_or_else(|| format!("not a length in range 1-0x10000: {}", value))
+}
+
+/// Parses an IBIS address in decimal or, with a `0x` prefix, in hex, e.g.
+/// `10` or `0x0a`, refusing addresses that cannot be sent.
+fn parse_address(value: &str) -> Result<u8, String> {
+    let address = parse_number(value)
+        .ok()
+        .and_then(|address| u8::try_from(address).ok())
+        .ok_or_else(|| format!("not an address: {}", value))?;
+    Telegram::try_display_status(address).map_err(|e| e.to_string())?;
+    Ok(address)
+}
+
+fn default_wake_count() -> u32 {
+    1
+}
+
+fn default_chunk_retries() -> u32 {
+    3
+}
+
+fn default_timeout_secs() -> u64 {
+    3
+}
+
+fn default_retry_interval_secs() -> u64 {
+    5
 }
 
 /// Loop through the given destination indexes in regular intervals.
@@ -103,20 +568,234 @@ pub struct Flash {
 #[derive(FromArgs, Deserialize)]
 #[argh(subcommand, name = "cycle")]
 pub struct Cycle {
-    /// indexes or index ranges of the destinations to loop through, with optional scheduled time e.g. 8 or 0-5@2021-06-03T00:00:00.
+    /// indexes or index ranges of the destinations to loop through, with optional line numbers, time to show each destination and scheduled time e.g. 8, 6:0-3,12:4-7, 0-5*30s or 0-5@2021-06-03T00:00:00.
     ///
     /// Indexes must be in range 0 to 999.
-    #[argh(positional)]
+    #[argh(positional, from_str_fn(parse_plan))]
     pub plan: Vec<Plan>,
-    /// interval to wait before switching to the next destination.
+    /// seconds to wait before switching to the next destination, at least
+    /// 0.1, defaults to 5.
     #[argh(option, short = 'i', default = "5.0")]
     pub interval_secs: f64,
     /// show scheduled destinations this many hours before scheduled start
-    #[argh(option, short = 'i', default = "12")]
+    #[argh(option, short = 'l', default = "12")]
     pub lookahead: u32,
-    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows.
+    /// IBIS address of the display, required for --status-gated.
+    #[argh(option, short = 'a', from_str_fn(parse_address))]
+    pub address: Option<u8>,
+    /// query the display status before each destination and wait until it
+    /// is ready to show destinations.
+    #[argh(switch)]
+    #[serde(default)]
+    pub status_gated: bool,
+    /// reuse a status seen within this time for --status-gated instead of
+    /// querying it again before each destination, e.g. 30s, defaults to
+    /// querying every time.
+    #[argh(option)]
+    pub status_ttl: Option<Interval>,
+    /// do not send a destination again if it is the one sent last, e.g. for
+    /// a plan with a single destination, to spare flipdot displays the
+    /// flicker.
+    #[argh(switch)]
+    #[serde(default)]
+    pub skip_unchanged: bool,
+    /// send an unchanged destination again after this long with
+    /// --skip-unchanged, e.g. 10m, in case the sign was power cycled,
+    /// defaults to never.
+    #[argh(option)]
+    pub force_refresh: Option<Interval>,
+    /// show the destinations of all active plans in a new random order on
+    /// every pass.
+    #[argh(switch)]
+    #[serde(default)]
+    pub shuffle: bool,
+    /// seed for --shuffle to get the same order on every run, defaults to
+    /// a new seed each time.
+    #[argh(option)]
+    pub seed: Option<u64>,
+    /// destination to show before stopping on Ctrl-C or SIGTERM, e.g. an
+    /// out of service destination, otherwise the last one is left shown.
+    #[argh(option, from_str_fn(parse_destination))]
+    pub default_destination: Option<u16>,
+    /// destination to show once whenever no plan is active, e.g. a blank or
+    /// out of service destination, otherwise the last one is left shown.
+    #[argh(option, from_str_fn(parse_destination))]
+    pub fallback: Option<u16>,
+    /// stop after this long, e.g. 30m or 2h, like on Ctrl-C, defaults to
+    /// running until stopped.
+    #[argh(option)]
+    pub run_for: Option<Interval>,
+    /// print when each plan would be shown over this many hours from now
+    /// instead of cycling, without connecting to any device.
+    #[argh(option)]
+    pub preview: Option<u32>,
+    /// seconds to wait before trying again when a destination could not be
+    /// sent or when no plan is active, at least 1, defaults to 5.
+    #[argh(option, default = "5")]
+    #[serde(default = "default_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
+}
+
+/// Send telegrams and records from a script file in order, optionally waiting
+/// between lines and checking responses.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "send-script")]
+pub struct SendScript {
+    /// path to the script, with one telegram, record, mnemonic, `wait` or
+    /// `expect` per line.
+    #[argh(positional)]
+    pub script: PathBuf,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
     #[argh(option, short = 's')]
-    pub serial: String,
+    pub serial: Option<String>,
+}
+
+/// Send a single IBIS telegram with the given payload, appending CR and the
+/// parity byte, and print the response.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "send")]
+pub struct SendTelegram {
+    /// payload of the telegram, e.g. aV1, with multiple arguments joined by
+    /// spaces.
+    #[argh(positional)]
+    pub payload: Vec<String>,
+    /// read the payload as bytes in hexadecimal notation, e.g. 61 56 31.
+    #[argh(switch)]
+    #[serde(default)]
+    pub hex: bool,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// write the telegram to this file instead of a serial port, or to stdout
+    /// with -, without waiting for a response.
+    #[argh(option)]
+    pub output: Option<PathBuf>,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
+}
+
+/// Query the software version of a display device using telegram DS120.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "version")]
+pub struct Version {
+    /// IBIS address of the display.
+    #[argh(option, short = 'a', from_str_fn(parse_address))]
+    pub address: u8,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
+}
+
+/// Query the status of a display device, exiting with 0 if it responded, 1 if
+/// none did and 2 if the query failed, e.g. to check for a sign in scripts.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "status")]
+pub struct Status {
+    /// IBIS address of the display.
+    #[argh(option, short = 'a', from_str_fn(parse_address))]
+    pub address: Option<u8>,
+    /// query every address in order until a display responds instead of a
+    /// single address, taking the lowest responding address.
+    #[argh(switch)]
+    #[serde(default)]
+    pub any_address: bool,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
+}
+
+/// Read the sign database off a BS210 sign and write it to an IHEX file, e.g.
+/// as a backup before flashing, using an experimental read query.
+#[derive(FromArgs, Deserialize)]
+#[argh(subcommand, name = "dump")]
+pub struct Dump {
+    /// path of the IHEX file to write.
+    #[argh(positional)]
+    pub output: PathBuf,
+    /// IBIS address of the sign.
+    #[argh(option, short = 'a', from_str_fn(parse_address))]
+    pub address: u8,
+    /// number of bytes to read from the start of the database, e.g. 0x2000,
+    /// defaults to all 0x10000 bytes.
+    #[argh(option, default = "0x1_0000", from_str_fn(parse_dump_len))]
+    #[serde(default = "default_dump_len")]
+    pub len: u32,
+    /// seconds to wait for each read from the device, rather than for the
+    /// whole command, defaults to 3.
+    #[argh(option, default = "3")]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// serial port to use, e.g. /dev/ttyUSB0 on Linux, or COM5 on Windows,
+    /// picked from the available ports if omitted.
+    #[argh(option, short = 's')]
+    pub serial: Option<String>,
+}
+
+fn default_dump_len() -> u32 {
+    0x1_0000
+}
+
+/// Print the plans of a cycle configuration that would be shown at the given
+/// time, or now, without connecting to any device.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "active")]
+pub struct Active {
+    /// path to a YAML configuration file for cycle.
+    #[argh(positional)]
+    pub config: PathBuf,
+    /// time to check instead of now, e.g. 2021-09-09T20:30:00 in local time,
+    /// or 2021-09-09T20:30:00+02:00.
+    #[argh(option)]
+    pub at: Option<String>,
+}
+
+/// Calculate the IBIS parity byte or the BS210 record checksum of the given
+/// bytes, e.g. to check captured frames by hand.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "checksum")]
+pub struct Checksum {
+    /// bytes in hexadecimal notation, e.g. 61 30 0d. For IBIS telegrams,
+    /// include the terminating carriage return. For records, include the
+    /// leading length byte. Read from stdin if omitted.
+    #[argh(positional)]
+    pub bytes: Vec<String>,
+    /// only print the IBIS parity byte.
+    #[argh(switch)]
+    pub ibis: bool,
+    /// only print the BS210 record checksum.
+    #[argh(switch)]
+    pub record: bool,
 }
 
 #[cfg(test)]
@@ -132,12 +811,86 @@ mod test {
         match args.invocation {
             Invocation::Cycle(Cycle { plan, serial, .. }) => {
                 assert_eq!(plan, vec!["0".parse().unwrap(), "0-5".parse().unwrap()]);
-                assert_eq!(serial, expected_serial);
+                assert_eq!(serial.as_deref(), Some(expected_serial));
             }
             _ => panic!("unexpected subcommand"),
         }
     }
 
+    #[test]
+    fn interval_and_lookahead_short_flags() {
+        let args = ["cycle", "0", "-i", "8", "-l", "24"];
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &args).unwrap();
+        match args.invocation {
+            Invocation::Cycle(Cycle {
+                interval_secs,
+                lookahead,
+                ..
+            }) => {
+                assert_eq!(interval_secs, 8.0);
+                assert_eq!(lookahead, 24);
+            }
+            _ => panic!("unexpected subcommand"),
+        }
+    }
+
+    #[test]
+    fn out_of_range_numbers() {
+        for args in &[
+            &["line", "1000"][..],
+            &["destination", "0", "--line", "0"][..],
+            &["version", "--address", "16"][..],
+            &["flash", "db.hex", "--address", "0x10"][..],
+            &["cycle", "0", "--default-destination", "1000"][..],
+            &["destination", "1000"][..],
+            &["destination", "998-1000"][..],
+            &["destination", "70000"][..],
+            &["cycle", "1000"][..],
+            &["cycle", "0", "1000:5"][..],
+            &["cycle", "0-1000*5s"][..],
+        ] {
+            let early_exit = match TopLevel::from_args(&["ibisibi"], args) {
+                Ok(_) => panic!("expected {:?} to be refused", args),
+                Err(early_exit) => early_exit,
+            };
+            assert!(
+                early_exit.output.contains("out of range"),
+                "{}",
+                early_exit.output
+            );
+        }
+    }
+
+    #[test]
+    fn decimal_and_hex_addresses() {
+        for &(value, expected) in &[
+            ("10", 10),
+            ("0x0a", 10),
+            ("0XA", 10),
+            ("0x0", 0),
+            ("15", 15),
+        ] {
+            assert_eq!(parse_address(value), Ok(expected), "{}", value);
+        }
+        for value in &["0x", "0xg", "a", "-1", "0x100"] {
+            assert!(parse_address(value).is_err(), "{}", value);
+        }
+        let args = ["flash", "db.hex", "--address", "0x0f"];
+        match TopLevel::from_args(&["ibisibi"], &args).unwrap().invocation {
+            Invocation::Flash(Flash { address, .. }) => assert_eq!(address, Some(15)),
+            _ => panic!("unexpected subcommand"),
+        }
+    }
+
+    #[test]
+    fn base_address() {
+        assert_eq!(parse_base_address("256"), Ok(0x100));
+        assert_eq!(parse_base_address("0x100"), Ok(0x100));
+        assert_eq!(parse_base_address("0xFFFF"), Ok(0xFFFF));
+        assert!(parse_base_address("0x10000").is_err());
+        assert!(parse_base_address("-1").is_err());
+    }
+
     #[test]
     fn time_ranges() {
         let args = [
@@ -165,6 +918,49 @@ mod test {
         }
     }
 
+    #[test]
+    fn destination_range_with_interval() {
+        let args = [
+            "destination",
+            "1-3",
+            "--interval",
+            "1s",
+            "-s",
+            "/dev/ttyUSB0",
+        ];
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &args).unwrap();
+        match args.invocation {
+            Invocation::Destination(Destination {
+                index, interval, ..
+            }) => {
+                assert_eq!(index.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+                assert_eq!(
+                    interval.map(|i| i.as_duration()),
+                    Some(std::time::Duration::from_secs(1))
+                );
+            }
+            _ => panic!("unexpected subcommand"),
+        }
+    }
+
+    #[test]
+    fn destination_retry() {
+        let args = ["destination", "4", "--retry", "3", "--retry-delay", "500ms"];
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &args).unwrap();
+        match args.invocation {
+            Invocation::Destination(Destination {
+                retry, retry_delay, ..
+            }) => {
+                assert_eq!(retry, 3);
+                assert_eq!(
+                    retry_delay.map(|d| d.as_duration()),
+                    Some(std::time::Duration::from_millis(500))
+                );
+            }
+            _ => panic!("unexpected subcommand"),
+        }
+    }
+
     #[test]
     fn deserialize_cycle() {
         let invocation: Invocation =
@@ -175,20 +971,18 @@ mod test {
                 interval_secs,
                 lookahead,
                 serial,
+                ..
             }) => {
                 assert_eq!(
                     plan,
                     vec! {
-                        Plan::range("0"),
-                        Plan::range_start_end(
-                            "6",
-                            "2021-09-09T18:00:00/2021-09-10T00:00:00"
-                        )
+                        "0".parse::<Plan>().unwrap(),
+                        "6@2021-09-09T18:00:00/2021-09-10T00:00:00".parse().unwrap()
                     }
                 );
                 assert_eq!(interval_secs, 9.0);
                 assert_eq!(lookahead, 12);
-                assert_eq!(serial, "/dev/ttyUSB0");
+                assert_eq!(serial.as_deref(), Some("/dev/ttyUSB0"));
             }
             _ => panic!("Unexcpected invocation kind"),
         }
@@ -200,11 +994,63 @@ mod test {
             from_str(include_str!("../examples/destination.yaml")).unwrap();
         match invocation {
             Invocation::Destination(Destination {
-                index: 0,
+                index,
                 line: Some(6),
                 serial,
+                ..
             }) => {
-                assert_eq!(serial, "COM5");
+                assert_eq!(index, "0".parse().unwrap());
+                assert_eq!(serial.as_deref(), Some("COM5"));
+            }
+            _ => panic!("Unexcpected invocation kind"),
+        }
+    }
+
+    #[test]
+    fn log_level() {
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &["list"]).unwrap();
+        assert_eq!(args.log_level, Level::WARN);
+        let args: TopLevel =
+            argh::FromArgs::from_args(&["ibisibi"], &["-v", "debug", "list"]).unwrap();
+        assert_eq!(args.log_level, Level::DEBUG);
+    }
+
+    #[test]
+    fn scan_addresses() {
+        let addresses = |args: &[&str]| match TopLevel::from_args(&["ibisibi"], args)
+            .map(|args| args.invocation)
+        {
+            Ok(Invocation::Scan(Scan { addresses, .. })) => {
+                Ok(addresses.map(|range| range.to_string()))
+            }
+            Ok(_) => panic!("unexpected subcommand"),
+            Err(_) => Err(()),
+        };
+        assert_eq!(addresses(&["scan"]), Ok(None));
+        assert_eq!(
+            addresses(&["scan", "--addresses", "0-3"]),
+            Ok(Some("0-3".to_string()))
+        );
+        assert_eq!(
+            addresses(&["scan", "--addresses", "12-20"]),
+            Ok(Some("12-15".to_string()))
+        );
+        assert_eq!(addresses(&["scan", "--addresses", "16-20"]), Err(()));
+        assert_eq!(addresses(&["scan", "--addresses", "x"]), Err(()));
+    }
+
+    #[test]
+    fn scan_has_shorter_default_timeout() {
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &["scan"]).unwrap();
+        match args.invocation {
+            Invocation::Scan(Scan { timeout_secs, .. }) => assert_eq!(timeout_secs, 1),
+            _ => panic!("unexpected subcommand"),
+        }
+        let invocation: Invocation =
+            from_str(include_str!("../examples/destination.yaml")).unwrap();
+        match invocation {
+            Invocation::Destination(Destination { timeout_secs, .. }) => {
+                assert_eq!(timeout_secs, 3)
             }
             _ => panic!("Unexcpected invocation kind"),
         }
@@ -218,4 +1064,36 @@ mod test {
             _ => panic!("Unexcpected invocation kind"),
         }
     }
+
+    #[test]
+    fn deserialize_line() {
+        let invocation: Invocation = from_str(include_str!("../examples/line.yaml")).unwrap();
+        match invocation {
+            Invocation::Line(Line { line, serial, .. }) => {
+                assert_eq!(line, Range::single(6));
+                assert_eq!(serial.as_deref(), Some("COM5"));
+            }
+            _ => panic!("Unexcpected invocation kind"),
+        }
+    }
+
+    #[test]
+    fn blank_index() {
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &["blank"]).unwrap();
+        match args.invocation {
+            Invocation::Blank(Blank { blank_index, .. }) => assert_eq!(blank_index, 0),
+            _ => panic!("unexpected subcommand"),
+        }
+        let invocation: Invocation = from_str(include_str!("../examples/blank.yaml")).unwrap();
+        match invocation {
+            Invocation::Blank(Blank {
+                blank_index,
+                serial,
+            }) => {
+                assert_eq!(blank_index, 999);
+                assert_eq!(serial.as_deref(), Some("COM5"));
+            }
+            _ => panic!("Unexcpected invocation kind"),
+        }
+    }
 }
@@ -0,0 +1,96 @@
+//! Shows the destination that is agreed to be blank, e.g. to clear the signs
+//! at the end of service without having to remember its index.
+
+use crate::args::Blank;
+use crate::echo;
+use crate::error_kind::ErrorKind;
+use crate::port::{self, PortError};
+use crate::serial::{open, OpenRetry};
+use crate::telegram::{Telegram, TelegramError};
+use std::io::Write;
+use std::time::Duration;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, BlankError>;
+
+/// Nothing is read back after blanking, so this only limits how long writing
+/// may take.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+pub fn blank(opts: &Blank, retry: &OpenRetry) -> Result<()> {
+    // checked here too since configuration files skip the argument parsers
+    let telegram = Telegram::try_destination(opts.blank_index)?;
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial = open(&port, TIMEOUT, retry).map_err(|source| BlankError::Serial {
+        source,
+        port: port.clone(),
+    })?;
+    send_blank(&mut serial, &telegram).map_err(|source| BlankError::IO { source, port })
+}
+
+fn send_blank<W: Write + ?Sized>(serial: &mut W, telegram: &Telegram) -> std::io::Result<()> {
+    echo::telegram(telegram);
+    serial.write_all(telegram.as_bytes())?;
+    serial.flush()
+}
+
+#[derive(Error, Debug)]
+pub enum BlankError {
+    #[error("{0}")]
+    Telegram(#[from] TelegramError),
+    #[error("Could not send blank destination to port: {port}, due to I/O error: {source}")]
+    IO {
+        source: std::io::Error,
+        port: String,
+    },
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+}
+
+impl BlankError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            BlankError::Telegram(_) => ErrorKind::Usage,
+            BlankError::IO { source, .. } => ErrorKind::of_io(source),
+            BlankError::Serial { .. } => ErrorKind::Port,
+            BlankError::Port(error) => error.kind(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serial::{mock, Serial};
+
+    #[test]
+    fn sends_blank_index() {
+        let serial = Serial::builder()
+            .expect_write(Telegram::destination(999).as_bytes())
+            .build();
+        mock::register("/dev/ttyBlankMock", serial);
+        let opts = Blank {
+            blank_index: 999,
+            serial: Some("/dev/ttyBlankMock".to_string()),
+        };
+        blank(&opts, &OpenRetry::default()).unwrap();
+    }
+
+    #[test]
+    fn blank_index_out_of_range() {
+        let opts = Blank {
+            blank_index: 1000,
+            serial: None,
+        };
+        match blank(&opts, &OpenRetry::default()) {
+            Err(BlankError::Telegram(TelegramError::DestinationOutOfRange(1000))) => {}
+            other => panic!("Expected out of range, got: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Checks a configuration for values that only fail once the command runs,
+//! e.g. destination indexes that telegrams cannot encode, without opening a
+//! serial port.
+//!
+//! Command line arguments are already checked while parsing, but values read
+//! from YAML configuration files are not.
+
+use crate::args::{Cycle, Destination, Invocation};
+use crate::cycle::{self, CycleError};
+use crate::destination::{check_sendable, DestinationError};
+use crate::line::{check_lines, LineError};
+use crate::telegram::{Telegram, TelegramError};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, CheckError>;
+
+pub fn check(invocation: &Invocation) -> Result<()> {
+    match invocation {
+        Invocation::Destination(destination) => check_destination(destination),
+        Invocation::Cycle(cycle) => check_cycle(cycle),
+        Invocation::Line(line) => Ok(check_lines(&line.line)?),
+        Invocation::Blank(blank) => check_index(blank.blank_index),
+        Invocation::Flash(flash) => check_address(flash.address),
+        Invocation::Version(version) => check_address(Some(version.address)),
+        Invocation::Dump(dump) => check_address(Some(dump.address)),
+        Invocation::Status(status) => check_address(status.address),
+        _ => Ok(()),
+    }
+}
+
+fn check_destination(destination: &Destination) -> Result<()> {
+    check_sendable(&destination.index, destination.line)?;
+    check_address(destination.address)
+}
+
+fn check_cycle(cycle: &Cycle) -> Result<()> {
+    cycle::check_interval(cycle.interval_secs)?;
+    cycle::check_retry_interval(cycle.retry_interval_secs)?;
+    cycle::check_plan_sizes(&cycle.plan)?;
+    for destination in cycle.plan.iter().flat_map(|plan| plan.destinations()) {
+        check_sendable(destination.range(), destination.line())?;
+    }
+    for index in cycle.default_destination.iter().chain(&cycle.fallback) {
+        check_index(*index)?;
+    }
+    check_address(cycle.address)
+}
+
+fn check_index(index: u16) -> Result<()> {
+    Telegram::try_destination(index)?;
+    Ok(())
+}
+
+fn check_address(address: Option<u8>) -> Result<()> {
+    if let Some(address) = address {
+        Telegram::try_display_status(address)?;
+    }
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum CheckError {
+    #[error("{0}")]
+    Destination(#[from] DestinationError),
+    #[error("{0}")]
+    Telegram(#[from] TelegramError),
+    #[error("{0}")]
+    Cycle(#[from] CycleError),
+    #[error("{0}")]
+    Line(#[from] LineError),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_yaml::from_str;
+
+    fn check_yaml(yaml: &str) -> Result<()> {
+        check(&from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn examples_pass() {
+        check_yaml(include_str!("../examples/cycle.yaml")).unwrap();
+        check_yaml(include_str!("../examples/destination.yaml")).unwrap();
+        check_yaml(include_str!("../examples/line.yaml")).unwrap();
+        check_yaml(include_str!("../examples/blank.yaml")).unwrap();
+    }
+
+    #[test]
+    fn destination_beyond_999() {
+        match check_yaml("destination:\n  index: 990-1000\n") {
+            Err(CheckError::Destination(DestinationError::OutOfRange(range))) => {
+                assert_eq!(range.to_string(), "990-1000")
+            }
+            other => panic!("Expected range error, got: {:?}", other),
+        }
+        // the range is checked at both ends
+        check_yaml("destination:\n  index: 1000-990\n").unwrap_err();
+    }
+
+    #[test]
+    fn destination_line_out_of_range() {
+        match check_yaml("destination:\n  index: 0\n  line: 1000\n") {
+            Err(CheckError::Destination(DestinationError::Telegram(
+                TelegramError::LineOutOfRange(1000),
+            ))) => {}
+            other => panic!("Expected line error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycle_address_out_of_range() {
+        match check_yaml(
+            "cycle:
+  plan: [{destinations: [\"0\"]}]
+  interval_secs: 5
+  lookahead: 12
+  address: 16
+",
+        ) {
+            Err(CheckError::Telegram(TelegramError::AddressOutOfRange(16))) => {}
+            other => panic!("Expected address error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycle_plan_beyond_999() {
+        match check_yaml(
+            "cycle:
+  plan: [{destinations: [\"6:0-3\", \"12:998-1001\"]}]
+  interval_secs: 5
+  lookahead: 12
+",
+        ) {
+            Err(CheckError::Destination(DestinationError::OutOfRange(_))) => {}
+            other => panic!("Expected range error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycle_interval_too_short() {
+        match check_yaml(
+            "cycle:
+  plan: [{destinations: [\"0\"]}]
+  interval_secs: 0
+  lookahead: 12
+",
+        ) {
+            Err(CheckError::Cycle(CycleError::IntervalTooShort(_))) => {}
+            other => panic!("Expected interval error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycle_interval_too_long() {
+        match check_yaml(
+            "cycle:
+  plan: [{destinations: [\"0\"]}]
+  interval_secs: 1e30
+  lookahead: 12
+",
+        ) {
+            Err(CheckError::Cycle(CycleError::IntervalTooLong(_))) => {}
+            other => panic!("Expected interval error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycle_retry_interval_zero() {
+        match check_yaml(
+            "cycle:
+  plan: [{destinations: [\"0\"]}]
+  interval_secs: 5
+  retry_interval_secs: 0
+  lookahead: 12
+",
+        ) {
+            Err(CheckError::Cycle(CycleError::RetryIntervalZero)) => {}
+            other => panic!("Expected retry interval error, got: {:?}", other),
+        }
+    }
+}
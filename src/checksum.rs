@@ -0,0 +1,75 @@
+use crate::args::Checksum;
+use crate::error_kind::ErrorKind;
+use crate::hex::{format_hex, parse_hex, ParseHexError};
+use crate::parity::parity_byte;
+use crate::record;
+use std::io::{self, stdin, Read};
+use thiserror::Error;
+
+pub fn checksum(opts: Checksum) -> Result<(), ChecksumError> {
+    let bytes = parse_hex(&input(&opts.bytes, stdin())?)?;
+    let both = !opts.ibis && !opts.record;
+    if opts.ibis || both {
+        println!(
+            "IBIS parity:     {} [{:02X}]",
+            format_hex(&bytes),
+            parity_byte(&bytes)
+        );
+    }
+    if opts.record || both {
+        println!(
+            "Record checksum: {} [{:02X}]",
+            format_hex(&bytes),
+            record::checksum(&bytes)
+        );
+    }
+    Ok(())
+}
+
+/// Gets the hex bytes from the arguments or, if there are none, from stdin,
+/// e.g. to pipe in bytes copied from a capture.
+fn input<R: Read>(args: &[String], mut stdin: R) -> io::Result<String> {
+    if !args.is_empty() {
+        return Ok(args.join(" "));
+    }
+    let mut input = String::new();
+    stdin.read_to_string(&mut input)?;
+    Ok(input)
+}
+
+#[derive(Error, Debug)]
+pub enum ChecksumError {
+    #[error("{0}")]
+    Hex(#[from] ParseHexError),
+    #[error("Could not read bytes from stdin, error: {0}")]
+    Stdin(#[from] io::Error),
+}
+
+impl ChecksumError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ChecksumError::Hex(_) => ErrorKind::Usage,
+            ChecksumError::Stdin(_) => ErrorKind::Io,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn input_from_args() {
+        let args = vec!["61".to_string(), "30 0d".to_string()];
+        let input = input(&args, &b"ff"[..]).unwrap();
+        assert_eq!(parse_hex(&input).unwrap(), vec![0x61, 0x30, 0x0d]);
+    }
+
+    #[test]
+    fn input_from_stdin() {
+        let input = input(&[], &b"61 30\n0d\n"[..]).unwrap();
+        assert_eq!(parse_hex(&input).unwrap(), vec![0x61, 0x30, 0x0d]);
+        assert_eq!(parity_byte(&parse_hex(&input).unwrap()), 0x23);
+    }
+}
@@ -0,0 +1,164 @@
+//! Analysis of which parts of a sign database in `.hex` format are covered by
+//! data records, to catch truncated or mis-generated databases before
+//! flashing them.
+//!
+//! Databases we have seen put each data record at the start of a chunk of
+//! [`CHUNK_LEN`] bytes, even if the record is shorter than that. A chunk
+//! without a record below the highest record address would be left undefined
+//! on the sign after flashing, so it is reported as missing.
+//!
+//! The header at the start of a database presumably declares the panel
+//! geometry, but its layout is not understood well enough to compare the
+//! coverage against it. Only chunks missing in between records can be
+//! detected for now, not chunks missing at the end of the database.
+
+use ihex::{Reader, ReaderError, Record};
+use std::convert::TryFrom;
+
+/// Size of the chunks that data records are aligned to.
+const CHUNK_LEN: u16 = 0x20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Coverage {
+    /// Address right after the last byte of the last data record.
+    end: u32,
+    /// Start addresses of chunks that are not covered by any data record.
+    missing_chunks: Vec<u16>,
+}
+
+impl Coverage {
+    pub fn analyze(db: &str) -> Result<Coverage, ReaderError> {
+        let mut chunk_addresses = vec![];
+        let mut end = 0;
+        // added to record addresses by extended address records
+        let mut base = 0_u32;
+        for record in Reader::new(db) {
+            match record? {
+                Record::Data { value, .. } if value.is_empty() => {}
+                Record::Data { offset, value } => {
+                    let address = base + offset as u32;
+                    // records beyond 16 bits are rejected when flashing
+                    if let Ok(address) = u16::try_from(address) {
+                        chunk_addresses.push(address - address % CHUNK_LEN);
+                        end = end.max(address as u32 + value.len() as u32);
+                    }
+                }
+                Record::ExtendedSegmentAddress(segment) => base = (segment as u32) << 4,
+                Record::ExtendedLinearAddress(upper) => base = (upper as u32) << 16,
+                _ => {}
+            }
+        }
+        chunk_addresses.sort_unstable();
+        chunk_addresses.dedup();
+
+        let mut missing_chunks = vec![];
+        // wider than the addresses, since the chunk after the last one at
+        // 0xFFE0 starts beyond 16 bits
+        let mut expected = 0_u32;
+        for address in chunk_addresses {
+            while expected < address as u32 {
+                missing_chunks.push(expected as u16);
+                expected += CHUNK_LEN as u32;
+            }
+            expected = address as u32 + CHUNK_LEN as u32;
+        }
+
+        Ok(Coverage {
+            end,
+            missing_chunks,
+        })
+    }
+
+    pub fn end(&self) -> u32 {
+        self.end
+    }
+
+    pub fn missing_chunks(&self) -> &[u16] {
+        &self.missing_chunks[..]
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing_chunks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn complete() {
+        let coverage = Coverage::analyze(
+            ":02000000AABB99
+:100040004F00004F0000000000000000000000FF13
+:02002000CCDD35
+:00000001FF
+",
+        )
+        .unwrap();
+        assert!(coverage.is_complete());
+        assert_eq!(coverage.end(), 0x50);
+    }
+
+    #[test]
+    fn missing_chunks_in_between() {
+        let coverage = Coverage::analyze(
+            ":02000000AABB99
+:02006000CCDDF5
+:00000001FF
+",
+        )
+        .unwrap();
+        assert!(!coverage.is_complete());
+        assert_eq!(coverage.missing_chunks(), &[0x20, 0x40]);
+        assert_eq!(coverage.end(), 0x62);
+    }
+
+    #[test]
+    fn record_in_last_chunk() {
+        let coverage = Coverage::analyze(
+            ":02000000AABB99
+:02FFE000CCDD76
+:00000001FF
+",
+        )
+        .unwrap();
+        assert_eq!(coverage.missing_chunks().len(), 0xFFE0 / 0x20 - 1);
+        assert_eq!(coverage.missing_chunks().last(), Some(&0xFFC0));
+        assert_eq!(coverage.end(), 0xFFE2);
+    }
+
+    #[test]
+    fn complete_up_to_last_chunk() {
+        let db: String = (0..=0xFFFF_u32)
+            .step_by(CHUNK_LEN as usize)
+            .map(|address| {
+                Record::Data {
+                    offset: address as u16,
+                    value: vec![0xAA],
+                }
+                .to_record_string()
+                .unwrap()
+                    + "\n"
+            })
+            .collect();
+        let coverage = Coverage::analyze(&db).unwrap();
+        assert!(coverage.is_complete());
+        assert_eq!(coverage.end(), 0xFFE1);
+    }
+
+    #[test]
+    fn extended_segment_address() {
+        // second record at segment 2, i.e. 0x20
+        let coverage = Coverage::analyze(
+            ":02000000AABB99
+:020000020002FA
+:02000000CCDD55
+:00000001FF
+",
+        )
+        .unwrap();
+        assert!(coverage.is_complete());
+        assert_eq!(coverage.end(), 0x22);
+    }
+}
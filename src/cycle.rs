@@ -1,87 +1,743 @@
 use crate::args::{Cycle, Destination};
-use crate::destination::{destination, DestinationError};
+use crate::destination::{check_address, check_sendable, destination_cached, DestinationError};
+use crate::error_kind::ErrorKind;
 use crate::plan::Plan;
+use crate::port::{self, PortError};
+use crate::preview::preview;
+use crate::range::Range;
+use crate::serial::OpenRetry;
+use crate::shuffle::Shuffler;
+use crate::shutdown::{self, sleep};
 use crate::slot::Slot;
-use chrono::{Duration as ChronoDuration, Local};
-use std::thread::sleep;
-use std::time::Duration;
+use crate::status::StatusCache;
+use chrono::{DateTime, Duration as ChronoDuration, Local, TimeZone};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 type Result<T> = std::result::Result<T, CycleError>;
 
-const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// Most destinations a single plan may expand to, which is the number of
+/// valid destination indexes.
+const MAX_PLAN_DESTINATIONS: usize = 1000;
 
-pub fn cycle(options: &Cycle) -> Result<()> {
-    assert!(options.interval_secs > 1.0, "Expected at least 1s delay");
+/// Shortest interval between destinations, so that a typo in the interval
+/// does not flood the bus or keep the CPU busy.
+const MIN_INTERVAL_SECS: f64 = 0.1;
+
+/// Cycles through the plans until stopped with Ctrl-C or SIGTERM, or until
+/// the configured run time is up, which takes effect before the next
+/// destination is sent.
+pub fn cycle(options: &Cycle, retry: &OpenRetry) -> Result<()> {
+    let sleep_duration = check_interval(options.interval_secs)?;
+    let retry_interval = check_retry_interval(options.retry_interval_secs)?;
     assert!(
         !options.plan.is_empty(),
         "Expected at least one destination index"
     );
+    check_plan_sizes(&options.plan)?;
+    for destination in options.plan.iter().flat_map(|plan| plan.destinations()) {
+        check_sendable(destination.range(), destination.line())?;
+    }
+    check_address(options.address)?;
+    if let Some(hours) = options.preview {
+        preview(options, hours);
+        return Ok(());
+    }
 
-    let sleep_duration = Duration::from_secs_f64(options.interval_secs);
     let lookahead = ChronoDuration::hours(options.lookahead as i64);
-    loop {
-        let active_count = options
-            .plan
-            .iter()
-            .filter(|plan| is_active(plan.slots(), lookahead))
-            .map(|plan| execute(plan, &options.serial, sleep_duration))
-            .count();
-        if active_count == 0 {
-            eprintln!(
-                "nothing to show at the moment, retry after {interval:?}",
-                interval = RETRY_INTERVAL
-            );
-            sleep(RETRY_INTERVAL);
+    let status_ttl = options
+        .status_ttl
+        .map(|ttl| ttl.as_duration())
+        .unwrap_or(Duration::from_secs(0));
+    let mut status_cache = StatusCache::new(status_ttl);
+    let mut last_sent = LastSent::new(options);
+    // pick the port once rather than before every destination
+    let port = port::resolve(options.serial.as_deref())?;
+    let mut shuffler = options.seed.map_or_else(Shuffler::from_time, Shuffler::new);
+    // a run time too long to represent as an instant never ends
+    let deadline = options
+        .run_for
+        .and_then(|run_for| Instant::now().checked_add(run_for.as_duration()));
+    // the next activation reported while nothing is active, so that it is
+    // not repeated every few seconds
+    let mut idle_reported = None;
+    // whether the fallback destination was shown since the last active plan,
+    // so that it is sent once per idle period
+    let mut fallback_shown = false;
+    shutdown::install();
+    while !should_stop(deadline) {
+        if options.shuffle {
+            // shuffling needs all destinations of the pass up front, so check
+            // all slots at the start of the pass
+            let now = Local::now();
+            let mut entries: Vec<Entry> = options
+                .plan
+                .iter()
+                .filter(|plan| is_active(plan.slots(), lookahead, &now))
+                .flat_map(|plan| entries(plan, sleep_duration))
+                .collect();
+            shuffler.shuffle(&mut entries);
+            if !entries.is_empty() {
+                execute(
+                    entries,
+                    options,
+                    &port,
+                    retry,
+                    deadline,
+                    &mut status_cache,
+                    &mut last_sent,
+                )?;
+                idle_reported = None;
+                fallback_shown = false;
+                continue;
+            }
+        } else {
+            let mut active_count = 0;
+            for plan in options
+                .plan
+                .iter()
+                .filter(|plan| is_active(plan.slots(), lookahead, &Local::now()))
+            {
+                execute(
+                    entries(plan, sleep_duration),
+                    options,
+                    &port,
+                    retry,
+                    deadline,
+                    &mut status_cache,
+                    &mut last_sent,
+                )?;
+                active_count += 1;
+            }
+            if active_count > 0 {
+                idle_reported = None;
+                fallback_shown = false;
+                continue;
+            }
+        }
+        let next = next_activation(&options.plan, lookahead, &Local::now());
+        if idle_reported != Some(next) {
+            match next {
+                Some(next) => eprintln!(
+                    "nothing to show at the moment, next plan starts showing at {}",
+                    next
+                ),
+                None => {
+                    eprintln!("nothing to show at the moment, and no plan starts showing later")
+                }
+            }
+            idle_reported = Some(next);
+        }
+        if let (Some(index), false) = (options.fallback, fallback_shown) {
+            let destination_args = destination_args(options, &port, None, usize::from(index));
+            match destination_cached(&destination_args, retry, &mut status_cache) {
+                Ok(()) => {
+                    eprintln!("showing fallback destination {}", index);
+                    last_sent.sent(None, usize::from(index), Instant::now());
+                    fallback_shown = true;
+                }
+                Err(err) if !is_worth_retrying(&err, &last_sent) => {
+                    return Err(CycleError::Fatal {
+                        index: usize::from(index),
+                        source: err,
+                    })
+                }
+                // try again after the retry interval if still idle then
+                Err(err) => eprintln!(
+                    "error: could not switch to fallback destination {dest}, reason: {reason}, retry after {interval:?}",
+                    dest = index,
+                    reason = err,
+                    interval = retry_interval
+                ),
+            }
+        }
+        sleep(retry_interval);
+    }
+
+    match options.default_destination {
+        Some(index) => {
+            eprintln!("stopping, showing default destination {}", index);
+            let destination_args = destination_args(options, &port, None, usize::from(index));
+            destination_cached(&destination_args, retry, &mut status_cache)?;
+        }
+        None => eprintln!("stopping"),
+    }
+    Ok(())
+}
+
+/// Checks whether shutdown was requested or the deadline for the cycle, if
+/// any, has passed.
+fn should_stop(deadline: Option<Instant>) -> bool {
+    shutdown::requested() || deadline.iter().any(|&deadline| Instant::now() >= deadline)
+}
+
+/// Line, index and time to show a single destination of a plan.
+type Entry = (Option<u16>, usize, Duration);
+
+/// Lists the destinations of a plan in order, each with the time to show it.
+fn entries(plan: &Plan, sleep_duration: Duration) -> impl Iterator<Item = Entry> + '_ {
+    plan.destinations().iter().flat_map(move |d| {
+        let dwell = d
+            .dwell()
+            .map_or(sleep_duration, |dwell| dwell.as_duration());
+        d.range().iter().map(move |index| (d.line(), index, dwell))
+    })
+}
+
+/// Line and index of the destination sent last, so that `--skip-unchanged`
+/// can leave the sign alone if it already shows the next destination.
+///
+/// A cycle sends to a single address, so there is only one destination to
+/// keep track of.
+#[derive(Debug)]
+struct LastSent {
+    skip_unchanged: bool,
+    force_refresh: Option<Duration>,
+    sent: Option<(Option<u16>, usize, Instant)>,
+}
+
+impl LastSent {
+    fn new(options: &Cycle) -> Self {
+        LastSent {
+            skip_unchanged: options.skip_unchanged,
+            force_refresh: options.force_refresh.map(|t| t.as_duration()),
+            sent: None,
+        }
+    }
+
+    /// Checks whether the destination was sent last and, with a forced
+    /// refresh, less than the refresh interval before `now`.
+    fn is_shown(&self, line: Option<u16>, index: usize, now: Instant) -> bool {
+        match self.sent {
+            Some((sent_line, sent_index, sent_at))
+                if self.skip_unchanged && sent_line == line && sent_index == index =>
+            {
+                self.force_refresh
+                    .iter()
+                    .all(|&refresh| now.saturating_duration_since(sent_at) < refresh)
+            }
+            _ => false,
         }
     }
+
+    fn sent(&mut self, line: Option<u16>, index: usize, now: Instant) {
+        self.sent = Some((line, index, now));
+    }
+
+    fn any_sent(&self) -> bool {
+        self.sent.is_some()
+    }
 }
 
-/// Checks whether the given plan element applies at the current point
-/// in time, executes the plan, and returns whether or not it had applied.
+/// Checks whether to keep trying to send a destination after the error.
+///
+/// A missing port is worth waiting for if destinations were sent over it
+/// before, since the adapter was probably unplugged, but not if the cycle
+/// never got to send anything, since the port is probably mistyped.
+fn is_worth_retrying(error: &DestinationError, last_sent: &LastSent) -> bool {
+    match error {
+        DestinationError::Serial { .. } if last_sent.any_sent() => true,
+        error => error.is_recoverable(),
+    }
+}
+
+/// Shows the given destinations one after another.
 ///
 /// When errors occur, e.g. serial port disconnection, then retries until
-/// successful execution.
-fn execute(plan: &Plan, serial: &str, sleep_duration: Duration) {
-    let line = plan.line();
-    let destinations = plan.destinations().iter().flat_map(|r| r.iter());
-
-    for destination_index in destinations {
-        let destination_args = Destination {
-            index: destination_index as u16,
-            line,
-            serial: serial.to_string(),
-        };
-        while let Err(err) = destination(&destination_args) {
+/// successful execution or until shutdown is requested, unless retrying
+/// cannot help, e.g. because the port never existed.
+fn execute(
+    destinations: impl IntoIterator<Item = Entry>,
+    options: &Cycle,
+    port: &str,
+    retry: &OpenRetry,
+    deadline: Option<Instant>,
+    status_cache: &mut StatusCache,
+    last_sent: &mut LastSent,
+) -> Result<()> {
+    let retry_interval = Duration::from_secs(options.retry_interval_secs);
+    for (line, destination_index, dwell) in destinations {
+        if should_stop(deadline) {
+            return Ok(());
+        }
+        if last_sent.is_shown(line, destination_index, Instant::now()) {
+            sleep(dwell);
+            continue;
+        }
+        let destination_args = destination_args(options, port, line, destination_index);
+        while let Err(err) = destination_cached(&destination_args, retry, status_cache) {
+            if !is_worth_retrying(&err, last_sent) {
+                return Err(CycleError::Fatal {
+                    index: destination_index,
+                    source: err,
+                });
+            }
             eprintln!(
                 "error: could not switch to destination {dest}, reason: {reason}, retry after {interval:?}",
                 dest = destination_index,
                 reason = err,
-                interval = RETRY_INTERVAL
+                interval = retry_interval
             );
-            sleep(RETRY_INTERVAL);
+            if sleep(retry_interval) {
+                return Ok(());
+            }
         }
-        sleep(sleep_duration);
+        last_sent.sent(line, destination_index, Instant::now());
+        sleep(dwell);
+    }
+    Ok(())
+}
+
+/// Arguments to show a single destination of a cycle.
+fn destination_args(options: &Cycle, port: &str, line: Option<u16>, index: usize) -> Destination {
+    Destination {
+        index: Range::single(index),
+        line,
+        interval: None,
+        address: options.address,
+        group: None,
+        groups: None,
+        all_addresses: false,
+        status_gated: options.status_gated,
+        verify: false,
+        timeout_secs: options.timeout_secs,
+        // the cycle retries on its own
+        retry: 0,
+        retry_delay: None,
+        output: None,
+        serial: Some(port.to_string()),
     }
 }
 
-fn is_active(slots: &[Slot], lookahead: ChronoDuration) -> bool {
+/// Checks whether a plan with the given slots should be shown at `now`,
+/// either because it has no slots or because one of its slots is currently
+/// happening or starts within the lookahead.
+///
+/// Slot times without an offset are taken to be in the time zone of `now`.
+pub fn is_active<Tz: TimeZone>(
+    slots: &[Slot],
+    lookahead: ChronoDuration,
+    now: &DateTime<Tz>,
+) -> bool {
     if slots.is_empty() {
         return true; // no slots defined means show always
     }
 
-    let now = Local::now().naive_local();
-    let soonest_to_show = now + lookahead;
-    slots.iter().any(|slot| {
-        // cease to show events when already over
-        now < slot.end()
-                // show when currently happening or within lookahead
-                && soonest_to_show > slot.start()
-    })
+    slots.iter().any(|slot| slot.is_active(lookahead, now))
+}
+
+/// Finds when the first of the plans that are not active at `now` becomes
+/// active, counting the lookahead.
+fn next_activation<Tz: TimeZone>(
+    plans: &[Plan],
+    lookahead: ChronoDuration,
+    now: &DateTime<Tz>,
+) -> Option<DateTime<Tz>> {
+    plans
+        .iter()
+        .flat_map(|plan| plan.slots())
+        .filter_map(|slot| slot.next_activation(lookahead, now))
+        .min()
+}
+
+/// Refuses intervals that are too short to be useful, too long to represent,
+/// or not a number of seconds at all.
+pub fn check_interval(interval_secs: f64) -> Result<Duration> {
+    // also catches NaN, which compares false with anything
+    if interval_secs >= MIN_INTERVAL_SECS && interval_secs.is_finite() {
+        Duration::try_from_secs_f64(interval_secs)
+            .map_err(|_| CycleError::IntervalTooLong(interval_secs))
+    } else {
+        Err(CycleError::IntervalTooShort(interval_secs))
+    }
+}
+
+/// Refuses a retry interval of zero, which would keep retrying a
+/// disconnected adapter without pause.
+pub fn check_retry_interval(retry_interval_secs: u64) -> Result<Duration> {
+    if retry_interval_secs == 0 {
+        Err(CycleError::RetryIntervalZero)
+    } else {
+        Ok(Duration::from_secs(retry_interval_secs))
+    }
+}
+
+/// Refuses plans that expand to more destinations than there are valid
+/// indexes, e.g. a mistyped `0-999999`, before they flood the bus.
+pub fn check_plan_sizes(plans: &[Plan]) -> Result<()> {
+    for plan in plans {
+        let count: usize = plan.destinations().iter().map(|d| d.range().len()).sum();
+        if count > MAX_PLAN_DESTINATIONS {
+            return Err(CycleError::TooManyDestinations {
+                plan: plan.to_string(),
+                count,
+            });
+        }
+    }
+    Ok(())
 }
 
 #[derive(Error, Debug)]
 pub enum CycleError {
+    #[error("Plan {plan} expands to {count} destinations, but at most {max} are allowed", max = MAX_PLAN_DESTINATIONS)]
+    TooManyDestinations { plan: String, count: usize },
+    #[error("Interval of {0}s is too short, expected at least {min}s", min = MIN_INTERVAL_SECS)]
+    IntervalTooShort(f64),
+    #[error("Interval of {0}s is too long")]
+    IntervalTooLong(f64),
+    #[error("Retry interval must be at least 1s")]
+    RetryIntervalZero,
     #[error("{0}")]
     Destination(#[from] DestinationError),
+    #[error("Could not switch to destination {index}, giving up since retrying would not help: {source}")]
+    Fatal {
+        index: usize,
+        source: DestinationError,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+}
+
+impl CycleError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CycleError::TooManyDestinations { .. }
+            | CycleError::IntervalTooShort(_)
+            | CycleError::IntervalTooLong(_)
+            | CycleError::RetryIntervalZero => ErrorKind::Usage,
+            CycleError::Destination(error) => error.kind(),
+            CycleError::Fatal { source, .. } => source.kind(),
+            CycleError::Port(error) => error.kind(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serial::{mock, Serial};
+    use crate::telegram::Telegram;
+    use chrono::Utc;
+
+    fn slots() -> Vec<Slot> {
+        vec!["2021-09-09T18:00:00/2021-09-10T00:00:00".parse().unwrap()]
+    }
+
+    fn at(date_time: &str) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&date_time.parse().unwrap())
+    }
+
+    fn cycle_args(args: &[&str]) -> Cycle {
+        argh::FromArgs::from_args(&["cycle"], args).unwrap()
+    }
+
+    #[test]
+    fn run_for_then_default_destination() {
+        // the port is opened again for each destination
+        mock::register(
+            "/dev/ttyCycleMock",
+            Serial::builder()
+                .expect_write(Telegram::destination(1).as_bytes())
+                .build(),
+        );
+        mock::register(
+            "/dev/ttyCycleMock",
+            Serial::builder()
+                .expect_write(Telegram::destination(999).as_bytes())
+                .build(),
+        );
+        let options = cycle_args(&[
+            "1",
+            "--interval-secs",
+            "0.1",
+            "--run-for",
+            "50ms",
+            "--default-destination",
+            "999",
+            "--serial",
+            "/dev/ttyCycleMock",
+        ]);
+
+        cycle(&options, &OpenRetry::default()).unwrap();
+        assert_eq!(mock::registered("/dev/ttyCycleMock"), 0);
+    }
+
+    #[test]
+    fn skip_unchanged_sends_once() {
+        // a second mock would be taken if the destination was sent again
+        for _ in 0..2 {
+            mock::register(
+                "/dev/ttyCycleSkipMock",
+                Serial::builder()
+                    .expect_write(Telegram::destination(1).as_bytes())
+                    .build(),
+            );
+        }
+        let options = cycle_args(&[
+            "1",
+            "--interval-secs",
+            "0.1",
+            "--run-for",
+            "350ms",
+            "--skip-unchanged",
+            "--serial",
+            "/dev/ttyCycleSkipMock",
+        ]);
+
+        cycle(&options, &OpenRetry::default()).unwrap();
+        assert_eq!(mock::registered("/dev/ttyCycleSkipMock"), 1);
+    }
+
+    #[test]
+    fn fallback_once_while_idle() {
+        // a second mock would be taken if the fallback was sent again
+        for _ in 0..2 {
+            mock::register(
+                "/dev/ttyCycleFallbackMock",
+                Serial::builder()
+                    .expect_write(Telegram::destination(999).as_bytes())
+                    .build(),
+            );
+        }
+        let options = cycle_args(&[
+            "1@2000-01-01T00:00:00/2000-01-01T01:00:00",
+            "--fallback",
+            "999",
+            "--retry-interval-secs",
+            "1",
+            "--run-for",
+            "1500ms",
+            "--serial",
+            "/dev/ttyCycleFallbackMock",
+        ]);
+
+        cycle(&options, &OpenRetry::default()).unwrap();
+        assert_eq!(mock::registered("/dev/ttyCycleFallbackMock"), 1);
+    }
+
+    #[test]
+    fn missing_port_aborts() {
+        let options = cycle_args(&[
+            "1",
+            "--interval-secs",
+            "0.1",
+            "--run-for",
+            "1s",
+            "--serial",
+            "/dev/ttyCycleTypo",
+        ]);
+
+        match cycle(&options, &OpenRetry::default()) {
+            Err(CycleError::Fatal { index: 1, .. }) => {}
+            other => panic!("Expected to give up, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_port_retried_after_sending() {
+        let missing = DestinationError::Serial {
+            source: serialport::Error::new(serialport::ErrorKind::NoDevice, "unplugged"),
+            port: "/dev/ttyUSB0".to_string(),
+        };
+        let mut last_sent = LastSent::new(&cycle_args(&["1"]));
+        assert!(!is_worth_retrying(&missing, &last_sent));
+        last_sent.sent(None, 1, Instant::now());
+        assert!(is_worth_retrying(&missing, &last_sent));
+        assert!(!is_worth_retrying(
+            &DestinationError::StatusGateWithoutAddress,
+            &last_sent
+        ));
+    }
+
+    #[test]
+    fn last_sent_refresh() {
+        let mut options = cycle_args(&["1", "--skip-unchanged", "--force-refresh", "10s"]);
+        let start = Instant::now();
+        let mut last_sent = LastSent::new(&options);
+        assert!(!last_sent.is_shown(None, 1, start));
+
+        last_sent.sent(None, 1, start);
+        assert!(last_sent.is_shown(None, 1, start + Duration::from_secs(9)));
+        assert!(!last_sent.is_shown(Some(6), 1, start));
+        assert!(!last_sent.is_shown(None, 2, start));
+        assert!(!last_sent.is_shown(None, 1, start + Duration::from_secs(10)));
+
+        options.skip_unchanged = false;
+        let mut last_sent = LastSent::new(&options);
+        last_sent.sent(None, 1, start);
+        assert!(!last_sent.is_shown(None, 1, start));
+    }
+
+    #[test]
+    fn retry_interval() {
+        let options = cycle_args(&["1", "--retry-interval-secs", "30"]);
+        assert_eq!(
+            check_retry_interval(options.retry_interval_secs).unwrap(),
+            Duration::from_secs(30)
+        );
+        assert_eq!(cycle_args(&["1"]).retry_interval_secs, 5);
+        match check_retry_interval(0) {
+            Err(CycleError::RetryIntervalZero) => {}
+            other => panic!("Expected retry interval error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn interval_below_one_second() {
+        assert_eq!(check_interval(0.5).unwrap(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn interval_too_short() {
+        for &secs in &[0.0, 0.05, -1.0, f64::NAN, f64::INFINITY] {
+            match check_interval(secs) {
+                Err(CycleError::IntervalTooShort(_)) => {}
+                other => panic!(
+                    "Expected interval of {}s to be refused, got: {:?}",
+                    secs, other
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn interval_too_long() {
+        match check_interval(1e30) {
+            Err(CycleError::IntervalTooLong(_)) => {}
+            other => panic!("Expected interval of 1e30s to be refused, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn plan_sizes_within_limit() {
+        let plans = vec!["0-999".parse().unwrap(), "6:7-10".parse().unwrap()];
+        check_plan_sizes(&plans).unwrap();
+    }
+
+    #[test]
+    fn oversized_plan() {
+        let plans = vec!["0".parse().unwrap(), "0-999999".parse().unwrap()];
+        match check_plan_sizes(&plans).unwrap_err() {
+            CycleError::TooManyDestinations { plan, count } => {
+                assert_eq!(plan, "0-999999");
+                assert_eq!(count, 1_000_000);
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn refuses_unsendable_plan_before_opening_port() {
+        // plans from configuration files are not checked while parsing
+        let mut options = cycle_args(&["0", "--serial", "/dev/ttyUnregisteredMock"]);
+        options.plan = vec!["0".parse().unwrap(), "1000:5".parse().unwrap()];
+        match cycle(&options, &OpenRetry::default()).unwrap_err() {
+            CycleError::Destination(DestinationError::Telegram(_)) => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+        options.plan = vec!["0".parse().unwrap(), "998-1000".parse().unwrap()];
+        match cycle(&options, &OpenRetry::default()).unwrap_err() {
+            CycleError::Destination(DestinationError::OutOfRange(_)) => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn refuses_address_out_of_range_before_opening_port() {
+        // addresses from configuration files are not checked while parsing
+        let mut options = cycle_args(&["0", "--serial", "/dev/ttyUnregisteredMock"]);
+        options.address = Some(20);
+        options.status_gated = true;
+        match cycle(&options, &OpenRetry::default()).unwrap_err() {
+            CycleError::Destination(DestinationError::Telegram(_)) => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn active_without_slots() {
+        assert!(is_active(
+            &[],
+            ChronoDuration::hours(0),
+            &at("2000-01-01T00:00:00")
+        ));
+    }
+
+    #[test]
+    fn active_during_slot() {
+        assert!(is_active(
+            &slots(),
+            ChronoDuration::hours(0),
+            &at("2021-09-09T20:30:00")
+        ));
+    }
+
+    #[test]
+    fn active_within_lookahead() {
+        assert!(is_active(
+            &slots(),
+            ChronoDuration::hours(3),
+            &at("2021-09-09T16:00:00")
+        ));
+        assert!(!is_active(
+            &slots(),
+            ChronoDuration::hours(1),
+            &at("2021-09-09T16:00:00")
+        ));
+    }
+
+    #[test]
+    fn active_across_dst_change() {
+        // clocks in central Europe go back from 03:00 CEST to 02:00 CET, so
+        // this slot lasts 2.5 hours even though the wall clock only moves
+        // 1.5 hours ahead
+        let slots: Vec<Slot> = vec!["2021-10-31T01:00:00+02:00/2021-10-31T02:30:00+01:00"
+            .parse()
+            .unwrap()];
+        let at = |time| DateTime::parse_from_rfc3339(time).unwrap();
+        // the first 02:45, before clocks go back, is still within the slot
+        assert!(is_active(
+            &slots,
+            ChronoDuration::hours(0),
+            &at("2021-10-31T02:45:00+02:00")
+        ));
+        assert!(is_active(
+            &slots,
+            ChronoDuration::hours(0),
+            &at("2021-10-31T02:15:00+01:00")
+        ));
+        // the second 02:45 is after it
+        assert!(!is_active(
+            &slots,
+            ChronoDuration::hours(0),
+            &at("2021-10-31T02:45:00+01:00")
+        ));
+    }
+
+    #[test]
+    fn next_activation_of_plans() {
+        let plans: Vec<Plan> = vec![
+            "1@2021-09-09T18:00:00/2021-09-10T00:00:00".parse().unwrap(),
+            "2@2021-09-09T15:00:00/2021-09-09T16:00:00".parse().unwrap(),
+            "3@2021-09-09T08:00:00/2021-09-09T09:00:00".parse().unwrap(),
+        ];
+        assert_eq!(
+            next_activation(&plans, ChronoDuration::hours(1), &at("2021-09-09T12:00:00")),
+            Some(at("2021-09-09T14:00:00"))
+        );
+        assert_eq!(
+            next_activation(&plans, ChronoDuration::hours(1), &at("2021-09-10T00:00:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn inactive_after_slot() {
+        assert!(!is_active(
+            &slots(),
+            ChronoDuration::hours(12),
+            &at("2021-09-10T00:00:00")
+        ));
+    }
 }
@@ -1,29 +1,266 @@
 use crate::args::Destination;
-use crate::telegram::Telegram;
-use serialport::{new, DataBits, Parity, StopBits};
+use crate::echo;
+use crate::error_kind::ErrorKind;
+use crate::groups::{self, GroupsError};
+use crate::output;
+use crate::port::{self, PortError};
+use crate::range::Range;
+use crate::scan::{self, Find};
+use crate::serial::{self, open, OpenRetry, Serial};
+use crate::status::{self, status, Status, StatusCache};
+use crate::telegram::{Telegram, TelegramError};
+use std::io::Write;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tracing::{event, Level};
 
 pub type Result<T> = std::result::Result<T, DestinationError>;
 
-pub fn destination(destination: &Destination) -> Result<()> {
-    let mut serial = new(&destination.serial, 1200)
-        .data_bits(DataBits::Seven)
-        .stop_bits(StopBits::Two)
-        .parity(Parity::Even)
-        .open()
-        .map_err(|e| DestinationError::serial(e, &destination.serial))?;
+/// Time to wait between destinations when a range of destinations is shown
+/// and no interval was specified.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
 
+/// Time to wait before polling the status again when the device is not ready
+/// to show destinations.
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Highest destination index that fits into a telegram.
+const INDEX_MAX: usize = 999;
+
+/// How often to poll the status of a device that is not ready to show
+/// destinations before giving up.
+const STATUS_POLL_ATTEMPTS: u32 = 10;
+
+/// Time to wait before the first retry if none was specified.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Shows the destination, trying again as often as configured if the port
+/// cannot be opened or written to.
+///
+/// The port is opened again for each attempt, and a range of destinations is
+/// started over from the beginning.
+pub fn destination(destination: &Destination, retry: &OpenRetry) -> Result<()> {
+    let mut delay = destination
+        .retry_delay
+        .map_or(DEFAULT_RETRY_DELAY, |d| d.as_duration());
+    let mut retries_left = destination.retry;
+    loop {
+        let result = destination_cached(
+            destination,
+            retry,
+            &mut StatusCache::new(Duration::from_secs(0)),
+        );
+        match result {
+            Err(error) if retries_left > 0 && error.is_transient() => {
+                event!(
+                    Level::WARN,
+                    %error,
+                    ?delay,
+                    retries_left,
+                    "Could not show destination, retrying"
+                );
+                sleep(delay);
+                delay = serial::backoff(delay);
+                retries_left -= 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Shows the destination like [`destination`], but skips status queries if
+/// the cache holds a recent status that accepts destinations.
+pub fn destination_cached(
+    destination: &Destination,
+    retry: &OpenRetry,
+    cache: &mut StatusCache,
+) -> Result<()> {
+    check_sendable(&destination.index, destination.line)?;
+    check_address(destination.address)?;
+    if destination.group.is_some() && !(destination.status_gated || destination.verify) {
+        // destination telegrams are not addressed, so the group would be
+        // ignored
+        return Err(DestinationError::GroupWithoutStatusCheck);
+    }
+    if let Some(path) = &destination.output {
+        return write_output(path, destination);
+    }
+    let port = port::resolve(destination.serial.as_deref())?;
+    let mut serial = open(&port, Duration::from_secs(destination.timeout_secs), retry)
+        .map_err(|e| DestinationError::serial(e, &port))?;
+    check_and_show(&mut serial, &port, destination, cache)
+}
+
+/// Refuses destination indexes and lines that cannot be sent, so that values
+/// from configuration files, which are not checked while parsing, fail before
+/// anything is sent.
+pub fn check_sendable(index: &Range, line: Option<u16>) -> Result<()> {
+    if let Some(line) = line {
+        Telegram::try_line(line)?;
+    }
+    let mut indexes = index.iter();
+    let first = indexes.next().unwrap_or(0);
+    let last = indexes.next_back().unwrap_or(first);
+    if first.max(last) > INDEX_MAX {
+        return Err(DestinationError::OutOfRange(*index));
+    }
+    Ok(())
+}
+
+/// Refuses addresses that cannot be queried for their status, which, like
+/// indexes, are not checked while parsing configuration files.
+pub fn check_address(address: Option<u8>) -> Result<()> {
+    if let Some(address) = address {
+        Telegram::try_display_status(address)?;
+    }
+    Ok(())
+}
+
+/// Writes the telegrams to the output instead of a serial port, refusing
+/// status checks since there is no device to read a status from.
+fn write_output(path: &Path, destination: &Destination) -> Result<()> {
+    if destination.all_addresses || destination.status_gated || destination.verify {
+        return Err(DestinationError::OutputWithStatusCheck);
+    }
+    let mut out = output::create(path).map_err(|e| DestinationError::output(e, path))?;
+    show(&mut out, destination)
+        .and_then(|_| out.flush())
+        .map_err(|e| DestinationError::output(e, path))
+}
+
+/// Runs the status checks requested in the options, and only if they pass
+/// shows the destinations.
+fn check_and_show(
+    serial: &mut Serial,
+    port: &str,
+    destination: &Destination,
+    cache: &mut StatusCache,
+) -> Result<()> {
+    if destination.all_addresses {
+        return check_all_and_show(serial, port, destination, cache);
+    }
+    if destination.status_gated || destination.verify {
+        let addresses = groups::resolve(
+            destination.address,
+            destination.group.as_deref(),
+            destination.groups.as_deref(),
+        )?;
+        if addresses.is_empty() {
+            return Err(DestinationError::StatusGateWithoutAddress);
+        }
+        for address in addresses {
+            if destination.status_gated {
+                await_accepting(serial, address, STATUS_POLL_INTERVAL, cache)?;
+            } else {
+                status(serial, address)
+                    .map_err(|source| DestinationError::NoResponse { address, source })?;
+            }
+        }
+    }
+
+    show(serial, destination).map_err(|e| DestinationError::io(e, port))
+}
+
+/// Runs the status checks requested in the options on every device that
+/// responds to a status query, printing which devices passed and which did
+/// not, and shows the destinations if at least one device passed.
+///
+/// Destination telegrams are not addressed, so they are sent only once for
+/// all devices.
+fn check_all_and_show(
+    serial: &mut Serial,
+    port: &str,
+    destination: &Destination,
+    cache: &mut StatusCache,
+) -> Result<()> {
+    let addresses: Vec<u8> = scan::all_responding(serial)
+        .iter()
+        .map(Find::address)
+        .collect();
+    if addresses.is_empty() {
+        return Err(DestinationError::NoDevice);
+    }
+
+    // devices just responded to the scan, so only --status-gated is left to
+    // check
+    let mut failed = vec![];
+    if destination.status_gated {
+        for &address in &addresses {
+            if let Err(error) = await_accepting(serial, address, STATUS_POLL_INTERVAL, cache) {
+                println!("{}: {}", address, error);
+                failed.push(address);
+            }
+        }
+    }
+    if failed.len() == addresses.len() {
+        return Err(DestinationError::Failed(failed));
+    }
+
+    show(serial, destination).map_err(|e| DestinationError::io(e, port))?;
+    for address in addresses.iter().filter(|a| !failed.contains(a)) {
+        println!("{}: ok", address);
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(DestinationError::Failed(failed))
+    }
+}
+
+/// Polls the status of the device at the given address until it reports a
+/// status that accepts destinations, so that we do not send destinations to a
+/// device that would ignore them.
+fn await_accepting(
+    serial: &mut Serial,
+    address: u8,
+    poll_interval: Duration,
+    cache: &mut StatusCache,
+) -> Result<()> {
+    if let Some(status) = cache.get(address, Instant::now()) {
+        if status.accepts_destinations() {
+            return Ok(());
+        }
+    }
+
+    let mut attempt = 1;
+    loop {
+        let status = status(serial, address)?;
+        cache.insert(address, status, Instant::now());
+        if status.accepts_destinations() {
+            return Ok(());
+        }
+        if attempt == STATUS_POLL_ATTEMPTS {
+            return Err(DestinationError::NotAccepting { address, status });
+        }
+        attempt += 1;
+        sleep(poll_interval);
+    }
+}
+
+/// Sends the line, if any, and then each destination in the range once,
+/// waiting for the configured interval between destinations.
+fn show<W: Write + ?Sized>(serial: &mut W, destination: &Destination) -> std::io::Result<()> {
     if let Some(line) = destination.line {
         let line_telegram = Telegram::line(line);
-        serial
-            .write(line_telegram.as_bytes())
-            .map_err(|e| DestinationError::io(e, &destination.serial))?;
+        echo::telegram(&line_telegram);
+        serial.write_all(line_telegram.as_bytes())?;
     }
 
-    let destination_telegram = Telegram::destination(destination.index);
-    serial
-        .write(destination_telegram.as_bytes())
-        .map_err(|e| DestinationError::io(e, &destination.serial))?;
+    let interval = destination
+        .interval
+        .map(|i| i.as_duration())
+        .unwrap_or(DEFAULT_INTERVAL);
+    for (nth, index) in destination.index.iter().enumerate() {
+        if nth > 0 {
+            sleep(interval);
+        }
+        // fits since the range was checked before sending anything
+        let destination_telegram = Telegram::destination(index as u16);
+        echo::telegram(&destination_telegram);
+        serial.write_all(destination_telegram.as_bytes())?;
+    }
 
     Ok(())
 }
@@ -40,9 +277,88 @@ pub enum DestinationError {
         source: serialport::Error,
         port: String,
     },
+    #[error("Could not write destination telegrams to: {path}, due to I/O error: {source}")]
+    Output {
+        source: std::io::Error,
+        path: String,
+    },
+    #[error("Destinations {0} are out of range 0-999")]
+    OutOfRange(Range),
+    #[error("{0}")]
+    Telegram(#[from] TelegramError),
+    #[error("Status checks need a serial port and cannot be combined with --output")]
+    OutputWithStatusCheck,
+    #[error("Could not check if device is ready for destinations: {0}")]
+    Status(#[from] status::Error),
+    #[error("No device responded to the status query at address {address}: {source}")]
+    NoResponse { address: u8, source: status::Error },
+    #[error("Device at address {address} is not ready to show destinations, status: {status}")]
+    NotAccepting { address: u8, status: Status },
+    #[error(
+        "An address or group is required to check the device status before sending destinations"
+    )]
+    StatusGateWithoutAddress,
+    #[error("Destinations are shown on all devices, --group only selects the devices to check with --status-gated or --verify")]
+    GroupWithoutStatusCheck,
+    #[error("No device responded to the status query at any address 0-15")]
+    NoDevice,
+    #[error("Devices at addresses {0:?} failed the checks")]
+    Failed(Vec<u8>),
+    #[error("{0}")]
+    Groups(#[from] GroupsError),
+    #[error("{0}")]
+    Port(#[from] PortError),
 }
 
 impl DestinationError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            DestinationError::IO { source, .. } => ErrorKind::of_io(source),
+            DestinationError::Serial { .. } => ErrorKind::Port,
+            DestinationError::Output { .. } => ErrorKind::Io,
+            DestinationError::OutOfRange(_)
+            | DestinationError::Telegram(_)
+            | DestinationError::OutputWithStatusCheck
+            | DestinationError::StatusGateWithoutAddress
+            | DestinationError::GroupWithoutStatusCheck => ErrorKind::Usage,
+            DestinationError::Status(error) => error.kind(),
+            DestinationError::NoResponse { .. } | DestinationError::NoDevice => {
+                ErrorKind::NoResponse
+            }
+            DestinationError::NotAccepting { .. } | DestinationError::Failed(_) => {
+                ErrorKind::Device
+            }
+            DestinationError::Groups(error) => error.kind(),
+            DestinationError::Port(error) => error.kind(),
+        }
+    }
+}
+
+impl DestinationError {
+    /// Checks whether the error came from the port itself rather than from
+    /// the device or the options, so that trying again might help.
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::IO { .. } | Self::Serial { .. })
+    }
+
+    /// Checks whether sending the destination again later might succeed,
+    /// e.g. after a timeout, a busy port or a device that is not ready yet,
+    /// as opposed to a port that does not exist or options that cannot work.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Serial { source, .. } => serial::is_recoverable(source),
+            Self::OutOfRange(_)
+            | Self::Telegram(_)
+            | Self::OutputWithStatusCheck
+            | Self::StatusGateWithoutAddress
+            | Self::GroupWithoutStatusCheck
+            | Self::Groups(_)
+            | Self::Port(_) => false,
+            _ => true,
+        }
+    }
+
     fn io(source: std::io::Error, port: &str) -> Self {
         Self::IO {
             source,
@@ -50,6 +366,13 @@ impl DestinationError {
         }
     }
 
+    fn output(source: std::io::Error, path: &Path) -> Self {
+        Self::Output {
+            source,
+            path: output::name(path),
+        }
+    }
+
     fn serial(source: serialport::Error, port: &str) -> Self {
         Self::Serial {
             source,
@@ -57,3 +380,372 @@ impl DestinationError {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serial::Serial;
+
+    /// Options to show the destinations at the index on /dev/ttyUSB0, without
+    /// a line, status checks or retries.
+    fn args(index: &str) -> Destination {
+        Destination {
+            index: index.parse().unwrap(),
+            line: None,
+            interval: None,
+            address: None,
+            group: None,
+            groups: None,
+            all_addresses: false,
+            status_gated: false,
+            verify: false,
+            timeout_secs: 3,
+            retry: 0,
+            retry_delay: None,
+            output: None,
+            serial: Some("/dev/ttyUSB0".to_string()),
+        }
+    }
+
+    fn no_cache() -> StatusCache {
+        StatusCache::new(Duration::from_secs(0))
+    }
+
+    #[test]
+    fn await_accepting_reuses_cached_status() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .build();
+        let mut cache = StatusCache::new(Duration::from_secs(60));
+
+        // only the first check queries the device
+        await_accepting(&mut serial, 1, Duration::from_millis(1), &mut cache).unwrap();
+        await_accepting(&mut serial, 1, Duration::from_millis(1), &mut cache).unwrap();
+    }
+
+    #[test]
+    fn single_destination_with_line() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::line(6).as_bytes())
+            .expect_write(Telegram::destination(0).as_bytes())
+            .build();
+        let destination = Destination {
+            line: Some(6),
+            ..args("0")
+        };
+
+        show(&mut serial, &destination).unwrap();
+    }
+
+    #[test]
+    fn destination_on_registered_mock() {
+        let serial = Serial::builder()
+            .expect_write(Telegram::destination(4).as_bytes())
+            .build();
+        crate::serial::mock::register("/dev/ttyDestinationMock", serial);
+        let destination = Destination {
+            serial: Some("/dev/ttyDestinationMock".to_string()),
+            ..args("4")
+        };
+
+        super::destination(&destination, &OpenRetry::default()).unwrap();
+    }
+
+    #[test]
+    fn opens_port_with_timeout() {
+        let serial = Serial::builder()
+            // same bytes as before the port was opened through serial::open
+            .expect_write(b"z004\r<")
+            .build();
+        crate::serial::mock::register("/dev/ttyTimeoutMock", serial);
+        let destination = Destination {
+            timeout_secs: 7,
+            serial: Some("/dev/ttyTimeoutMock".to_string()),
+            ..args("4")
+        };
+
+        super::destination(&destination, &OpenRetry::default()).unwrap();
+        assert_eq!(
+            crate::serial::mock::opened_timeouts("/dev/ttyTimeoutMock"),
+            vec![Duration::from_secs(7)]
+        );
+    }
+
+    #[test]
+    fn retry_reports_last_error() {
+        let destination = Destination {
+            retry: 2,
+            retry_delay: Some("1ms".parse().unwrap()),
+            serial: Some("/dev/ttyUnregisteredMock".to_string()),
+            ..args("4")
+        };
+
+        match super::destination(&destination, &OpenRetry::default()) {
+            Err(DestinationError::Serial { port, .. }) => {
+                assert_eq!(port, "/dev/ttyUnregisteredMock")
+            }
+            other => panic!("Expected serial error, but got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn retry_reopens_port() {
+        let serial = Serial::builder()
+            .expect_write(Telegram::destination(4).as_bytes())
+            .build();
+        crate::serial::mock::register("/dev/ttyRetryMock", serial);
+        let destination = Destination {
+            retry: 1,
+            retry_delay: Some("1ms".parse().unwrap()),
+            serial: Some("/dev/ttyRetryMock".to_string()),
+            ..args("4")
+        };
+
+        // the first attempt takes the only mock, so a second one would fail
+        super::destination(&destination, &OpenRetry::default()).unwrap();
+        super::destination(&destination, &OpenRetry::default()).unwrap_err();
+    }
+
+    #[test]
+    fn refuses_unsendable_indexes_before_opening_port() {
+        // the port is not registered, so opening it would fail differently
+        for index in &["1000", "998-1000", "70000"] {
+            let destination = Destination {
+                serial: Some("/dev/ttyUnregisteredMock".to_string()),
+                ..args(index)
+            };
+            match super::destination(&destination, &OpenRetry::default()) {
+                Err(error @ DestinationError::OutOfRange(_)) => {
+                    assert!(!error.is_recoverable())
+                }
+                other => panic!("Expected {} to be out of range, got: {:?}", index, other),
+            }
+        }
+        let destination = Destination {
+            line: Some(1000),
+            serial: Some("/dev/ttyUnregisteredMock".to_string()),
+            ..args("0")
+        };
+        match super::destination(&destination, &OpenRetry::default()) {
+            Err(DestinationError::Telegram(TelegramError::LineOutOfRange(1000))) => {}
+            other => panic!("Expected line to be out of range, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refuses_address_out_of_range_before_opening_port() {
+        let destination = Destination {
+            address: Some(20),
+            status_gated: true,
+            serial: Some("/dev/ttyUnregisteredMock".to_string()),
+            ..args("1")
+        };
+        match super::destination(&destination, &OpenRetry::default()) {
+            Err(error @ DestinationError::Telegram(TelegramError::AddressOutOfRange(20))) => {
+                assert!(!error.is_recoverable())
+            }
+            other => panic!("Expected address to be out of range, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refuses_group_without_status_check() {
+        let destination = Destination {
+            group: Some("group_a".to_string()),
+            groups: Some("examples/groups.yaml".into()),
+            serial: Some("/dev/ttyUnregisteredMock".to_string()),
+            ..args("4")
+        };
+        match super::destination(&destination, &OpenRetry::default()) {
+            Err(error @ DestinationError::GroupWithoutStatusCheck) => {
+                assert!(!error.is_recoverable())
+            }
+            other => panic!("Expected group to be refused, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn only_port_errors_are_transient() {
+        assert!(DestinationError::io(std::io::ErrorKind::TimedOut.into(), "COM1").is_transient());
+        assert!(!DestinationError::NoDevice.is_transient());
+        assert!(!DestinationError::StatusGateWithoutAddress.is_transient());
+    }
+
+    #[test]
+    fn missing_port_and_bad_options_not_recoverable() {
+        let missing = serialport::Error::new(serialport::ErrorKind::NoDevice, "no such port");
+        assert!(!DestinationError::serial(missing, "/dev/ttyUSB9").is_recoverable());
+        assert!(!DestinationError::StatusGateWithoutAddress.is_recoverable());
+        let busy = serialport::Error::new(serialport::ErrorKind::Unknown, "busy");
+        assert!(DestinationError::serial(busy, "/dev/ttyUSB0").is_recoverable());
+        assert!(DestinationError::io(std::io::ErrorKind::TimedOut.into(), "COM1").is_recoverable());
+        assert!(DestinationError::NotAccepting {
+            address: 1,
+            status: Status::ReadyForData
+        }
+        .is_recoverable());
+    }
+
+    #[test]
+    fn range_of_destinations() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::destination(1).as_bytes())
+            .expect_write(Telegram::destination(2).as_bytes())
+            .expect_write(Telegram::destination(3).as_bytes())
+            .build();
+        let destination = Destination {
+            interval: Some("1ms".parse().unwrap()),
+            ..args("1-3")
+        };
+
+        show(&mut serial, &destination).unwrap();
+    }
+
+    #[test]
+    fn verify_refuses_silent_address() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(3).as_bytes())
+            .time_out()
+            .build();
+        let destination = Destination {
+            line: Some(6),
+            address: Some(3),
+            verify: true,
+            ..args("0")
+        };
+
+        // the mock panics if any line or destination telegram is written
+        match check_and_show(&mut serial, "/dev/ttyUSB0", &destination, &mut no_cache())
+            .unwrap_err()
+        {
+            DestinationError::NoResponse { address: 3, .. } => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn verify_then_show() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .expect_write(Telegram::destination(0).as_bytes())
+            .build();
+        let destination = Destination {
+            address: Some(1),
+            verify: true,
+            ..args("0")
+        };
+
+        check_and_show(&mut serial, "/dev/ttyUSB0", &destination, &mut no_cache()).unwrap();
+    }
+
+    #[test]
+    fn all_addresses_reports_devices_not_ready() {
+        let mut serial = Serial::builder();
+        for address in 0..=15 {
+            serial.expect_write(Telegram::display_status(address).as_bytes());
+            match address {
+                1 | 2 => {
+                    serial
+                        .respond(b"a3\r ")
+                        .expect_write(Telegram::display_version(address).as_bytes())
+                        .time_out();
+                }
+                _ => {
+                    serial.time_out();
+                }
+            }
+        }
+        serial
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .expect_write(Telegram::display_status(2).as_bytes())
+            .time_out()
+            .expect_write(Telegram::destination(0).as_bytes());
+        let mut serial = serial.build();
+        let destination = Destination {
+            all_addresses: true,
+            status_gated: true,
+            ..args("0")
+        };
+
+        match check_and_show(&mut serial, "/dev/ttyUSB0", &destination, &mut no_cache()) {
+            Err(DestinationError::Failed(failed)) => assert_eq!(failed, vec![2]),
+            other => panic!("Expected failure at address 2, got: {:?}", other),
+        }
+    }
+
+    fn output_args(output: &Path, status_gated: bool) -> Destination {
+        Destination {
+            line: Some(6),
+            interval: Some("1ms".parse().unwrap()),
+            address: Some(1),
+            status_gated,
+            output: Some(output.to_path_buf()),
+            serial: None,
+            ..args("1-2")
+        }
+    }
+
+    #[test]
+    fn writes_telegrams_to_output() {
+        let path = std::env::temp_dir().join(format!("ibisibi-dest-{}.bin", std::process::id()));
+        let result = destination(&output_args(&path, false), &OpenRetry::default());
+        let written = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+        let expected = [
+            Telegram::line(6),
+            Telegram::destination(1),
+            Telegram::destination(2),
+        ]
+        .iter()
+        .flat_map(|t| t.as_bytes().to_vec())
+        .collect::<Vec<u8>>();
+        assert_eq!(written.unwrap(), expected);
+    }
+
+    #[test]
+    fn output_refuses_status_checks() {
+        let path = std::env::temp_dir().join(format!("ibisibi-gated-{}.bin", std::process::id()));
+        match destination(&output_args(&path, true), &OpenRetry::default()) {
+            Err(DestinationError::OutputWithStatusCheck) => {}
+            other => panic!("Expected refusal, got: {:?}", other),
+        }
+        assert!(!path.exists(), "Expected no output file");
+    }
+
+    #[test]
+    fn await_accepting_polls_until_ok() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a0\r#")
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .build();
+
+        await_accepting(&mut serial, 1, Duration::from_millis(1), &mut no_cache()).unwrap();
+    }
+
+    #[test]
+    fn await_accepting_gives_up() {
+        let mut serial = Serial::builder();
+        for _ in 0..STATUS_POLL_ATTEMPTS {
+            serial
+                .expect_write(Telegram::display_status(1).as_bytes())
+                .respond(b"a0\r#");
+        }
+        let mut serial = serial.build();
+
+        match await_accepting(&mut serial, 1, Duration::from_millis(1), &mut no_cache())
+            .unwrap_err()
+        {
+            DestinationError::NotAccepting {
+                address: 1,
+                status: Status::ReadyForData,
+            } => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+}
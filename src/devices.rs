@@ -1,28 +1,143 @@
-use crate::{args::Scan as Opts, scan::Scan, serial::open};
+use crate::{
+    args::{Format, Scan as Opts},
+    error_kind::ErrorKind,
+    json,
+    port::{self, PortError},
+    scan::{Find, Scan},
+    serial::{open, OpenRetry},
+    status::Status,
+};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io;
+use std::time::Duration;
 use thiserror::Error;
 
 type Result<T> = std::result::Result<T, ScanError>;
 
-pub fn scan(scan: Opts) -> Result<()> {
-    let mut serial = open(&scan.serial).map_err(|e| ScanError::Serial {
+pub fn scan(scan: Opts, retry: &OpenRetry) -> Result<()> {
+    let port = port::resolve(scan.serial.as_deref())?;
+    let timeout = Duration::from_secs(scan.timeout_secs);
+    let mut serial = open(&port, timeout, retry).map_err(|e| ScanError::Serial {
         source: e,
-        port: scan.serial,
+        port: port.clone(),
     })?;
 
-    let mut none = false;
-    for find in Scan::new(&mut serial).filter_map(crate::scan::Result::ok) {
-        none = true;
-        println!(
-            "{address:X?}: {status}",
+    let parity_retries = if scan.retry_parity { 1 } else { 0 };
+    let mut finds = Scan::new(&mut serial).parity_retries(parity_retries);
+    if let Some(addresses) = scan.addresses {
+        finds = finds.addresses(addresses);
+    }
+    if let Some(quick_timeout) = scan.quick_timeout {
+        finds = finds.quick_pass(quick_timeout.as_duration(), timeout);
+    }
+    // addresses that do not respond fail with a timeout, which only means
+    // that there is no device to list
+    let finds = finds.filter_map(crate::scan::Result::ok);
+    match scan.format {
+        Format::Text => print_text(&mut std::io::stdout(), finds, scan.include_reserved)?,
+        // JSON and CSV are only printed once the scan is complete
+        Format::Json => println!("{}", json(&finds.collect::<Vec<_>>())),
+        Format::Csv => print!("{}", csv(&finds.collect::<Vec<_>>())),
+    }
+
+    Ok(())
+}
+
+/// Prints each device as it is found.
+fn print_text<W: io::Write>(
+    out: &mut W,
+    finds: impl Iterator<Item = Find>,
+    include_reserved: bool,
+) -> io::Result<()> {
+    let mut found_any = false;
+    for find in finds {
+        found_any = true;
+        let flag = if include_reserved && find.is_reserved() {
+            " (non-standard address)"
+        } else {
+            ""
+        };
+        let version = find.version().map(|v| v.as_str()).unwrap_or("unknown");
+        writeln!(
+            out,
+            "{address:X?}: {status}, version {version}{flag}",
             address = find.address(),
-            status = find.status()
+            status = find.status(),
+            version = version,
+            flag = flag
+        )?;
+    }
+    if !found_any {
+        writeln!(out, "No display devices found.")?;
+    }
+    Ok(())
+}
+
+/// Formats the found devices as a JSON array of objects with address, status
+/// and version, which is `null` if the device did not respond to the version
+/// query.
+fn json(finds: &[Find]) -> String {
+    let mut json = String::from("[");
+    for (idx, find) in finds.iter().enumerate() {
+        if idx > 0 {
+            json.push(',');
+        }
+        let version = find
+            .version()
+            .map(|version| json::string(version.as_str()))
+            .unwrap_or_else(|| "null".to_string());
+        // writing to a string cannot fail
+        let _ = write!(
+            json,
+            r#"{{"address":{},"status":{},"version":{}}}"#,
+            find.address(),
+            json::string(&status_name(find.status())),
+            version
         );
     }
-    if none {
-        println!("No display devices found.")
+    json.push(']');
+    json
+}
+
+/// Formats the found devices as CSV with a header row, and one row per
+/// device with address, status and version, which is empty if the device did
+/// not respond to the version query.
+///
+/// Addresses have two digits so that they also sort correctly as text.
+fn csv(finds: &[Find]) -> String {
+    let mut csv = String::from("address,status,version\r\n");
+    for find in finds {
+        let version = find.version().map(|v| v.as_str()).unwrap_or("");
+        // writing to a string cannot fail
+        let _ = write!(
+            csv,
+            "{:02},{},{}\r\n",
+            find.address(),
+            status_name(find.status()),
+            csv_field(version)
+        );
     }
+    csv
+}
 
-    Ok(())
+/// Quotes the text for CSV if it contains separators, quotes or line breaks.
+fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains(&[',', '"', '\r', '\n'][..]) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Names the status for JSON and CSV output, e.g. `ok` or `unknown_5` for statuses
+/// without a known meaning.
+fn status_name(status: Status) -> String {
+    match status {
+        Status::Ok => "ok".to_string(),
+        Status::ReadyForData => "ready_for_data".to_string(),
+        Status::Uncategorized(byte) => format!("unknown_{}", byte as char),
+    }
 }
 
 #[derive(Error, Debug)]
@@ -32,4 +147,102 @@ pub enum ScanError {
         source: serialport::Error,
         port: String,
     },
+    #[error("{0}")]
+    Port(#[from] PortError),
+    #[error("Could not print found devices: {0}")]
+    Print(#[from] io::Error),
+}
+
+impl ScanError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ScanError::Serial { .. } => ErrorKind::Port,
+            ScanError::Port(error) => error.kind(),
+            ScanError::Print(_) => ErrorKind::Io,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parity::parity_byte;
+    use crate::scan::all_responding;
+    use crate::serial::Serial;
+    use crate::telegram::Telegram;
+
+    /// Scans with a device at address 2 and one at address 5 that does not
+    /// respond to the version query.
+    fn finds() -> Vec<Find> {
+        let mut serial = Serial::builder();
+        for address in 0..=15 {
+            serial.expect_write(Telegram::display_status(address).as_bytes());
+            match address {
+                2 => {
+                    let version = b"aVV\"2\\1\r";
+                    serial
+                        .respond(b"a3\r ")
+                        .expect_write(Telegram::display_version(2).as_bytes())
+                        .respond(version)
+                        .respond(&[parity_byte(version)]);
+                }
+                5 => {
+                    serial
+                        .respond(b"a7\r$")
+                        .expect_write(Telegram::display_version(5).as_bytes())
+                        .time_out();
+                }
+                _ => {
+                    serial.time_out();
+                }
+            }
+        }
+        all_responding(&mut serial.build())
+    }
+
+    #[test]
+    fn json_array() {
+        assert_eq!(
+            json(&finds()),
+            r#"[{"address":2,"status":"ok","version":"V\"2\\1"},{"address":5,"status":"unknown_7","version":null}]"#
+        );
+    }
+
+    fn text(finds: Vec<Find>) -> String {
+        let mut out = Vec::new();
+        print_text(&mut out, finds.into_iter(), false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_with_devices() {
+        assert_eq!(
+            text(finds()),
+            "2: Ok (3), version V\"2\\1\n5: Unknown status (55), version unknown\n"
+        );
+    }
+
+    #[test]
+    fn text_empty_bus() {
+        assert_eq!(text(vec![]), "No display devices found.\n");
+    }
+
+    #[test]
+    fn csv_rows() {
+        assert_eq!(
+            csv(&finds()),
+            "address,status,version\r\n02,ok,\"V\"\"2\\1\"\r\n05,unknown_7,\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_empty() {
+        assert_eq!(csv(&[]), "address,status,version\r\n");
+    }
+
+    #[test]
+    fn json_empty() {
+        assert_eq!(json(&[]), "[]");
+    }
 }
@@ -0,0 +1,273 @@
+//! Reads the sign database off a BS210 sign and writes it to an IHEX file,
+//! e.g. to back up a sign before flashing it.
+//!
+//! Reading relies on the experimental [`query::read`], so on signs that do
+//! not understand it, only the blocks before the first failed read end up in
+//! the file.
+//!
+//! [`query::read`]: crate::record::query::read
+
+use crate::{
+    args::Dump as Opts,
+    error_kind::ErrorKind,
+    flash::{self, FlashError},
+    port::{self, PortError},
+    serial::{open, OpenRetry, Serial},
+};
+use ihex::Record;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{event, Level};
+
+type Result<T> = std::result::Result<T, DumpError>;
+
+/// Bytes to request with each read, the same as the chunks of exported
+/// databases.
+const BLOCK_LEN: u32 = 0x20;
+
+pub fn dump(opts: Opts, retry: &OpenRetry) -> Result<()> {
+    if opts.address > 15 {
+        return Err(DumpError::Address(opts.address));
+    }
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial =
+        open(&port, Duration::from_secs(opts.timeout_secs), retry).map_err(|source| {
+            DumpError::Serial {
+                source,
+                port: port.clone(),
+            }
+        })?;
+
+    flash::select_address(&mut serial, opts.address, 1)?;
+    let blocks = read_blocks(&mut serial, opts.len)?;
+    let db = to_ihex(&blocks.read)?;
+    std::fs::write(&opts.output, &db).map_err(|e| DumpError::write(e, &opts.output))?;
+
+    let len: usize = blocks.read.iter().map(|(_, data)| data.len()).sum();
+    println!("Dumped 0x{:X} bytes to {}", len, opts.output.display());
+    if let Some((offset, error)) = blocks.stopped {
+        println!(
+            "Stopped at 0x{:04X} since the sign did not respond as expected: {}",
+            offset, error
+        );
+    }
+    Ok(())
+}
+
+/// Blocks of the database that could be read, with their offsets.
+struct Blocks {
+    read: Vec<(u16, Vec<u8>)>,
+    /// Offset of the first block that could not be read and why, if reading
+    /// stopped early.
+    stopped: Option<(u16, DumpError)>,
+}
+
+/// Reads the first `len` bytes of the database block by block, stopping at
+/// the first block that cannot be read.
+///
+/// Fails only if not even the first block can be read.
+fn read_blocks(serial: &mut Serial, len: u32) -> Result<Blocks> {
+    let mut read = vec![];
+    for start in (0..len).step_by(BLOCK_LEN as usize) {
+        // the length is at most 0x10000, so every start fits
+        let offset = start as u16;
+        let block_len = BLOCK_LEN.min(len - start) as u8;
+        match read_block(serial, offset, block_len) {
+            Ok(data) => read.push((offset, data)),
+            Err(error) if read.is_empty() => return Err(error),
+            Err(error) => {
+                event!(Level::WARN, offset, %error, "Could not read block, stopping");
+                return Ok(Blocks {
+                    read,
+                    stopped: Some((offset, error)),
+                });
+            }
+        }
+    }
+    Ok(Blocks {
+        read,
+        stopped: None,
+    })
+}
+
+/// Requests `len` bytes at `offset` like [`flash::read_back`], and checks
+/// that the sign sent all of them.
+fn read_block(serial: &mut Serial, offset: u16, len: u8) -> Result<Vec<u8>> {
+    let data = flash::read_back(serial, offset, len, DumpError::Corrupt)?;
+    if data.len() != len as usize {
+        return Err(DumpError::Length {
+            expected: len,
+            received: data.len(),
+        });
+    }
+    Ok(data)
+}
+
+/// Writes the blocks as IHEX data records followed by an end of file record,
+/// and checks that the result reads back as a complete database.
+fn to_ihex(blocks: &[(u16, Vec<u8>)]) -> Result<String> {
+    let mut records: Vec<Record> = blocks
+        .iter()
+        .map(|(offset, data)| Record::Data {
+            offset: *offset,
+            value: data.clone(),
+        })
+        .collect();
+    records.push(Record::EndOfFile);
+    let db = ihex::create_object_file_representation(&records)?;
+    flash::check_database(&db).map_err(DumpError::Malformed)?;
+    Ok(db)
+}
+
+#[derive(Error, Debug)]
+pub enum DumpError {
+    #[error("Address {0} is out of range 0-15")]
+    Address(u8),
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+    #[error("Failed to communicate over serial port, error: {0}")]
+    IO(#[from] io::Error),
+    #[error("Could not read sign database, unexpected response from device: {0}")]
+    Corrupt(crate::record::Error),
+    #[error("Could not read sign database, requested {expected} bytes but received {received}")]
+    Length { expected: u8, received: usize },
+    #[error("Could not convert the sign database to IHEX, error: {0}")]
+    Convert(#[from] ihex::WriterError),
+    #[error("Dumped sign database does not read back as IHEX, error: {0}")]
+    Malformed(FlashError),
+    #[error("Could not write sign database to: {path}, due to I/O error: {source}")]
+    Write { source: io::Error, path: String },
+}
+
+impl DumpError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            DumpError::Address(_) => ErrorKind::Usage,
+            DumpError::Serial { .. } => ErrorKind::Port,
+            DumpError::Port(error) => error.kind(),
+            DumpError::IO(error) => ErrorKind::of_io(error),
+            DumpError::Corrupt(_) | DumpError::Length { .. } => ErrorKind::Device,
+            DumpError::Convert(_) | DumpError::Malformed(_) => ErrorKind::Database,
+            DumpError::Write { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+impl DumpError {
+    fn write(source: io::Error, path: &Path) -> Self {
+        Self::Write {
+            source,
+            path: path.display().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::record::{checksum, query};
+    use crate::telegram::Telegram;
+    use ihex::Reader;
+
+    /// Response to a read with the given content.
+    fn read_response(content: &[u8]) -> Vec<u8> {
+        let mut response = vec![0x4f, content.len() as u8];
+        response.extend_from_slice(content);
+        response.push(checksum(&response[1..]));
+        response
+    }
+
+    fn opts(output: &Path, len: u32) -> Opts {
+        Opts {
+            output: output.to_path_buf(),
+            address: 1,
+            len,
+            timeout_secs: 3,
+            serial: Some("/dev/ttyDumpMock".to_string()),
+        }
+    }
+
+    #[test]
+    fn dump_to_ihex() {
+        let serial = Serial::builder()
+            .expect_write(Telegram::empty().as_bytes())
+            .expect_write(Telegram::bs_select_address(1).as_bytes())
+            .expect_write(query::read(0x00, 0x20).unwrap().as_bytes())
+            .respond(&read_response(&[0xAA; 0x20]))
+            .expect_write(query::read(0x20, 0x08).unwrap().as_bytes())
+            .respond(&read_response(&[0xBB; 0x08]))
+            .build();
+        crate::serial::mock::register("/dev/ttyDumpMock", serial);
+        let path = std::env::temp_dir().join(format!("ibisibi-dump-{}.hex", std::process::id()));
+
+        let result = dump(opts(&path, 0x28), &OpenRetry::default());
+        let written = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        let records: Vec<Record> = Reader::new(&written.unwrap())
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            records,
+            vec![
+                Record::Data {
+                    offset: 0x00,
+                    value: vec![0xAA; 0x20]
+                },
+                Record::Data {
+                    offset: 0x20,
+                    value: vec![0xBB; 0x08]
+                },
+                Record::EndOfFile,
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_first_unreadable_block() {
+        let mut serial = Serial::builder()
+            .expect_write(query::read(0x00, 0x20).unwrap().as_bytes())
+            .respond(&read_response(&[0xAA; 0x20]))
+            .expect_write(query::read(0x20, 0x20).unwrap().as_bytes())
+            .time_out()
+            .build();
+
+        let blocks = read_blocks(&mut serial, 0x60).unwrap();
+
+        assert_eq!(blocks.read, vec![(0x00, vec![0xAA; 0x20])]);
+        match blocks.stopped {
+            Some((0x20, DumpError::IO(_))) => {}
+            other => panic!("Expected to stop at 0x20, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fails_if_nothing_readable() {
+        let mut corrupt = read_response(&[0xAA; 0x20]);
+        *corrupt.last_mut().unwrap() ^= 0x01;
+        let mut serial = Serial::builder()
+            .expect_write(query::read(0x00, 0x20).unwrap().as_bytes())
+            .respond(&corrupt)
+            .build();
+
+        match read_blocks(&mut serial, 0x60) {
+            Err(DumpError::Corrupt(_)) => {}
+            Err(other) => panic!("Expected corrupt response, got: {:?}", other),
+            Ok(_) => panic!("Expected failure"),
+        }
+    }
+
+    #[test]
+    fn empty_dump_is_only_end_of_file() {
+        assert_eq!(to_ihex(&[]).unwrap(), ":00000001FF\n");
+    }
+}
@@ -0,0 +1,34 @@
+//! Printing of everything that is sent over the wire to stderr, enabled with
+//! `--echo`, to see what was actually sent without enabling trace logging.
+//!
+//! Echoed lines go to stderr so that they do not mix with the output of
+//! commands like `scan --format json`.
+
+use crate::telegram::Telegram;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Starts printing sent telegrams and records for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Prints the telegram and its bytes, if echoing is enabled.
+pub fn telegram(telegram: &Telegram) {
+    if enabled() {
+        eprintln!("> {:?} {:02X?}", telegram, telegram.as_bytes());
+    }
+}
+
+/// Prints bytes that are not a telegram, e.g. a record sent when flashing, if
+/// echoing is enabled.
+pub fn bytes(bytes: &[u8]) {
+    if enabled() {
+        eprintln!("> {:02X?}", bytes);
+    }
+}
@@ -0,0 +1,50 @@
+//! Categories of failures for `--json-errors`, so that scripts can react to
+//! e.g. a display that does not respond differently than to a mistyped
+//! option, whichever command failed.
+
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Options or arguments that cannot work, e.g. an address above 15.
+    Usage,
+    /// A configuration, groups or script file could not be read or parsed.
+    Config,
+    /// No serial port was found, or it could not be opened or configured.
+    Port,
+    /// Reading from or writing to the port, a file or a standard stream
+    /// failed.
+    Io,
+    /// No display responded in time.
+    NoResponse,
+    /// A display responded, but not as expected, e.g. with a corrupt
+    /// response or a status that refuses the command.
+    Device,
+    /// The sign database to flash is invalid.
+    Database,
+}
+
+impl ErrorKind {
+    /// Kind of an I/O error on a serial port, which means that no display
+    /// responded if reading timed out.
+    pub fn of_io(error: &io::Error) -> ErrorKind {
+        if error.kind() == io::ErrorKind::TimedOut {
+            ErrorKind::NoResponse
+        } else {
+            ErrorKind::Io
+        }
+    }
+
+    /// Names the kind for JSON output, e.g. `no_response`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Usage => "usage",
+            ErrorKind::Config => "config",
+            ErrorKind::Port => "port",
+            ErrorKind::Io => "io",
+            ErrorKind::NoResponse => "no_response",
+            ErrorKind::Device => "device",
+            ErrorKind::Database => "database",
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    args::Version as Opts,
+    error_kind::ErrorKind,
+    port::{self, PortError},
+    serial::{open, OpenRetry},
+    version::{self, version},
+};
+use std::time::Duration;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, FirmwareError>;
+
+/// Prints the software version reported by the device at the address.
+pub fn firmware(opts: Opts, retry: &OpenRetry) -> Result<()> {
+    if opts.address > 15 {
+        return Err(FirmwareError::Address(opts.address));
+    }
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial = open(&port, Duration::from_secs(opts.timeout_secs), retry).map_err(|e| {
+        FirmwareError::Serial {
+            source: e,
+            port: port.clone(),
+        }
+    })?;
+
+    let version = version(&mut serial, opts.address)?;
+    println!("{}", version);
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum FirmwareError {
+    #[error("Address {0} is out of range 0-15")]
+    Address(u8),
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+    #[error("{0}")]
+    Version(#[from] version::Error),
+}
+
+impl FirmwareError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            FirmwareError::Address(_) => ErrorKind::Usage,
+            FirmwareError::Serial { .. } => ErrorKind::Port,
+            FirmwareError::Port(error) => error.kind(),
+            FirmwareError::Version(error) => error.kind(),
+        }
+    }
+}
@@ -1,40 +1,529 @@
 use crate::{
-    args::Flash,
+    args::{DbFormat, Flash},
+    coverage::Coverage,
+    echo,
+    error_kind::ErrorKind,
+    groups,
+    port::{self, PortError},
     record::{db::DatabaseChunk, query, res},
-    serial::{self, Serial},
-    status::status,
+    scan,
+    serial::{self, OpenRetry, Serial},
+    status::{status, Status},
     telegram::Telegram,
+    version::version,
 };
 use ihex::{Reader, Record};
 use std::backtrace::Backtrace;
 use std::{
-    fs::read_to_string,
-    io::{Read, Write},
+    convert::TryFrom,
+    fmt,
+    fs::read,
+    io::{self, stdin, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 use tracing::{event, Level};
 
 pub type Result<T> = std::result::Result<T, FlashError>;
 
+/// Upper bound for bytes to discard after the last finishing step, in case
+/// the device keeps sending.
+const MAX_FINISH_RESPONSE_LEN: usize = 64;
+
+/// Size of the database on the device as far as we know, which is what the
+/// 16 bit offsets of database chunks can address.
+const DB_CAPACITY: u32 = 0x1_0000;
+
+/// Bytes per chunk when flashing a raw binary image, the same as in the
+/// databases exported by sign database editors.
+const BIN_CHUNK_LEN: usize = 0x20;
+
 #[tracing::instrument]
-pub fn flash(opts: Flash) -> Result<()> {
+pub fn flash(opts: Flash, retry: &OpenRetry) -> Result<()> {
+    let selections = [
+        opts.address.is_some() || opts.group.is_some(),
+        opts.any_address,
+        opts.all_addresses,
+    ];
+    if selections.iter().filter(|&&s| s).count() > 1 {
+        return Err(FlashError::AmbiguousAddress);
+    }
+    let addresses = groups::resolve(opts.address, opts.group.as_deref(), opts.groups.as_deref())?;
+    if addresses.is_empty() && !opts.any_address && !opts.all_addresses {
+        return Err(FlashError::NoAddress);
+    }
+
+    let db = load_db(&opts)?;
+    let db_len = check_database(&db)?;
+    event!(Level::INFO, db_len, "Database checked");
+    let options = Options::from(&opts);
+    check_coverage(&db, &options)?;
+
+    if opts.dry_run {
+        // the port is only needed to check the devices, so leave it alone
+        // unless explicitly given
+        if opts.serial.is_some() {
+            let mut serial = open(&opts, retry)?;
+            for address in scanned_addresses(&mut serial, &opts, addresses)? {
+                check_compatibility(&mut serial, address, &options)?;
+            }
+        }
+        return print_chunks(Reader::new(&db));
+    }
+
+    let mut serial = open(&opts, retry)?;
+    let addresses = scanned_addresses(&mut serial, &opts, addresses)?;
+    let mut report = ProgressReport::new(db_len, opts.quiet);
+
+    let mut failed = vec![];
+    for address in addresses {
+        event!(Level::INFO, address, "Flashing device");
+        let result = check_compatibility(&mut serial, address, &options).and_then(|_| {
+            perform_flashing(&mut serial, address, &db, &options, &mut |step| {
+                log_step(step);
+                report.step(step);
+            })
+        });
+        match result {
+            // keep going with the other devices that responded to the scan
+            Err(error) if opts.all_addresses => {
+                println!("{}: {}", address, error);
+                failed.push(address);
+            }
+            Err(error) => return Err(error),
+            Ok(()) if opts.all_addresses => println!("{}: ok", address),
+            Ok(()) => {}
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(FlashError::Failed(failed))
+    }
+}
+
+/// Replaces the addresses with the first address that a device responds at
+/// if `--any-address` is set, or with all of them for `--all-addresses`.
+fn scanned_addresses(serial: &mut Serial, opts: &Flash, addresses: Vec<u8>) -> Result<Vec<u8>> {
+    if opts.any_address {
+        let find = scan::first_responding(serial).ok_or(FlashError::NoDevice)?;
+        event!(Level::INFO, address = find.address(), "Found device");
+        Ok(vec![find.address()])
+    } else if opts.all_addresses {
+        let addresses: Vec<u8> = scan::all_responding(serial)
+            .iter()
+            .map(scan::Find::address)
+            .collect();
+        if addresses.is_empty() {
+            return Err(FlashError::NoDevice);
+        }
+        event!(Level::INFO, ?addresses, "Found devices");
+        Ok(addresses)
+    } else {
+        Ok(addresses)
+    }
+}
+
+/// Path that stands for stdin instead of a file when given as the database.
+pub const STDIN_PATH: &str = "-";
+
+/// Reads the database from the file at the path, or from stdin if the path
+/// is [`STDIN_PATH`], e.g. to pipe in a generated database.
+fn read_db(path: &Path) -> io::Result<Vec<u8>> {
+    if path == Path::new(STDIN_PATH) {
+        let mut db = Vec::new();
+        stdin().read_to_end(&mut db)?;
+        Ok(db)
+    } else {
+        read(path)
+    }
+}
+
+/// Reads the database as IHEX, converting raw binary images so that they are
+/// checked and flashed like any other database.
+fn load_db(opts: &Flash) -> Result<String> {
+    let db = read_db(&opts.sign_db_hex).map_err(FlashError::db_read)?;
+    match db_format(opts) {
+        DbFormat::Hex => String::from_utf8(db)
+            .map_err(|e| FlashError::db_read(io::Error::new(io::ErrorKind::InvalidData, e))),
+        DbFormat::Bin => bin_to_ihex(&db, opts.base_address),
+    }
+}
+
+/// The format given in the options, or else bin for files ending in `.bin`
+/// and hex for anything else, including stdin.
+fn db_format(opts: &Flash) -> DbFormat {
+    opts.format.unwrap_or_else(|| {
+        let is_bin = opts
+            .sign_db_hex
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .iter()
+            .any(|ext| ext.eq_ignore_ascii_case("bin"));
+        if is_bin {
+            DbFormat::Bin
+        } else {
+            DbFormat::Hex
+        }
+    })
+}
+
+/// Splits a raw binary image into data records of [`BIN_CHUNK_LEN`] bytes,
+/// the first one written to `base_address`, followed by an end of file
+/// record.
+fn bin_to_ihex(bin: &[u8], base_address: u16) -> Result<String> {
+    let end = u32::try_from(bin.len())
+        .unwrap_or(u32::MAX)
+        .saturating_add(base_address.into());
+    if end > DB_CAPACITY {
+        return Err(FlashError::DbTooLarge(end));
+    }
+    let mut records: Vec<Record> = bin
+        .chunks(BIN_CHUNK_LEN)
+        .enumerate()
+        .map(|(idx, data)| Record::Data {
+            // fits since the image ends within the capacity
+            offset: base_address + (idx * BIN_CHUNK_LEN) as u16,
+            value: data.to_vec(),
+        })
+        .collect();
+    records.push(Record::EndOfFile);
+    ihex::create_object_file_representation(&records).map_err(FlashError::DbConvert)
+}
+
+fn open(opts: &Flash, retry: &OpenRetry) -> Result<Serial> {
     event!(Level::DEBUG, "Opening serial port connection");
-    let Flash {
-        address,
-        ref sign_db_hex,
-        ref serial,
-        ..
-    } = opts;
-    let mut serial = serial::open_for_flashing(&opts).map_err(|e| FlashError::Serial {
+    let port = port::resolve(opts.serial.as_deref())?;
+    serial::open_for_flashing(opts, &port, retry).map_err(|e| FlashError::Serial {
         source: e,
-        port: serial.clone(),
+        port,
         backtrace: Backtrace::capture(),
-    })?;
-    let db = read_to_string(sign_db_hex).map_err(FlashError::db_read)?;
-    let db = Reader::new(&db);
+    })
+}
+
+/// Prints offset, length and bytes of each chunk that flashing the database
+/// would send, without sending anything.
+fn print_chunks(reader: Reader) -> Result<()> {
+    for (offset, data) in chunks(reader)? {
+        let chunk = DatabaseChunk::new(offset, &data).map_err(FlashError::DbRecordTooLong)?;
+        println!(
+            "0x{:04X} {} bytes: {:02X?}",
+            offset,
+            data.len(),
+            chunk.as_bytes()
+        );
+    }
+    Ok(())
+}
+
+/// Collects the non-empty data records with the offsets they are written to.
+fn chunks(reader: Reader) -> Result<Vec<(u16, Vec<u8>)>> {
+    Chunks::new(reader).collect()
+}
+
+/// Non-empty data records of a database with the offsets on the device that
+/// they are written to, which are the record addresses combined with the
+/// last extended address record.
+///
+/// Records after the end of file record are rejected, and start address
+/// records are ignored, since entry points are meaningless for a sign
+/// database.
+struct Chunks<'a> {
+    reader: Reader<'a>,
+    base: u32,
+    eof_found: bool,
+}
+
+impl<'a> Chunks<'a> {
+    fn new(reader: Reader<'a>) -> Self {
+        Chunks {
+            reader,
+            base: 0,
+            eof_found: false,
+        }
+    }
+
+    fn eof_found(&self) -> bool {
+        self.eof_found
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<(u16, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.reader.next()? {
+                Ok(record) => record,
+                Err(error) => return Some(Err(error.into())),
+            };
+            if self.eof_found {
+                return Some(Err(FlashError::DbUnexpectedRecordType));
+            }
+            match record {
+                Record::Data { value, .. } if value.is_empty() => {
+                    // Nothing to write, and sending an empty chunk would
+                    // overwrite the start of a chunk with nothing
+                    event!(Level::DEBUG, "Skipping data record without content");
+                }
+                Record::Data { offset, value } => {
+                    let address = self.base + u32::from(offset);
+                    return Some(
+                        u16::try_from(address)
+                            .map(|offset| (offset, value))
+                            .map_err(|_| FlashError::DbAddressOutOfRange(address)),
+                    );
+                }
+                Record::EndOfFile => self.eof_found = true,
+                Record::ExtendedSegmentAddress(segment) => self.base = u32::from(segment) << 4,
+                Record::ExtendedLinearAddress(upper) => self.base = u32::from(upper) << 16,
+                Record::StartSegmentAddress { .. } | Record::StartLinearAddress(_) => {
+                    event!(Level::DEBUG, "Ignoring start address record");
+                }
+            }
+        }
+    }
+}
+
+/// Progress of flashing a single device, reported to the caller of
+/// [`perform_flashing`] as each step is reached, e.g. to render a progress
+/// bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashStep {
+    /// About to select the device at the address for flashing.
+    SelectAddress { address: u8 },
+    /// About to clear the database on the device.
+    ClearStarted,
+    /// About to send the clear command with the given index, starting at 0.
+    ClearBlock(u8),
+    /// The device acknowledged a chunk of the database written to the offset.
+    ChunkWritten { offset: u16, len: usize },
+    /// All chunks are written, about to finish flashing.
+    FinishStarted,
+    /// Flashing of the device is complete.
+    Done,
+}
+
+/// Turns flashing steps into log messages for the command line.
+fn log_step(step: FlashStep) {
+    match step {
+        FlashStep::SelectAddress { address } => {
+            event!(Level::DEBUG, address, "Selecting address for flashing")
+        }
+        FlashStep::ClearStarted => event!(Level::DEBUG, "Clearing database"),
+        FlashStep::ClearBlock(idx) => event!(Level::TRACE, "Clearing ({}/4)", idx),
+        FlashStep::ChunkWritten { offset, len } => event!(
+            Level::TRACE,
+            "Flashed {len} bytes at offset 0x{offset:X?}",
+            len = len,
+            offset = offset
+        ),
+        FlashStep::FinishStarted => event!(Level::TRACE, "Finishing flashing (1/2)"),
+        FlashStep::Done => event!(Level::TRACE, "Done flashing database"),
+    }
+}
+
+/// Reads the whole database before anything is sent, so that a corrupt or
+/// truncated file is refused before the device is cleared, and returns the
+/// number of data bytes in it.
+///
+/// Checks the record checksums, that every chunk can be sent and fits into
+/// the database on the device, and that the file ends with an end of file
+/// record.
+pub fn check_database(db: &str) -> Result<usize> {
+    let mut chunks = Chunks::new(Reader::new(db));
+    let mut len = 0;
+    for chunk in &mut chunks {
+        let (offset, data) = chunk?;
+        DatabaseChunk::new(offset, &data).map_err(FlashError::DbRecordTooLong)?;
+        let end = u32::from(offset) + data.len() as u32;
+        if end > DB_CAPACITY {
+            return Err(FlashError::DbTooLarge(end));
+        }
+        len += data.len();
+    }
+    if chunks.eof_found() {
+        Ok(len)
+    } else {
+        Err(FlashError::DbTruncated)
+    }
+}
+
+/// Prints how much of the database was written to stderr, so that operators
+/// can tell a slow link from a stuck one.
+#[derive(Debug)]
+struct ProgressReport {
+    total: usize,
+    written: usize,
+    quiet: bool,
+}
+
+impl ProgressReport {
+    fn new(total: usize, quiet: bool) -> Self {
+        ProgressReport {
+            total,
+            written: 0,
+            quiet,
+        }
+    }
+
+    fn step(&mut self, step: FlashStep) {
+        match step {
+            FlashStep::SelectAddress { .. } => self.written = 0,
+            FlashStep::ChunkWritten { len, .. } => {
+                self.written += len;
+                if !self.quiet {
+                    eprint!("\r{}", self);
+                }
+            }
+            // end the line that was overwritten with each chunk
+            FlashStep::Done if !self.quiet && self.written > 0 => eprintln!(),
+            _ => {}
+        }
+    }
+
+    fn percent(&self) -> usize {
+        (self.written * 100).checked_div(self.total).unwrap_or(100)
+    }
+}
+
+impl fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Flashed {}/{} bytes ({}%)",
+            self.written,
+            self.total,
+            self.percent()
+        )
+    }
+}
+
+/// Settings for flashing that do not concern the serial port connection.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Query the software version before flashing and refuse to flash devices
+    /// that do not identify as a sign we know how to flash.
+    pub check_version: bool,
+    /// Refuse to flash devices that report a status other than
+    /// [`Status::ReadyForData`].
+    pub require_ready: bool,
+    /// Proceed with flashing even if sanity checks fail, only logging a warning.
+    pub force: bool,
+    /// Refuse to flash databases that fail the analysis before flashing,
+    /// rather than only logging a warning.
+    pub strict: bool,
+    /// Read and discard a response to the last finishing step instead of
+    /// assuming that the device stays silent.
+    pub drain_finish: bool,
+    /// Read back the cleared region after clearing and abort if it was not
+    /// actually cleared.
+    pub verify_clear: bool,
+    /// Read back the database after flashing and fail if it differs from
+    /// what was written.
+    pub verify: bool,
+    /// Query the status after flashing and fail unless the device reports
+    /// [`Status::Ok`].
+    pub check_status_after: bool,
+    /// Receive timeout for the other steps, restored after flashing so that
+    /// the next device is checked with it. Leaves the timeout of the port as
+    /// is if `None`.
+    pub timeout: Option<Duration>,
+    /// Receive timeout while clearing the database, which can take longer
+    /// than other steps. Leaves the timeout of the port as is if `None`.
+    pub clear_timeout: Option<Duration>,
+    /// Receive timeout for acknowledgements of database chunks, which
+    /// usually arrive quickly. Leaves the timeout of the port as is if `None`.
+    pub chunk_ack_timeout: Option<Duration>,
+    /// How many empty telegrams to send before selecting the address, to get
+    /// the attention of signs on a shared bus.
+    pub wake_count: u32,
+    /// How many times to send a chunk again if the device responds with
+    /// anything other than an acknowledgement, e.g. on a noisy line.
+    pub chunk_retries: u32,
+    /// Abort flashing a device that takes longer than this, e.g. because it
+    /// stopped acknowledging chunks.
+    pub max_flash_time: Option<Duration>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            check_version: false,
+            require_ready: false,
+            force: false,
+            strict: false,
+            drain_finish: false,
+            verify_clear: false,
+            verify: false,
+            check_status_after: false,
+            timeout: None,
+            clear_timeout: None,
+            chunk_ack_timeout: None,
+            wake_count: 1,
+            chunk_retries: 3,
+            max_flash_time: None,
+        }
+    }
+}
+
+impl From<&Flash> for Options {
+    fn from(flash: &Flash) -> Self {
+        Options {
+            check_version: flash.check_version,
+            require_ready: flash.require_ready,
+            force: flash.force,
+            strict: flash.strict,
+            drain_finish: flash.drain_finish,
+            verify_clear: flash.verify_clear,
+            verify: flash.verify,
+            check_status_after: flash.check_status_after,
+            // both phases fall back to the global timeout, which is also
+            // set when opening the port
+            timeout: Some(Duration::from_secs(flash.timeout)),
+            clear_timeout: Some(
+                flash
+                    .clear_timeout
+                    .map(|t| t.as_duration())
+                    .unwrap_or_else(|| Duration::from_secs(flash.timeout)),
+            ),
+            chunk_ack_timeout: Some(
+                flash
+                    .chunk_ack_timeout
+                    .map(|t| t.as_duration())
+                    .unwrap_or_else(|| Duration::from_secs(flash.timeout)),
+            ),
+            wake_count: flash.wake_count,
+            chunk_retries: flash.chunk_retries,
+            max_flash_time: flash.max_flash_time.map(|t| t.as_duration()),
+        }
+    }
+}
+
+/// Checks that the data records cover the database without gaps, warning
+/// about missing chunks, or failing if the `strict` option is set.
+fn check_coverage(db: &str, options: &Options) -> Result<()> {
+    let coverage = Coverage::analyze(db)?;
+    if coverage.is_complete() {
+        event!(
+            Level::DEBUG,
+            end = coverage.end(),
+            "Database has no missing chunks"
+        );
+        return Ok(());
+    }
 
-    check_compatibility(&mut serial, address)?;
-    perform_flashing(&mut serial, address, db)
+    if options.strict {
+        Err(FlashError::DbIncomplete(coverage))
+    } else {
+        event!(
+            Level::WARN,
+            "Database is missing chunks at {:X?}, which will be left undefined",
+            coverage.missing_chunks()
+        );
+        Ok(())
+    }
 }
 
 /// Ensure that a device is listening at the specified address for flashing, so
@@ -42,56 +531,195 @@ pub fn flash(opts: Flash) -> Result<()> {
 ///
 /// More sanity checks may be added to this function in the future.
 #[tracing::instrument(skip(serial))]
-fn check_compatibility(serial: &mut Serial, address: u8) -> Result<()> {
-    // Check device status first and print it as debug output,
-    dump_status(serial, address)
+fn check_compatibility(serial: &mut Serial, address: u8, options: &Options) -> Result<()> {
+    // Check device status first and print it as debug output, refusing
+    // devices that are not ready if asked to,
+    check_status(serial, address, options.require_ready, options.force)?;
+
+    // then check that the device identifies as a sign, since some other
+    // IBIS peripherals also answer the status query.
+    if options.check_version {
+        check_version(serial, address, options.force)?;
+    }
 
     // Other commands are sent in observed flashings that might
     // also serve as sanity checks, but we do not understand them well
     // enoug to add them here yet.
+    Ok(())
+}
+
+#[tracing::instrument(skip(serial))]
+fn check_version(serial: &mut Serial, address: u8, force: bool) -> Result<()> {
+    event!(Level::TRACE, "Checking device software version");
+    let version = version(serial, address)?;
+    event!(Level::DEBUG, %version, "Checked device software version");
+    if version.is_flashable() {
+        Ok(())
+    } else if force {
+        event!(
+            Level::WARN,
+            %version,
+            "Device does not identify as a supported sign, flashing anyway"
+        );
+        Ok(())
+    } else {
+        Err(FlashError::UnsupportedVersion(version))
+    }
 }
 
 #[tracing::instrument(skip(serial))]
-fn dump_status(serial: &mut Serial, address: u8) -> Result<()> {
+fn check_status(serial: &mut Serial, address: u8, require_ready: bool, force: bool) -> Result<()> {
     event!(Level::TRACE, "Checking device status");
     let status = status(serial, address)?;
     event!(Level::DEBUG, %status, "Checked device status");
+    if !require_ready || status == Status::ReadyForData {
+        Ok(())
+    } else if force {
+        event!(
+            Level::WARN,
+            %status,
+            "Device is not ready for data, flashing anyway"
+        );
+        Ok(())
+    } else {
+        Err(FlashError::UnexpectedStatus(status))
+    }
+}
+
+/// Sends the actual flashing commands over the wire, reporting each step to
+/// `progress`.
+#[tracing::instrument(skip(serial, db, progress))]
+fn perform_flashing(
+    serial: &mut Serial,
+    address: u8,
+    db: &str,
+    options: &Options,
+    progress: &mut dyn FnMut(FlashStep),
+) -> Result<()> {
+    let result = perform_phases(serial, address, db, options, progress);
+    // also after failures, since the next address may still be flashed
+    let restored = set_phase_timeout(serial, options.timeout);
+    result.and(restored)
+}
+
+fn perform_phases(
+    serial: &mut Serial,
+    address: u8,
+    db: &str,
+    options: &Options,
+    progress: &mut dyn FnMut(FlashStep),
+) -> Result<()> {
+    let watchdog = Watchdog::start(options.max_flash_time);
+    progress(FlashStep::SelectAddress { address });
+    select_address(serial, address, options.wake_count)?;
+    set_phase_timeout(serial, options.clear_timeout)?;
+    clear_database(serial, progress)?;
+    if options.verify_clear {
+        verify_clear(serial)?;
+    }
+    set_phase_timeout(serial, options.chunk_ack_timeout)?;
+    flash_database(serial, Reader::new(db), options, &watchdog, progress)?;
+    if options.verify {
+        verify_database(serial, Reader::new(db))?;
+    }
+    if options.check_status_after {
+        check_status_after(serial, address)?;
+    }
     Ok(())
 }
 
-/// Sends the actual flashing commands over the wire.
-#[tracing::instrument(skip(serial, db))]
-fn perform_flashing(serial: &mut Serial, address: u8, db: Reader) -> Result<()> {
-    select_address(serial, address)?;
-    clear_database(serial)?;
-    flash_database(serial, db)
+/// Queries the status once the database is committed, since the finishing
+/// steps get no response that would tell whether the device accepted it.
+fn check_status_after(serial: &mut Serial, address: u8) -> Result<()> {
+    let status = status(serial, address).map_err(FlashError::PostFlashStatusQuery)?;
+    event!(Level::DEBUG, %status, "Checked device status after flashing");
+    if status == Status::Ok {
+        Ok(())
+    } else {
+        Err(FlashError::PostFlashStatus(status))
+    }
 }
 
+/// Limits the time that flashing a device may take, checked between steps.
+#[derive(Debug)]
+struct Watchdog {
+    started: Instant,
+    limit: Option<Duration>,
+}
+
+impl Watchdog {
+    fn start(limit: Option<Duration>) -> Self {
+        Watchdog {
+            started: Instant::now(),
+            limit,
+        }
+    }
+
+    /// Fails if the time limit has been exceeded, naming the offset of the
+    /// last chunk that the device acknowledged.
+    fn check(&self, last_acked_offset: Option<u16>) -> Result<()> {
+        match self.limit {
+            Some(limit) if self.started.elapsed() > limit => Err(FlashError::Stuck {
+                limit,
+                last_acked_offset,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn set_phase_timeout(serial: &mut Serial, timeout: Option<Duration>) -> Result<()> {
+    if let Some(timeout) = timeout {
+        event!(Level::TRACE, ?timeout, "Setting receive timeout");
+        serial
+            .set_timeout(timeout)
+            .map_err(|e| FlashError::Timeout(e, Backtrace::capture()))?;
+    }
+    Ok(())
+}
+
+/// Writes a record or other bytes, printing them first if echoing is enabled.
+fn send(serial: &mut Serial, bytes: &[u8]) -> std::io::Result<()> {
+    echo::bytes(bytes);
+    serial.write_all(bytes)
+}
+
+fn send_telegram(serial: &mut Serial, telegram: &Telegram) -> std::io::Result<()> {
+    echo::telegram(telegram);
+    serial.write_all(telegram.as_bytes())
+}
+
+/// Wakes the device and selects it for the following queries, which is also
+/// needed before reading from it.
 #[tracing::instrument(skip(serial))]
-fn select_address(serial: &mut Serial, address: u8) -> Result<()> {
-    event!(Level::DEBUG, "Selecting address for flashing");
-    serial.write_all(Telegram::empty().as_bytes())?;
+pub(crate) fn select_address(
+    serial: &mut Serial,
+    address: u8,
+    wake_count: u32,
+) -> std::io::Result<()> {
+    for _ in 0..wake_count {
+        send_telegram(serial, &Telegram::empty())?;
+    }
     // r.S1 (select address?)
-    serial.write_all(Telegram::bs_select_address(address).as_bytes())?;
-    serial.flush()?;
+    send_telegram(serial, &Telegram::bs_select_address(address))?;
     // no response expected
-    Ok(())
+    serial.flush()
 }
 
-#[tracing::instrument(skip(serial))]
-fn clear_database(serial: &mut Serial) -> Result<()> {
+#[tracing::instrument(skip(serial, progress))]
+fn clear_database(serial: &mut Serial, progress: &mut dyn FnMut(FlashStep)) -> Result<()> {
     let mut buf = [0_u8; 4];
 
-    event!(Level::DEBUG, "Clearing database");
+    progress(FlashStep::ClearStarted);
     event!(Level::TRACE, "Preparing clearing (1/2)");
-    serial.write_all(query::prepare_clear_0().as_bytes())?;
+    send(serial, query::prepare_clear_0().as_bytes())?;
     serial.flush()?;
     serial.read_exact(&mut buf[0..1])?;
     res::verify_ack_response(&buf[0..1]).map_err(FlashError::PrepareClear0)?;
 
     event!(Level::TRACE, "Preparing clearing (2/2)");
     const EXPECTED_QUERY_1_RESPONSE: &[u8] = &[0x57];
-    serial.write_all(query::prepare_clear_1().as_bytes())?;
+    send(serial, query::prepare_clear_1().as_bytes())?;
     serial.flush()?;
     serial.read_exact(&mut buf[..])?;
     let unknown_query_1_response =
@@ -101,8 +729,8 @@ fn clear_database(serial: &mut Serial) -> Result<()> {
     }
 
     for i in 0..4 {
-        event!(Level::TRACE, "Clearing ({}/4)", i);
-        serial.write_all(query::clear().as_bytes())?;
+        progress(FlashStep::ClearBlock(i));
+        send(serial, query::clear().as_bytes())?;
         serial.flush()?;
         serial.read_exact(&mut buf[0..1])?;
         let response = buf[0];
@@ -112,13 +740,13 @@ fn clear_database(serial: &mut Serial) -> Result<()> {
     }
 
     event!(Level::TRACE, "Finishing clearing (1/2)");
-    serial.write_all(query::finish_clear_0().as_bytes())?;
+    send(serial, query::finish_clear_0().as_bytes())?;
     serial.flush()?;
     serial.read_exact(&mut buf[0..1])?;
     res::verify_ack_response(&buf[0..1]).map_err(FlashError::FinishClear0)?;
 
     event!(Level::TRACE, "Finishing clearing (2/2)");
-    serial.write_all(query::finish_clear_1().as_bytes())?;
+    send(serial, query::finish_clear_1().as_bytes())?;
     serial.flush()?;
     serial.read_exact(&mut buf[0..1])?;
     res::verify_ack_response(&buf[0..1]).map_err(FlashError::FinishClear1)?;
@@ -126,78 +754,207 @@ fn clear_database(serial: &mut Serial) -> Result<()> {
     Ok(())
 }
 
+/// Reads back the region written by the clear message and checks that it holds
+/// the clear fill, for signs that acknowledge clearing without clearing.
+#[tracing::instrument(skip(serial))]
+fn verify_clear(serial: &mut Serial) -> Result<()> {
+    event!(Level::TRACE, "Reading back cleared region");
+    let cleared = read_back(
+        serial,
+        query::CLEAR_ADDRESS,
+        query::CLEAR_LEN,
+        FlashError::ClearReadback,
+    )?;
+    match cleared.iter().position(|&b| b != query::CLEAR_FILL) {
+        Some(idx) => Err(FlashError::NotCleared {
+            address: query::CLEAR_ADDRESS + idx as u16,
+            found: cleared[idx],
+        }),
+        None => {
+            event!(Level::DEBUG, "Cleared region holds the expected fill");
+            Ok(())
+        }
+    }
+}
+
+/// Reads back the chunks written by [`flash_database`] and compares them to
+/// the database, failing at the first byte that differs.
 #[tracing::instrument(skip(serial, reader))]
-fn flash_database(serial: &mut Serial, reader: Reader) -> Result<()> {
-    event!(Level::DEBUG, "Flashing database");
+fn verify_database(serial: &mut Serial, reader: Reader) -> Result<()> {
+    event!(Level::DEBUG, "Verifying database");
+    for chunk in Chunks::new(reader) {
+        let (offset, data) = chunk?;
+        let len = u8::try_from(data.len()).map_err(|_| {
+            FlashError::DbRecordTooLong(crate::record::Error::RecordLengthOutOfBounds)
+        })?;
+        let received = read_back(serial, offset, len, FlashError::VerifyReadback)?;
+        if let Some(idx) = (0..data.len()).find(|&idx| received.get(idx) != Some(&data[idx])) {
+            return Err(FlashError::VerifyMismatch {
+                offset: offset + idx as u16,
+                expected: data[idx],
+                received: received.get(idx).copied(),
+            });
+        }
+    }
+    event!(Level::DEBUG, "Database on device matches");
+    Ok(())
+}
 
+/// Sends the data as a chunk at the offset and waits for the acknowledgement,
+/// sending it again up to `retries` times if the device responds with
+/// anything else.
+fn write_chunk(serial: &mut Serial, offset: u16, data: &[u8], retries: u32) -> Result<()> {
+    let chunk = DatabaseChunk::new(offset, data).map_err(FlashError::DbRecordTooLong)?;
     let mut buf = [0_u8; 1];
-    let mut eof_found = false;
-    let mut write_offset = 0;
-    for record in reader {
-        let record = record?;
-        if eof_found {
-            return Err(FlashError::DbUnexpectedRecordType);
-        }
-        match record {
-            Record::Data { value: data, .. } => {
+    let mut attempt = 0;
+    loop {
+        send(serial, chunk.as_bytes())?;
+        serial.flush()?;
+        serial.read_exact(&mut buf)?;
+        match res::verify_ack_response(&buf) {
+            Ok(()) => return Ok(()),
+            Err(error) if attempt < retries => {
+                attempt += 1;
                 event!(
-                    Level::TRACE,
-                    "Flashing {len} bytes at offset 0x{offset:X?}",
-                    len = data.len(),
-                    offset = write_offset
+                    Level::WARN,
+                    offset,
+                    %error,
+                    "Chunk not acknowledged, sending again ({}/{})",
+                    attempt,
+                    retries
                 );
+            }
+            Err(error) => return Err(FlashError::flash_chunk_not_acknowledged(error)),
+        }
+    }
+}
 
-                serial.write_all(
-                    DatabaseChunk::new(write_offset, &data)
-                        .map_err(FlashError::DbRecordTooLong)?
-                        .as_bytes(),
-                )?;
-                serial.flush()?;
+/// Requests `len` bytes at `address` from the device with the experimental
+/// [`query::read`] and returns them, mapping corrupt responses with `corrupt`.
+pub(crate) fn read_back<E: From<std::io::Error>>(
+    serial: &mut Serial,
+    address: u16,
+    len: u8,
+    corrupt: fn(crate::record::Error) -> E,
+) -> std::result::Result<Vec<u8>, E> {
+    let query = query::read(address, len).expect("read query should always fit into a record");
+    send(serial, query.as_bytes())?;
+    serial.flush()?;
+    // 4f, length, the requested bytes and a checksum
+    let mut buf = vec![0_u8; len as usize + 3];
+    serial.read_exact(&mut buf)?;
+    Ok(res::response_payload(&buf).map_err(corrupt)?.to_vec())
+}
 
-                serial.read_exact(&mut buf)?;
-                res::verify_ack_response(&buf).map_err(FlashError::flash_chunk_not_acknowledged)?;
+/// Writes the data records of the database in chunks at their record
+/// addresses, see [`Chunks`], then finishes flashing.
+///
+/// The BS210 signs we captured flashings of do not respond to the last
+/// finishing step, but other signs have been reported to reply to it. With
+/// `drain_finish` set in the options, such a reply is read and discarded so
+/// that it does not end up in the response to the next command sent on the
+/// same connection.
+#[tracing::instrument(skip(serial, reader, progress))]
+fn flash_database(
+    serial: &mut Serial,
+    reader: Reader,
+    options: &Options,
+    watchdog: &Watchdog,
+    progress: &mut dyn FnMut(FlashStep),
+) -> Result<()> {
+    event!(Level::DEBUG, "Flashing database");
 
-                write_offset += 0x20;
-            }
-            Record::EndOfFile => {
-                eof_found = true;
-            }
-            _ => return Err(FlashError::DbUnexpectedRecordType),
-        }
+    let mut buf = [0_u8; 1];
+    let mut last_acked_offset = None;
+    let mut chunks = Chunks::new(reader);
+    while let Some(chunk) = {
+        watchdog.check(last_acked_offset)?;
+        chunks.next()
+    } {
+        let (offset, data) = chunk?;
+        write_chunk(serial, offset, &data, options.chunk_retries)?;
+        last_acked_offset = Some(offset);
+        progress(FlashStep::ChunkWritten {
+            offset,
+            len: data.len(),
+        });
     }
 
-    if !eof_found {
+    if !chunks.eof_found() {
         event!(Level::WARN, "No EOF record found in database, ignoring");
     }
 
-    event!(Level::TRACE, "Finishing flashing (1/2)");
-    serial.write_all(query::finish_flash_0().as_bytes())?;
+    watchdog.check(last_acked_offset)?;
+    progress(FlashStep::FinishStarted);
+    send(serial, query::finish_flash_0().as_bytes())?;
     serial.flush()?;
     serial.read_exact(&mut buf)?;
     res::verify_ack_response(&buf).map_err(FlashError::FinishFlash0)?;
 
     event!(Level::TRACE, "Finishing flashing (2/2)");
-    serial.write_all(query::finish_flash_1().as_bytes())?;
+    send(serial, query::finish_flash_1().as_bytes())?;
     serial.flush()?;
-    // do not expect any reponse for the second finishing step
+    // do not expect any reponse for the second finishing step, but discard
+    // one if asked to
+    if options.drain_finish {
+        let ignored = serial::drain(serial, MAX_FINISH_RESPONSE_LEN)?;
+        if !ignored.is_empty() {
+            event!(
+                Level::DEBUG,
+                "Ignoring response to last finishing step: {:X?}",
+                ignored
+            );
+        }
+    }
 
-    event!(Level::TRACE, "Done flashing database");
+    progress(FlashStep::Done);
 
     Ok(())
 }
 
 #[derive(Debug, Error)]
 pub enum FlashError {
+    #[error("An address or a group of addresses to flash to is required")]
+    NoAddress,
+    #[error("Use only one of --address or --group, --any-address and --all-addresses to select devices to flash")]
+    AmbiguousAddress,
+    #[error("No device responded to the status query at any address 0-15")]
+    NoDevice,
+    #[error("Flashing failed for devices at addresses {0:?}")]
+    Failed(Vec<u8>),
+    #[error("{0}")]
+    Groups(#[from] groups::GroupsError),
+    #[error("{0}")]
+    Port(#[from] PortError),
     #[error("Failed to read sign database, error: {0}, backtrace: {1}")]
     DbRead(std::io::Error, Backtrace),
     #[error("Failed to read sign database, error: {0}")]
     DbCorrupt(#[from] ihex::ReaderError),
+    #[error("Failed to convert binary sign database, error: {0}")]
+    DbConvert(ihex::WriterError),
     #[error("Failed to read sign database, error: {0}")]
     DbRecordTooLong(crate::record::Error),
     #[error(
         "Failed to read sign database, error: unrecognized format, found unexpected record type"
     )]
     DbUnexpectedRecordType,
+    #[error("Sign database has a record at 0x{0:X}, beyond the 16 bit addresses of the device")]
+    DbAddressOutOfRange(u32),
+    #[error("Sign database extends to 0x{0:X}, beyond the 0x{cap:X} bytes of the device", cap = DB_CAPACITY)]
+    DbTooLarge(u32),
+    #[error("Sign database ends without an end of file record, the file may be truncated")]
+    DbTruncated,
+    #[error(
+        "Flashing did not complete within {}s, last acknowledged chunk at offset {}",
+        .limit.as_secs_f64(),
+        .last_acked_offset.map(|o| format!("0x{:X}", o)).unwrap_or_else(|| "none".to_string())
+    )]
+    Stuck {
+        limit: Duration,
+        last_acked_offset: Option<u16>,
+    },
+    #[error("Sign database is missing chunks at {:X?}, use without --strict to flash anyway", .0.missing_chunks())]
+    DbIncomplete(Coverage),
     #[error(
         "Database record sent, but device failed to send acknowledgement: {0}, backtrace: {1}"
     )]
@@ -212,12 +969,22 @@ pub enum FlashError {
         port: String,
         backtrace: Backtrace,
     },
+    #[error("Failed to set receive timeout on serial port, error: {0}, backtrace: {1}")]
+    Timeout(serialport::Error, Backtrace),
     #[error("Failed to write to serial port, error: {0}, backtrace: {1}")]
     SerialWrite(#[from] std::io::Error, Backtrace),
     #[error("{0}, backtrace: {1}")]
     IbisResponseCorrupt(#[from] crate::telegram::TelegramParseError, Backtrace),
     #[error("Could not check device status before clearing and flashing, error: {0}")]
     Status(#[from] crate::status::Error),
+    #[error("Could not check device software version before clearing and flashing, error: {0}")]
+    Version(#[from] crate::version::Error),
+    #[error("Device reported software version {0}, which does not look like a sign that can be flashed, use --force to flash anyway")]
+    UnsupportedVersion(crate::version::Version),
+    #[error(
+        "Device reported status {0} instead of being ready for data, use --force to flash anyway"
+    )]
+    UnexpectedStatus(Status),
     #[error("Could not clear sign database, unexpected response from device at clearing preparation step 0")]
     PrepareClear0(crate::record::Error),
     #[error("Could not clear sign database, unexpected response from device at clearing preparation step 1, error: {0}")]
@@ -230,6 +997,68 @@ pub enum FlashError {
     FinishClear0(crate::record::Error),
     #[error("Could not clear sign database, unexpected response from device at clearing finishing step 1, error: {0}")]
     FinishClear1(crate::record::Error),
+    #[error("Could not read back cleared region, unexpected response from device: {0}")]
+    ClearReadback(crate::record::Error),
+    #[error("Could not read back flashed database, unexpected response from device: {0}")]
+    VerifyReadback(crate::record::Error),
+    #[error(
+        "Flashed database differs at 0x{offset:04X}, expected 0x{expected:02X}, but read {}",
+        .received.map(|r| format!("0x{:02X}", r)).unwrap_or_else(|| "nothing".to_string())
+    )]
+    VerifyMismatch {
+        offset: u16,
+        expected: u8,
+        received: Option<u8>,
+    },
+    #[error("Device reported status {0} after flashing instead of Ok, it may not have accepted the database")]
+    PostFlashStatus(Status),
+    #[error("Could not check device status after flashing, error: {0}")]
+    PostFlashStatusQuery(crate::status::Error),
+    #[error("Sign database was not cleared, found 0x{found:02X} at 0x{address:04X} instead of 0x{fill:02X}", fill = query::CLEAR_FILL)]
+    NotCleared { address: u16, found: u8 },
+}
+
+impl FlashError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            FlashError::NoAddress | FlashError::AmbiguousAddress => ErrorKind::Usage,
+            FlashError::NoDevice => ErrorKind::NoResponse,
+            FlashError::Groups(error) => error.kind(),
+            FlashError::Port(error) => error.kind(),
+            FlashError::DbRead(_, _)
+            | FlashError::DbCorrupt(_)
+            | FlashError::DbConvert(_)
+            | FlashError::DbRecordTooLong(_)
+            | FlashError::DbUnexpectedRecordType
+            | FlashError::DbAddressOutOfRange(_)
+            | FlashError::DbTooLarge(_)
+            | FlashError::DbTruncated
+            | FlashError::DbIncomplete(_) => ErrorKind::Database,
+            FlashError::Serial { .. } | FlashError::Timeout(_, _) => ErrorKind::Port,
+            FlashError::SerialWrite(error, _) => ErrorKind::of_io(error),
+            FlashError::Status(error) | FlashError::PostFlashStatusQuery(error) => error.kind(),
+            FlashError::Version(error) => error.kind(),
+            FlashError::Failed(_)
+            | FlashError::Stuck { .. }
+            | FlashError::FlashChunkNotAcknowledged(_, _)
+            | FlashError::FinishFlash0(_)
+            | FlashError::IbisResponseCorrupt(_, _)
+            | FlashError::UnsupportedVersion(_)
+            | FlashError::UnexpectedStatus(_)
+            | FlashError::PrepareClear0(_)
+            | FlashError::PrepareClear1CorruptResponse(_)
+            | FlashError::PrepareClear1
+            | FlashError::Clear(_)
+            | FlashError::FinishClear0(_)
+            | FlashError::FinishClear1(_)
+            | FlashError::ClearReadback(_)
+            | FlashError::VerifyReadback(_)
+            | FlashError::VerifyMismatch { .. }
+            | FlashError::PostFlashStatus(_)
+            | FlashError::NotCleared { .. } => ErrorKind::Device,
+        }
+    }
 }
 
 impl FlashError {
@@ -247,11 +1076,28 @@ mod test {
     use super::*;
     use crate::serial::Serial;
 
+    #[test]
+    fn any_address_without_device() {
+        let mut serial = Serial::builder();
+        for address in 0..=15 {
+            serial
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .time_out();
+        }
+        let mut serial = serial.build();
+        let opts: Flash =
+            argh::FromArgs::from_args(&["flash"], &["db.hex", "--any-address"]).unwrap();
+        match scanned_addresses(&mut serial, &opts, vec![]) {
+            Err(FlashError::NoDevice) => {}
+            other => panic!("Expected no device, got: {:?}", other),
+        }
+    }
+
     #[test]
     fn check_compatibility_timeout() {
         let mut serial = Serial::builder().expect_write(b"a1\r\"").time_out().build();
 
-        match check_compatibility(&mut serial, 1) {
+        match check_compatibility(&mut serial, 1, &Options::default()) {
             Err(FlashError::Status(_)) => {}
             other => panic!(
                 "Expected status error, but got Ok or unexpected variant: {:?}",
@@ -267,7 +1113,7 @@ mod test {
             .respond(b"a3\r?") // correct checksum would be a space (0x20)
             .build();
 
-        match check_compatibility(&mut serial, 1) {
+        match check_compatibility(&mut serial, 1, &Options::default()) {
             Err(FlashError::Status(_)) => {}
             other => panic!(
                 "Expected status error, but got Ok or unexpected variant: {:?}",
@@ -283,7 +1129,7 @@ mod test {
             .respond(b"a3\r ")
             .build();
 
-        match check_compatibility(&mut serial, 1) {
+        match check_compatibility(&mut serial, 1, &Options::default()) {
             Ok(()) => {}
             Err(err) => panic!(
                 "Expected status query to be Ok but got unexpected error: {:?}",
@@ -292,11 +1138,867 @@ mod test {
         }
     }
 
-    /// Tests that an attempt to flash mini0 reproduces what we observed during actual flashing.
     #[test]
-    fn flash_mini0_happy_path() {
-        const MINI0: &str =
-            ":20000000570012001B00121C8B4506F900E001000AE001050A0080016001A0004F00003083
+    fn check_compatibility_require_ready() {
+        let options = Options {
+            require_ready: true,
+            ..Options::default()
+        };
+        let mut serial = Serial::builder()
+            .expect_write(b"a1\r\"")
+            .respond(b"a0\r#")
+            .build();
+        check_compatibility(&mut serial, 1, &options).expect("ready device should pass");
+
+        let mut serial = Serial::builder()
+            .expect_write(b"a1\r\"")
+            .respond(b"a3\r ")
+            .build();
+        match check_compatibility(&mut serial, 1, &options) {
+            Err(FlashError::UnexpectedStatus(Status::Ok)) => {}
+            other => panic!(
+                "Expected unexpected status error, but got Ok or unexpected variant: {:?}",
+                other
+            ),
+        }
+    }
+
+    #[test]
+    fn check_compatibility_require_ready_forced() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a1\r\"")
+            .respond(b"a3\r ")
+            .build();
+        let options = Options {
+            require_ready: true,
+            force: true,
+            ..Options::default()
+        };
+
+        check_compatibility(&mut serial, 1, &options).expect("forced check should pass");
+    }
+
+    #[test]
+    fn check_compatibility_unsupported_version() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a1\r\"")
+            .respond(b"a3\r ")
+            .expect_write(b"aV1\rt")
+            .respond(b"aVX\r")
+            .respond(&[0x7F ^ b'a' ^ b'V' ^ b'X' ^ b'\r'])
+            .build();
+        let options = Options {
+            check_version: true,
+            ..Options::default()
+        };
+
+        match check_compatibility(&mut serial, 1, &options) {
+            Err(FlashError::UnsupportedVersion(version)) => assert_eq!(version.as_str(), "X"),
+            other => panic!(
+                "Expected unsupported version error, but got Ok or unexpected variant: {:?}",
+                other
+            ),
+        }
+    }
+
+    #[test]
+    fn check_compatibility_unsupported_version_forced() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a1\r\"")
+            .respond(b"a3\r ")
+            .expect_write(b"aV1\rt")
+            .respond(b"aVX\r")
+            .respond(&[0x7F ^ b'a' ^ b'V' ^ b'X' ^ b'\r'])
+            .build();
+        let options = Options {
+            check_version: true,
+            force: true,
+            ..Options::default()
+        };
+
+        check_compatibility(&mut serial, 1, &options).expect("forced check should pass");
+    }
+
+    #[test]
+    fn chunks_for_dry_run() {
+        const DB: &str = ":02000000AABB99
+:00001000F0
+:0400000300000000F9
+:02002000CCDD35
+:00000001FF
+";
+        assert_eq!(
+            chunks(Reader::new(DB)).unwrap(),
+            vec![(0x00, vec![0xAA, 0xBB]), (0x20, vec![0xCC, 0xDD])]
+        );
+    }
+
+    #[test]
+    fn flash_database_skips_empty_data_records() {
+        const DB: &str = ":02000000AABB99
+:02002000CCDD35
+:00000001FF
+";
+        let reader = Reader::new(DB);
+        let mut serial = Serial::builder()
+            .expect_write(DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(DatabaseChunk::new(0x20, &[0xCC, 0xDD]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_1().as_bytes())
+            .build();
+
+        flash_database(
+            &mut serial,
+            reader,
+            &Options::default(),
+            &Watchdog::start(None),
+            &mut |_| {},
+        )
+        .expect("flashing should succeed here");
+    }
+
+    #[test]
+    fn flash_database_ignores_start_address_records() {
+        const DB: &str = ":02000000AABB99
+:0400000300000000F9
+:02002000CCDD35
+:04000005000000CD2A
+:00000001FF
+";
+        let mut serial = Serial::builder()
+            .expect_write(DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(DatabaseChunk::new(0x20, &[0xCC, 0xDD]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_1().as_bytes())
+            .build();
+
+        flash_database(
+            &mut serial,
+            Reader::new(DB),
+            &Options::default(),
+            &Watchdog::start(None),
+            &mut |_| {},
+        )
+        .expect("flashing should succeed here");
+    }
+
+    #[test]
+    fn flash_database_rejects_addresses_beyond_16_bits() {
+        const DB: &str = ":020000040001F9
+:02000000AABB99
+:00000001FF
+";
+        let mut serial = Serial::builder().build();
+
+        match flash_database(
+            &mut serial,
+            Reader::new(DB),
+            &Options::default(),
+            &Watchdog::start(None),
+            &mut |_| {},
+        ) {
+            Err(FlashError::DbAddressOutOfRange(0x10000)) => {}
+            other => panic!("Expected address out of range, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flash_database_multiple_segments() {
+        // records at 0x00 in segments 0, 2 and 0x10, i.e. at 0x00, 0x20 and
+        // 0x100, with a linear address of 0 that does not change anything
+        const DB: &str = ":020000040000FA
+:02000000AABB99
+:020000020002FA
+:02000000CCDD55
+:020000020010EC
+:02000000EEFF11
+:00000001FF
+";
+        let mut serial = Serial::builder()
+            .expect_write(DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(DatabaseChunk::new(0x20, &[0xCC, 0xDD]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(DatabaseChunk::new(0x100, &[0xEE, 0xFF]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_1().as_bytes())
+            .build();
+
+        flash_database(
+            &mut serial,
+            Reader::new(DB),
+            &Options::default(),
+            &Watchdog::start(None),
+            &mut |_| {},
+        )
+        .expect("flashing should succeed here");
+    }
+
+    #[test]
+    fn write_chunk_retries_on_nak() {
+        let chunk = DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap();
+        let mut serial = Serial::builder()
+            .expect_write(chunk.as_bytes())
+            .respond(b"N")
+            .expect_write(chunk.as_bytes())
+            .respond(b"O")
+            .build();
+
+        write_chunk(&mut serial, 0x00, &[0xAA, 0xBB], 3).unwrap();
+    }
+
+    #[test]
+    fn write_chunk_gives_up_after_retries() {
+        let chunk = DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap();
+        let mut serial = Serial::builder()
+            .expect_write(chunk.as_bytes())
+            .respond(b"N")
+            .expect_write(chunk.as_bytes())
+            .respond(b"N")
+            .build();
+
+        match write_chunk(&mut serial, 0x00, &[0xAA, 0xBB], 1) {
+            Err(FlashError::FlashChunkNotAcknowledged(_, _)) => {}
+            other => panic!("Expected chunk not acknowledged, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flash_database_uses_record_addresses() {
+        // a short record, a record that is not 0x20 bytes into the last one,
+        // a gap of a whole chunk and an extended segment address
+        const DB: &str = ":02000000AABB99
+:02001000CCDD45
+:02006000EEFFB1
+:020000020001FB
+:0200700011225B
+:00000001FF
+";
+        let mut serial = Serial::builder()
+            .expect_write(DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(DatabaseChunk::new(0x10, &[0xCC, 0xDD]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(DatabaseChunk::new(0x60, &[0xEE, 0xFF]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(DatabaseChunk::new(0x80, &[0x11, 0x22]).unwrap().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_1().as_bytes())
+            .build();
+
+        flash_database(
+            &mut serial,
+            Reader::new(DB),
+            &Options::default(),
+            &Watchdog::start(None),
+            &mut |_| {},
+        )
+        .expect("flashing should succeed here");
+    }
+
+    #[test]
+    fn flash_database_drains_response_to_finish() {
+        let reader = Reader::new(":00000001FF\n");
+        let mut serial = Serial::builder()
+            .expect_write(query::finish_flash_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_1().as_bytes())
+            // unexpected response to the last step, then silence
+            .respond(b"O")
+            .time_out()
+            // next command on the same connection
+            .expect_write(b"a1\r\"")
+            .respond(b"a3\r ")
+            .build();
+        let options = Options {
+            drain_finish: true,
+            ..Options::default()
+        };
+
+        flash_database(
+            &mut serial,
+            reader,
+            &options,
+            &Watchdog::start(None),
+            &mut |_| {},
+        )
+        .expect("flashing should succeed here");
+
+        // the stray byte must not show up in the next response
+        status(&mut serial, 1).expect("status response should not contain stray byte");
+    }
+
+    #[test]
+    fn watchdog_aborts_stuck_flashing() {
+        const DB: &str = ":02000000AABB99
+:02002000CCDD35
+:00000001FF
+";
+        let mut serial = Serial::builder()
+            .expect_write(DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap().as_bytes())
+            // sign takes too long to acknowledge the first chunk
+            .stall(Duration::from_millis(20))
+            .respond(b"O")
+            .build();
+        let watchdog = Watchdog::start(Some(Duration::from_millis(10)));
+
+        match flash_database(
+            &mut serial,
+            Reader::new(DB),
+            &Options::default(),
+            &watchdog,
+            &mut |_| {},
+        ) {
+            Err(FlashError::Stuck {
+                last_acked_offset: Some(0x00),
+                ..
+            }) => {}
+            other => panic!("Expected flashing to be stuck, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn select_address_after_wake_telegrams() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::empty().as_bytes())
+            .expect_write(Telegram::empty().as_bytes())
+            .expect_write(Telegram::empty().as_bytes())
+            .expect_write(Telegram::bs_select_address(2).as_bytes())
+            .build();
+
+        select_address(&mut serial, 2, 3).unwrap();
+    }
+
+    #[test]
+    fn check_coverage_strict() {
+        const DB: &str = ":02000000AABB99
+:02006000CCDDF5
+:00000001FF
+";
+        check_coverage(DB, &Options::default()).expect("should only warn when not strict");
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        match check_coverage(DB, &options) {
+            Err(FlashError::DbIncomplete(coverage)) => {
+                assert_eq!(coverage.missing_chunks(), &[0x20, 0x40])
+            }
+            other => panic!("Expected incomplete database, got: {:?}", other),
+        }
+    }
+
+    /// Plans flashing an empty database to the device at the address.
+    fn expect_empty_flash(serial: &mut crate::serial::mock::Builder, address: u8) {
+        serial
+            .expect_write(Telegram::empty().as_bytes())
+            .expect_write(Telegram::bs_select_address(address).as_bytes())
+            .expect_write(query::prepare_clear_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::prepare_clear_1().as_bytes())
+            .respond(&[0x4f, 0x01, 0x57, 0xa8]);
+        for _ in 0..4 {
+            serial.expect_write(query::clear().as_bytes()).respond(b"E");
+        }
+        serial
+            .expect_write(query::finish_clear_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_clear_1().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_1().as_bytes());
+    }
+
+    #[test]
+    fn perform_flashing_sets_phase_timeouts() {
+        let mut serial = Serial::builder();
+        expect_empty_flash(&mut serial, 1);
+        let mut serial = serial.build();
+        let clear_timeout = Duration::from_secs(10);
+        let chunk_ack_timeout = Duration::from_millis(500);
+        let options = Options {
+            clear_timeout: Some(clear_timeout),
+            chunk_ack_timeout: Some(chunk_ack_timeout),
+            ..Options::default()
+        };
+
+        perform_flashing(&mut serial, 1, ":00000001FF\n", &options, &mut |_| {})
+            .expect("flashing should succeed here");
+
+        assert_eq!(serial.timeouts(), &[clear_timeout, chunk_ack_timeout]);
+    }
+
+    #[test]
+    fn perform_flashing_restores_timeout_for_next_address() {
+        let mut serial = Serial::builder();
+        expect_empty_flash(&mut serial, 1);
+        expect_empty_flash(&mut serial, 2);
+        let mut serial = serial.build();
+        let timeout = Duration::from_secs(3);
+        let clear_timeout = Duration::from_secs(10);
+        let chunk_ack_timeout = Duration::from_millis(500);
+        let options = Options {
+            timeout: Some(timeout),
+            clear_timeout: Some(clear_timeout),
+            chunk_ack_timeout: Some(chunk_ack_timeout),
+            ..Options::default()
+        };
+
+        for address in 1..=2 {
+            perform_flashing(&mut serial, address, ":00000001FF\n", &options, &mut |_| {})
+                .expect("flashing should succeed here");
+        }
+
+        assert_eq!(
+            serial.timeouts(),
+            &[
+                clear_timeout,
+                chunk_ack_timeout,
+                timeout,
+                clear_timeout,
+                chunk_ack_timeout,
+                timeout
+            ]
+        );
+    }
+
+    #[test]
+    fn status_ok_after_flashing() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .build();
+
+        check_status_after(&mut serial, 1).expect("Ok status should pass");
+    }
+
+    #[test]
+    fn unexpected_status_after_flashing() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a0\r#")
+            .build();
+
+        match check_status_after(&mut serial, 1) {
+            Err(FlashError::PostFlashStatus(Status::ReadyForData)) => {}
+            other => panic!("Expected post flash status error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn no_status_after_flashing() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .time_out()
+            .build();
+
+        match check_status_after(&mut serial, 1) {
+            Err(FlashError::PostFlashStatusQuery(error)) if error.is_timed_out() => {}
+            other => panic!("Expected timed out status query, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flash_on_registered_mock() {
+        use argh::FromArgs;
+
+        let mut serial = Serial::builder();
+        serial
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .expect_write(Telegram::empty().as_bytes())
+            .expect_write(Telegram::bs_select_address(1).as_bytes())
+            .expect_write(query::prepare_clear_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::prepare_clear_1().as_bytes())
+            .respond(&[0x4f, 0x01, 0x57, 0xa8]);
+        for _ in 0..4 {
+            serial.expect_write(query::clear().as_bytes()).respond(b"E");
+        }
+        let serial = serial
+            .expect_write(query::finish_clear_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_clear_1().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_flash_1().as_bytes())
+            .build();
+        crate::serial::mock::register("/dev/ttyFlashMock", serial);
+        let db = std::env::temp_dir().join(format!("ibisibi-flash-{}.hex", std::process::id()));
+        std::fs::write(&db, ":00000001FF\n").unwrap();
+        let opts = Flash::from_args(
+            &["flash"],
+            &[
+                db.to_str().unwrap(),
+                "--address",
+                "1",
+                "--serial",
+                "/dev/ttyFlashMock",
+                "--quiet",
+            ],
+        )
+        .unwrap();
+
+        let result = flash(opts, &OpenRetry::default());
+        std::fs::remove_file(&db).unwrap();
+        result.expect("flashing should succeed here");
+    }
+
+    #[test]
+    fn flash_all_addresses_keeps_going_after_failure() {
+        use argh::FromArgs;
+
+        let chunk = DatabaseChunk::new(0x00, &[0xAA, 0xBB]).unwrap();
+        let mut serial = Serial::builder();
+        for address in 0..=scan::ADDRESS_MAX {
+            serial.expect_write(Telegram::display_status(address).as_bytes());
+            if address == 1 || address == 2 {
+                serial
+                    .respond(b"a3\r ")
+                    .expect_write(Telegram::display_version(address).as_bytes());
+            }
+            serial.time_out();
+        }
+        for address in 1..=2 {
+            serial
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .respond(b"a3\r ");
+            // the same steps as for an empty database up to the first chunk
+            serial
+                .expect_write(Telegram::empty().as_bytes())
+                .expect_write(Telegram::bs_select_address(address).as_bytes())
+                .expect_write(query::prepare_clear_0().as_bytes())
+                .respond(b"O")
+                .expect_write(query::prepare_clear_1().as_bytes())
+                .respond(&[0x4f, 0x01, 0x57, 0xa8]);
+            for _ in 0..4 {
+                serial.expect_write(query::clear().as_bytes()).respond(b"E");
+            }
+            serial
+                .expect_write(query::finish_clear_0().as_bytes())
+                .respond(b"O")
+                .expect_write(query::finish_clear_1().as_bytes())
+                .respond(b"O")
+                .expect_write(chunk.as_bytes());
+            if address == 1 {
+                // the first device refuses the chunk, so the second one is
+                // only flashed if flashing keeps going
+                serial.respond(b"N");
+            } else {
+                serial
+                    .respond(b"O")
+                    .expect_write(query::finish_flash_0().as_bytes())
+                    .respond(b"O")
+                    .expect_write(query::finish_flash_1().as_bytes())
+                    .expect_write(Telegram::display_status(address).as_bytes())
+                    .respond(b"a3\r ");
+            }
+        }
+        crate::serial::mock::register("/dev/ttyFlashAllMock", serial.build());
+        let db = std::env::temp_dir().join(format!("ibisibi-flash-all-{}.hex", std::process::id()));
+        std::fs::write(&db, ":02000000AABB99\n:00000001FF\n").unwrap();
+        let opts = Flash::from_args(
+            &["flash"],
+            &[
+                db.to_str().unwrap(),
+                "--all-addresses",
+                "--chunk-retries",
+                "0",
+                "--check-status-after",
+                "--serial",
+                "/dev/ttyFlashAllMock",
+                "--quiet",
+            ],
+        )
+        .unwrap();
+
+        let result = flash(opts, &OpenRetry::default());
+        std::fs::remove_file(&db).unwrap();
+        match result {
+            Err(FlashError::Failed(failed)) => assert_eq!(failed, vec![1]),
+            other => panic!("Expected only the first device to fail, got: {:?}", other),
+        }
+    }
+
+    /// Response to a read of the cleared region with the given content.
+    fn readback_response(content: &[u8]) -> Vec<u8> {
+        let mut response = vec![0x4f, content.len() as u8];
+        response.extend_from_slice(content);
+        response.push(crate::record::checksum(&response[1..]));
+        response
+    }
+
+    #[test]
+    fn verify_clear_ok() {
+        let mut serial = Serial::builder()
+            .expect_write(query::read(0x0000, 0x20).unwrap().as_bytes())
+            .respond(&readback_response(&[query::CLEAR_FILL; 0x20]))
+            .build();
+
+        verify_clear(&mut serial).unwrap();
+    }
+
+    #[test]
+    fn check_database_counts_data_bytes() {
+        const DB: &str = ":02000000AABB99
+:00000001FF
+";
+        assert_eq!(check_database(DB).unwrap(), 2);
+    }
+
+    #[test]
+    fn bin_chunked_from_base_address() {
+        let bin: Vec<u8> = (0..70).collect();
+        let db = bin_to_ihex(&bin, 0x100).unwrap();
+        assert_eq!(
+            chunks(Reader::new(&db)).unwrap(),
+            vec![
+                (0x100, bin[..32].to_vec()),
+                (0x120, bin[32..64].to_vec()),
+                (0x140, bin[64..].to_vec()),
+            ]
+        );
+        assert_eq!(check_database(&db).unwrap(), 70);
+    }
+
+    #[test]
+    fn empty_bin_is_only_end_of_file() {
+        let db = bin_to_ihex(&[], 0).unwrap();
+        assert_eq!(chunks(Reader::new(&db)).unwrap(), vec![]);
+        assert_eq!(check_database(&db).unwrap(), 0);
+    }
+
+    #[test]
+    fn bin_beyond_capacity() {
+        assert!(bin_to_ihex(&[0; 0x100], 0xFF00).is_ok());
+        match bin_to_ihex(&[0; 0x101], 0xFF00) {
+            Err(FlashError::DbTooLarge(0x1_0001)) => {}
+            other => panic!("Expected database too large, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn full_bin_passes_checks() {
+        let db = bin_to_ihex(&[0; DB_CAPACITY as usize], 0).unwrap();
+        assert_eq!(check_database(&db).unwrap(), DB_CAPACITY as usize);
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        check_coverage(&db, &options).unwrap();
+    }
+
+    #[test]
+    fn db_format_from_extension_or_option() {
+        use argh::FromArgs;
+
+        let format = |args: &[&str]| db_format(&Flash::from_args(&["flash"], args).unwrap());
+        assert_eq!(format(&["db.hex"]), DbFormat::Hex);
+        assert_eq!(format(&["db.BIN"]), DbFormat::Bin);
+        assert_eq!(format(&["--", "-"]), DbFormat::Hex);
+        assert_eq!(format(&["db.img", "--format", "bin"]), DbFormat::Bin);
+        assert_eq!(format(&["db.bin", "--format", "hex"]), DbFormat::Hex);
+    }
+
+    #[test]
+    fn check_database_truncated() {
+        match check_database(":02000000AABB99\n") {
+            Err(FlashError::DbTruncated) => {}
+            other => panic!("Expected truncated database, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flash_refuses_truncated_database_before_opening_port() {
+        use argh::FromArgs;
+
+        let db = std::env::temp_dir().join(format!("ibisibi-truncated-{}.hex", std::process::id()));
+        std::fs::write(&db, ":02000000AABB99\n").unwrap();
+        // nothing is registered for the port, so opening it would fail
+        let opts = Flash::from_args(
+            &["flash"],
+            &[
+                db.to_str().unwrap(),
+                "-a",
+                "1",
+                "-s",
+                "/dev/ttyUnregisteredMock",
+            ],
+        )
+        .unwrap();
+
+        let result = flash(opts, &OpenRetry::default());
+        std::fs::remove_file(&db).unwrap();
+        match result {
+            Err(FlashError::DbTruncated) => {}
+            other => panic!("Expected truncated database, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_database_corrupt_record() {
+        match check_database(":02000000AABB98\n:00000001FF\n") {
+            Err(FlashError::DbCorrupt(_)) => {}
+            other => panic!("Expected corrupt database, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_database_beyond_capacity() {
+        check_database(":02FFFE00AABB9C\n:00000001FF\n").unwrap();
+        const DB: &str =
+            ":20FFF000AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAB1
+:00000001FF
+";
+        match check_database(DB) {
+            Err(FlashError::DbTooLarge(0x10010)) => {}
+            other => panic!("Expected too large database, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn progress_report_percentage() {
+        let mut report = ProgressReport::new(64, true);
+        report.step(FlashStep::SelectAddress { address: 1 });
+        report.step(FlashStep::ChunkWritten {
+            offset: 0x00,
+            len: 32,
+        });
+        assert_eq!(report.to_string(), "Flashed 32/64 bytes (50%)");
+        report.step(FlashStep::ChunkWritten {
+            offset: 0x20,
+            len: 32,
+        });
+        assert_eq!(report.to_string(), "Flashed 64/64 bytes (100%)");
+
+        // starts over for the next device
+        report.step(FlashStep::SelectAddress { address: 2 });
+        assert_eq!(report.percent(), 0);
+    }
+
+    #[test]
+    fn verify_database_ok() {
+        const DB: &str = ":02000000AABB99
+:00002000E0
+:02002000CCDD35
+:00000001FF
+";
+        let mut serial = Serial::builder()
+            .expect_write(query::read(0x00, 2).unwrap().as_bytes())
+            .respond(&readback_response(&[0xAA, 0xBB]))
+            .expect_write(query::read(0x20, 2).unwrap().as_bytes())
+            .respond(&readback_response(&[0xCC, 0xDD]))
+            .build();
+
+        verify_database(&mut serial, Reader::new(DB)).unwrap();
+    }
+
+    #[test]
+    fn verify_database_mismatch() {
+        const DB: &str = ":02000000AABB99
+:02002000CCDD35
+:00000001FF
+";
+        let mut serial = Serial::builder()
+            .expect_write(query::read(0x00, 2).unwrap().as_bytes())
+            .respond(&readback_response(&[0xAA, 0xBB]))
+            .expect_write(query::read(0x20, 2).unwrap().as_bytes())
+            .respond(&readback_response(&[0xCC, 0x00]))
+            .build();
+
+        match verify_database(&mut serial, Reader::new(DB)) {
+            Err(FlashError::VerifyMismatch {
+                offset: 0x21,
+                expected: 0xDD,
+                received: Some(0x00),
+            }) => {}
+            other => panic!("Expected mismatch, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_clear_aborts_before_writing_chunks() {
+        let mut cleared = [query::CLEAR_FILL; 0x20];
+        cleared[4] = 0x57;
+        let mut serial = Serial::builder();
+        serial
+            .expect_write(Telegram::empty().as_bytes())
+            .expect_write(Telegram::bs_select_address(1).as_bytes())
+            .expect_write(query::prepare_clear_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::prepare_clear_1().as_bytes())
+            .respond(&[0x4f, 0x01, 0x57, 0xa8]);
+        for _ in 0..4 {
+            serial.expect_write(query::clear().as_bytes()).respond(b"E");
+        }
+        // no chunks are expected to be written after the readback
+        let mut serial = serial
+            .expect_write(query::finish_clear_0().as_bytes())
+            .respond(b"O")
+            .expect_write(query::finish_clear_1().as_bytes())
+            .respond(b"O")
+            .expect_write(query::read(0x0000, 0x20).unwrap().as_bytes())
+            .respond(&readback_response(&cleared))
+            .build();
+        let options = Options {
+            verify_clear: true,
+            ..Options::default()
+        };
+
+        match perform_flashing(
+            &mut serial,
+            1,
+            ":02000000AABB99\n:00000001FF\n",
+            &options,
+            &mut |_| {},
+        ) {
+            Err(FlashError::NotCleared {
+                address: 4,
+                found: 0x57,
+            }) => {}
+            other => panic!("Expected database not to be cleared, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn phase_timeouts_default_to_global_timeout() {
+        let args = [
+            "flash",
+            "db.hex",
+            "-a",
+            "1",
+            "-s",
+            "/dev/ttyUSB0",
+            "--timeout",
+            "3",
+            "--chunk-ack-timeout",
+            "500ms",
+        ];
+        let args: crate::args::TopLevel = argh::FromArgs::from_args(&["ibisibi"], &args).unwrap();
+        let flash = match args.invocation {
+            crate::args::Invocation::Flash(flash) => flash,
+            _ => panic!("unexpected subcommand"),
+        };
+        let options = Options::from(&flash);
+        assert_eq!(options.timeout, Some(Duration::from_secs(3)));
+        assert_eq!(options.clear_timeout, Some(Duration::from_secs(3)));
+        assert_eq!(options.chunk_ack_timeout, Some(Duration::from_millis(500)));
+    }
+
+    const MINI0: &str =
+        ":20000000570012001B00121C8B4506F900E001000AE001050A0080016001A0004F00003083
 :200020000D0D0D0D0D0D0D0D0D0D0D0D0D0D0D00000000E001000A004F004F004F004F00D6
 :100040004F00004F0000000000000000000000FF13
 :12006000464E543A20674255534530202D20312E323157
@@ -312,8 +2014,11 @@ mod test {
 :0D01A0003030310700E0B0C04141410DFF9B
 :00000001FF
 ";
-        let reader = Reader::new(MINI0);
-        let mut serial = Serial::builder()
+
+    /// Mock of a sign that responds like we observed during actual flashing
+    /// of mini0.
+    fn mini0_serial() -> Serial {
+        Serial::builder()
             // The initial address selection, no response expected
             .expect_write(&[0x0d, 0x72])
             .expect_write(&[0x1b, 0x53, 0x31, 0x0d, 0x0b])
@@ -438,8 +2143,47 @@ mod test {
             .expect_write(&[
                 0x01, 0x0f, 0xf0, 0x01, 0x0f, 0xf0, 0x01, 0x0f, 0xf0, 0x01, 0x0f, 0xf0,
             ])
-            .build();
+            .build()
+    }
 
-        perform_flashing(&mut serial, 1, reader).expect("flashing should succeed here");
+    /// Tests that an attempt to flash mini0 reproduces what we observed during actual flashing.
+    #[test]
+    fn flash_mini0_happy_path() {
+        let mut serial = mini0_serial();
+
+        perform_flashing(&mut serial, 1, MINI0, &Options::default(), &mut |_| {})
+            .expect("flashing should succeed here");
+    }
+
+    #[test]
+    fn flash_mini0_steps() {
+        let mut serial = mini0_serial();
+        let mut steps = vec![];
+
+        perform_flashing(&mut serial, 1, MINI0, &Options::default(), &mut |step| {
+            steps.push(step)
+        })
+        .expect("flashing should succeed here");
+
+        let mut expected = vec![
+            FlashStep::SelectAddress { address: 1 },
+            FlashStep::ClearStarted,
+            FlashStep::ClearBlock(0),
+            FlashStep::ClearBlock(1),
+            FlashStep::ClearBlock(2),
+            FlashStep::ClearBlock(3),
+        ];
+        let lens = [
+            0x20, 0x20, 0x10, 0x12, 0x20, 0x20, 0x20, 0x20, 0x20, 0x0d, 0x12, 0x0e, 0x12, 0x0d,
+        ];
+        for (idx, &len) in lens.iter().enumerate() {
+            expected.push(FlashStep::ChunkWritten {
+                offset: idx as u16 * 0x20,
+                len,
+            });
+        }
+        expected.push(FlashStep::FinishStarted);
+        expected.push(FlashStep::Done);
+        assert_eq!(steps, expected);
     }
 }
@@ -0,0 +1,184 @@
+//! Named groups of IBIS addresses, loaded from a YAML file like:
+//!
+//! ```yaml
+//! group_a: [1, 2, 3]
+//! front: [4]
+//! ```
+//!
+//! Commands that accept a `--group` resolve it to the addresses in the group
+//! and apply to each of them in order.
+
+use crate::error_kind::ErrorKind;
+use serde_yaml::from_reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, GroupsError>;
+
+/// Highest address that can be encoded in an IBIS telegram.
+const ADDRESS_MAX: u8 = 15;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Groups(HashMap<String, Vec<u8>>);
+
+impl Groups {
+    pub fn load(path: &Path) -> Result<Groups> {
+        let file = File::open(path).map_err(|e| GroupsError::io(e, path))?;
+        let groups = from_reader(file).map_err(|e| GroupsError::deserialize(e, path))?;
+        Self::from_map(groups)
+    }
+
+    fn from_map(groups: HashMap<String, Vec<u8>>) -> Result<Groups> {
+        for (name, addresses) in groups.iter() {
+            if let Some(&address) = addresses.iter().find(|&&a| a > ADDRESS_MAX) {
+                return Err(GroupsError::Address {
+                    group: name.clone(),
+                    address,
+                });
+            }
+        }
+        Ok(Groups(groups))
+    }
+
+    /// Gets the addresses in the group with the given name.
+    pub fn get(&self, name: &str) -> Result<&[u8]> {
+        self.0
+            .get(name)
+            .map(|addresses| &addresses[..])
+            .ok_or_else(|| GroupsError::Unknown {
+                group: name.to_string(),
+            })
+    }
+}
+
+/// Resolves the addresses a command applies to from either a single address
+/// or the name of a group defined in the groups file at `groups_path`.
+///
+/// Returns no addresses if neither is given, leaving it up to the command
+/// whether an address is required.
+pub fn resolve(
+    address: Option<u8>,
+    group: Option<&str>,
+    groups_path: Option<&Path>,
+) -> Result<Vec<u8>> {
+    match (address, group) {
+        (Some(_), Some(_)) => Err(GroupsError::AddressAndGroup),
+        (Some(address), None) => Ok(vec![address]),
+        (None, Some(group)) => {
+            let groups_path = groups_path.ok_or(GroupsError::NoGroupsFile)?;
+            Ok(Groups::load(groups_path)?.get(group)?.to_vec())
+        }
+        (None, None) => Ok(vec![]),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GroupsError {
+    #[error("Could not open address groups file {path}: {source}")]
+    IO {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not parse address groups file {path}: {source}")]
+    Deserialize {
+        source: serde_yaml::Error,
+        path: PathBuf,
+    },
+    #[error("Address {address} in group {group} is out of range 0-15")]
+    Address { group: String, address: u8 },
+    #[error("No address group named {group}")]
+    Unknown { group: String },
+    #[error("Use either an address or a group, but not both")]
+    AddressAndGroup,
+    #[error("A groups file is required to resolve a group, specify it with --groups")]
+    NoGroupsFile,
+}
+
+impl GroupsError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            GroupsError::IO { .. } | GroupsError::Deserialize { .. } => ErrorKind::Config,
+            GroupsError::Address { .. }
+            | GroupsError::Unknown { .. }
+            | GroupsError::AddressAndGroup
+            | GroupsError::NoGroupsFile => ErrorKind::Usage,
+        }
+    }
+}
+
+impl GroupsError {
+    fn io(source: std::io::Error, path: &Path) -> Self {
+        Self::IO {
+            source,
+            path: path.into(),
+        }
+    }
+
+    fn deserialize(source: serde_yaml::Error, path: &Path) -> Self {
+        Self::Deserialize {
+            source,
+            path: path.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_yaml::from_str;
+
+    fn groups(source: &str) -> Result<Groups> {
+        Groups::from_map(from_str(source).unwrap())
+    }
+
+    #[test]
+    fn resolve_group() {
+        let groups = groups("group_a: [1, 2, 3]\nfront: [4]").unwrap();
+        assert_eq!(groups.get("group_a").unwrap(), &[1, 2, 3]);
+        assert_eq!(groups.get("front").unwrap(), &[4]);
+    }
+
+    #[test]
+    fn unknown_group() {
+        let groups = groups("group_a: [1, 2, 3]").unwrap();
+        match groups.get("group_b").unwrap_err() {
+            GroupsError::Unknown { group } => assert_eq!(group, "group_b"),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn address_out_of_range() {
+        match groups("group_a: [1, 16]").unwrap_err() {
+            GroupsError::Address { address: 16, .. } => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn resolve_group_from_file() {
+        let addresses = resolve(
+            None,
+            Some("group_a"),
+            Some(Path::new("examples/groups.yaml")),
+        )
+        .unwrap();
+        assert_eq!(addresses, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn resolve_single_address() {
+        assert_eq!(resolve(Some(3), None, None).unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn resolve_group_without_file() {
+        match resolve(None, Some("group_a"), None).unwrap_err() {
+            GroupsError::NoGroupsFile => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Conversion between bytes and hexadecimal notation as used in captured
+//! frames, e.g. `61 30 0D 23`.
+
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Parses bytes in hexadecimal notation.
+///
+/// Bytes may be separated by whitespace but do not have to be, so both
+/// `61 30 0d` and `61300D` are accepted. Each whitespace-separated group of
+/// digits may have a `0x` prefix.
+pub fn parse_hex(source: &str) -> Result<Vec<u8>, ParseHexError> {
+    let mut bytes = vec![];
+    for group in source.split_whitespace() {
+        let digits = group
+            .strip_prefix("0x")
+            .or_else(|| group.strip_prefix("0X"))
+            .unwrap_or(group);
+        if digits.is_empty() || digits.len() % 2 != 0 || !digits.is_ascii() {
+            return Err(ParseHexError::malformed(group));
+        }
+        for pair in digits.as_bytes().chunks(2) {
+            // chunks of ASCII characters are always valid UTF-8
+            let pair = std::str::from_utf8(pair).unwrap();
+            let byte = u8::from_str_radix(pair, 16).map_err(|_| ParseHexError::malformed(group))?;
+            bytes.push(byte);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Formats the given bytes as upper-case hexadecimal, separated by spaces.
+pub fn format_hex(bytes: &[u8]) -> String {
+    let mut formatted = String::with_capacity(bytes.len() * 3);
+    for (idx, byte) in bytes.iter().enumerate() {
+        if idx > 0 {
+            formatted.push(' ');
+        }
+        // writing to a string cannot fail
+        write!(formatted, "{:02X}", byte).unwrap();
+    }
+    formatted
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseHexError {
+    #[error("Could not parse `{offending_input}` as bytes in hexadecimal notation")]
+    Malformed { offending_input: String },
+}
+
+impl ParseHexError {
+    fn malformed(source: &str) -> Self {
+        Self::Malformed {
+            offending_input: source.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_separated() {
+        assert_eq!(parse_hex("61 30 0d").unwrap(), vec![0x61, 0x30, 0x0d])
+    }
+
+    #[test]
+    fn parse_contiguous_with_prefix() {
+        assert_eq!(parse_hex("0x61300D").unwrap(), vec![0x61, 0x30, 0x0d])
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert!(parse_hex("").unwrap().is_empty())
+    }
+
+    #[test]
+    fn parse_odd_digit_count() {
+        assert_eq!(
+            parse_hex("61 3").unwrap_err(),
+            ParseHexError::Malformed {
+                offending_input: "3".to_string()
+            }
+        )
+    }
+
+    #[test]
+    fn parse_non_hex() {
+        assert_eq!(
+            parse_hex("6g").unwrap_err(),
+            ParseHexError::Malformed {
+                offending_input: "6g".to_string()
+            }
+        )
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_hex(&[0x61, 0x30, 0x0d]), "61 30 0D")
+    }
+}
@@ -0,0 +1,194 @@
+//! A span of time that can be parsed from strings like `500ms`, `1s`, `1.5s`,
+//! `30m` or `2h`.
+//!
+//! Numbers without a unit are interpreted as seconds.
+
+use serde::{de, Deserialize, Deserializer};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Interval(Duration);
+
+impl Interval {
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl Display for Interval {
+    /// Formats the interval in seconds, or in milliseconds if it is not a
+    /// whole number of seconds, e.g. `5s` or `500ms`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.subsec_nanos() == 0 {
+            write!(f, "{}s", self.0.as_secs())
+        } else {
+            write!(f, "{}ms", self.0.as_secs_f64() * 1000.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for Interval {
+    type Err = ParseIntervalError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        if source.is_empty() {
+            return Err(ParseIntervalError::Blank);
+        }
+
+        let unit_start = source
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(source.len());
+        let (amount, unit) = source.split_at(unit_start);
+        let secs_per_unit = match unit {
+            "ms" => 0.001,
+            "" | "s" => 1.0,
+            "m" => 60.0,
+            "h" => 60.0 * 60.0,
+            _ => return Err(ParseIntervalError::unit(source)),
+        };
+        let amount = amount
+            .parse::<f64>()
+            .map_err(|_| ParseIntervalError::malformed(source))?;
+        if !amount.is_finite() || amount < 0.0 {
+            return Err(ParseIntervalError::malformed(source));
+        }
+
+        Duration::try_from_secs_f64(amount * secs_per_unit)
+            .map(Interval)
+            .map_err(|_| ParseIntervalError::malformed(source))
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseIntervalError {
+    #[error("Could not parse blank string as a duration")]
+    Blank,
+    #[error("Could not parse `{offending_input}` as a duration, expected a non-negative number followed by a unit")]
+    Malformed { offending_input: String },
+    #[error("Unknown unit in duration `{offending_input}`, expected any of ms, s, m, h")]
+    Unit { offending_input: String },
+}
+
+impl ParseIntervalError {
+    fn malformed(source: &str) -> Self {
+        Self::Malformed {
+            offending_input: source.to_string(),
+        }
+    }
+
+    fn unit(source: &str) -> Self {
+        Self::Unit {
+            offending_input: source.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_secs() {
+        let interval: Interval = "1s".parse().unwrap();
+        assert_eq!(interval.as_duration(), Duration::from_secs(1))
+    }
+
+    #[test]
+    fn parse_without_unit() {
+        let interval: Interval = "5".parse().unwrap();
+        assert_eq!(interval.as_duration(), Duration::from_secs(5))
+    }
+
+    #[test]
+    fn parse_fractional_secs() {
+        let interval: Interval = "0.5s".parse().unwrap();
+        assert_eq!(interval.as_duration(), Duration::from_millis(500))
+    }
+
+    #[test]
+    fn parse_millis() {
+        let interval: Interval = "250ms".parse().unwrap();
+        assert_eq!(interval.as_duration(), Duration::from_millis(250))
+    }
+
+    #[test]
+    fn parse_minutes_and_hours() {
+        let minutes: Interval = "30m".parse().unwrap();
+        let hours: Interval = "2h".parse().unwrap();
+        assert_eq!(minutes.as_duration(), Duration::from_secs(30 * 60));
+        assert_eq!(hours.as_duration(), Duration::from_secs(2 * 60 * 60))
+    }
+
+    #[test]
+    fn parse_unknown_unit() {
+        let source = "3d";
+        assert_eq!(
+            source.parse::<Interval>().unwrap_err(),
+            ParseIntervalError::Unit {
+                offending_input: source.to_string()
+            }
+        )
+    }
+
+    #[test]
+    fn parse_negative() {
+        let source = "-3s";
+        assert_eq!(
+            source.parse::<Interval>().unwrap_err(),
+            ParseIntervalError::Malformed {
+                offending_input: source.to_string()
+            }
+        )
+    }
+
+    #[test]
+    fn parse_too_long() {
+        let source = "1000000000000000000000000000000h";
+        assert_eq!(
+            source.parse::<Interval>().unwrap_err(),
+            ParseIntervalError::Malformed {
+                offending_input: source.to_string()
+            }
+        )
+    }
+
+    #[test]
+    fn parse_unit_only() {
+        let source = "s";
+        assert_eq!(
+            source.parse::<Interval>().unwrap_err(),
+            ParseIntervalError::Malformed {
+                offending_input: source.to_string()
+            }
+        )
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(
+            "".parse::<Interval>().unwrap_err(),
+            ParseIntervalError::Blank
+        )
+    }
+
+    #[test]
+    fn display() {
+        let secs: Interval = "90".parse().unwrap();
+        let millis: Interval = "0.25s".parse().unwrap();
+        assert_eq!(secs.to_string(), "90s");
+        assert_eq!(millis.to_string(), "250ms")
+    }
+}
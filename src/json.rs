@@ -0,0 +1,22 @@
+//! Formatting of JSON output for other programs to read, e.g. found devices
+//! or errors with `--json-errors`.
+
+use std::fmt::Write as _;
+
+/// Quotes and escapes the text as a JSON string.
+pub fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
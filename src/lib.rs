@@ -0,0 +1,22 @@
+//! Building blocks of the `ibisibi` command line tool, for programs that
+//! talk IBIS to displays without shelling out to the CLI.
+//!
+//! The modules exported here are the public API:
+//!
+//! * [`telegram`]: IBIS telegrams like destination and status queries,
+//!   including parsing of responses,
+//! * [`parity`]: the parity byte that ends every telegram,
+//! * [`record`]: records in the protocol used to flash BS210 signs,
+//! * [`range`], [`slot`] and [`plan`]: what `cycle` shows and when,
+//! * [`interval`]: durations like `500ms` or `30s`, used by plans.
+//!
+//! Serial communication, flashing and the other commands are part of the
+//! binary and may change without notice.
+
+pub mod interval;
+pub mod parity;
+pub mod plan;
+pub mod range;
+pub mod record;
+pub mod slot;
+pub mod telegram;
@@ -0,0 +1,186 @@
+//! Sends only the line number, e.g. when the destination is already set and
+//! only the route number changes, or steps through a range of lines once.
+
+use crate::args::Line;
+use crate::echo;
+use crate::error_kind::ErrorKind;
+use crate::output;
+use crate::port::{self, PortError};
+use crate::range::Range;
+use crate::serial::{open, OpenRetry};
+use crate::telegram::Telegram;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::thread::sleep;
+use std::time::Duration;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, LineError>;
+
+/// Nothing is read back after sending a line, so this only limits how long
+/// writing may take.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Time to wait between lines when a range of lines is set and no interval
+/// was specified.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn line(opts: &Line, retry: &OpenRetry) -> Result<()> {
+    check_lines(&opts.line)?;
+    if let Some(path) = &opts.output {
+        return output::create(path)
+            .and_then(|mut out| send_lines(&mut out, opts))
+            .map_err(|source| LineError::Output {
+                source,
+                path: output::name(path),
+            });
+    }
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial = open(&port, TIMEOUT, retry).map_err(|source| LineError::Serial {
+        source,
+        port: port.clone(),
+    })?;
+    send_lines(&mut serial, opts).map_err(|source| LineError::IO { source, port })
+}
+
+/// Refuses ranges of lines that go below 1 or beyond 999 at either end, so
+/// that nothing is sent if any of the lines cannot be.
+pub fn check_lines(lines: &Range) -> Result<()> {
+    let mut iter = lines.iter();
+    let first = iter.next().unwrap_or(0);
+    let last = iter.next_back().unwrap_or(first);
+    for &line in &[first.min(last), first.max(last)] {
+        match u16::try_from(line) {
+            Ok(line) if Telegram::try_line(line).is_ok() => {}
+            _ => return Err(LineError::OutOfRange(line)),
+        }
+    }
+    Ok(())
+}
+
+fn send_lines<W: Write + ?Sized>(serial: &mut W, opts: &Line) -> std::io::Result<()> {
+    let interval = opts
+        .interval
+        .map(|i| i.as_duration())
+        .unwrap_or(DEFAULT_INTERVAL);
+    for (nth, line) in opts.line.iter().enumerate() {
+        if nth > 0 {
+            sleep(interval);
+        }
+        // fits since the range was checked before sending anything
+        send_line(serial, line as u16)?;
+    }
+    Ok(())
+}
+
+fn send_line<W: Write + ?Sized>(serial: &mut W, line: u16) -> std::io::Result<()> {
+    let telegram = Telegram::line(line);
+    echo::telegram(&telegram);
+    serial.write_all(telegram.as_bytes())?;
+    serial.flush()
+}
+
+#[derive(Error, Debug)]
+pub enum LineError {
+    #[error("Line {0} is out of range 1-999")]
+    OutOfRange(usize),
+    #[error("Could not send line to port: {port}, due to I/O error: {source}")]
+    IO {
+        source: std::io::Error,
+        port: String,
+    },
+    #[error("Could not write line to: {path}, due to I/O error: {source}")]
+    Output {
+        source: std::io::Error,
+        path: String,
+    },
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+}
+
+impl LineError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            LineError::OutOfRange(_) => ErrorKind::Usage,
+            LineError::IO { source, .. } => ErrorKind::of_io(source),
+            LineError::Output { .. } => ErrorKind::Io,
+            LineError::Serial { .. } => ErrorKind::Port,
+            LineError::Port(error) => error.kind(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serial::Serial;
+
+    fn output_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ibisibi-{}-{}.bin", name, std::process::id()))
+    }
+
+    #[test]
+    fn sends_only_line() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::line(26).as_bytes())
+            .build();
+        send_line(&mut serial, 26).unwrap();
+    }
+
+    #[test]
+    fn writes_line_to_output() {
+        let path = output_path("line");
+        let opts = Line {
+            line: Range::single(26),
+            interval: None,
+            output: Some(path.clone()),
+            serial: None,
+        };
+        let result = line(&opts, &OpenRetry::default());
+        let written = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+        assert_eq!(written.unwrap(), Telegram::line(26).as_bytes());
+    }
+
+    #[test]
+    fn steps_through_range_of_lines() {
+        let path = output_path("lines");
+        let opts = Line {
+            line: "1-3".parse().unwrap(),
+            interval: Some("1ms".parse().unwrap()),
+            output: Some(path.clone()),
+            serial: None,
+        };
+        let result = line(&opts, &OpenRetry::default());
+        let written = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+        let expected: Vec<u8> = (1..=3)
+            .flat_map(|line| Telegram::line(line).as_bytes().to_vec())
+            .collect();
+        assert_eq!(written.unwrap(), expected);
+    }
+
+    #[test]
+    fn line_out_of_range() {
+        for (lines, out_of_range) in &[("1000", 1000), ("998-1000", 1000), ("0-3", 0)] {
+            let opts = Line {
+                line: lines.parse().unwrap(),
+                interval: None,
+                output: None,
+                serial: None,
+            };
+            match line(&opts, &OpenRetry::default()) {
+                Err(LineError::OutOfRange(line)) => assert_eq!(line, *out_of_range),
+                other => panic!("Expected {} out of range, got: {:?}", lines, other),
+            }
+        }
+    }
+}
@@ -1,16 +1,158 @@
 use crate::args::List;
+use crate::error_kind::ErrorKind;
+use crate::scan::first_responding;
+use crate::serial::{open, OpenRetry};
+use serialport::{SerialPortInfo, SerialPortType};
+use std::time::Duration;
 use thiserror::Error;
+use tracing::{event, Level};
 
-pub fn list(_list: List) -> Result<(), ListError> {
-    let ports = serialport::available_ports()?;
+/// Time to wait for each status response when probing, short since devices
+/// usually respond within a few milliseconds and there are up to 16
+/// addresses to try per port.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+pub fn list(list: List) -> Result<(), ListError> {
+    let mut ports = serialport::available_ports()?;
+    if list.probe {
+        ports = probe(ports);
+    }
     for p in ports {
-        println!("{}", p.port_name);
+        if list.verbose {
+            println!("{}", describe(&p));
+        } else {
+            println!("{}", p.port_name);
+        }
     }
     Ok(())
 }
 
+/// Keeps only the ports where a device responds to a status query at any
+/// address, skipping ports that cannot be opened, e.g. Bluetooth ports
+/// without a paired device.
+fn probe(ports: Vec<SerialPortInfo>) -> Vec<SerialPortInfo> {
+    ports
+        .into_iter()
+        .filter(|port| {
+            let mut serial = match open(&port.port_name, PROBE_TIMEOUT, &OpenRetry::default()) {
+                Ok(serial) => serial,
+                Err(error) => {
+                    event!(Level::DEBUG, port = %port.port_name, %error, "Could not open port to probe");
+                    return false;
+                }
+            };
+            first_responding(&mut serial).is_some()
+        })
+        .collect()
+}
+
+/// Describes the port with how it is connected, e.g.
+/// `/dev/ttyUSB0 (USB 0403:6001, FT232R USB UART)`.
+fn describe(port: &SerialPortInfo) -> String {
+    let port_type = match &port.port_type {
+        SerialPortType::UsbPort(usb) => match &usb.product {
+            Some(product) => format!("USB {:04x}:{:04x}, {}", usb.vid, usb.pid, product),
+            None => format!("USB {:04x}:{:04x}", usb.vid, usb.pid),
+        },
+        SerialPortType::PciPort => "PCI".to_string(),
+        SerialPortType::BluetoothPort => "Bluetooth".to_string(),
+        SerialPortType::Unknown => "unknown type".to_string(),
+    };
+    format!("{} ({})", port.port_name, port_type)
+}
+
 #[derive(Error, Debug)]
 pub enum ListError {
     #[error("Could not list serial ports: {0}")]
     Serial(#[from] serialport::Error),
 }
+
+impl ListError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ListError::Serial(_) => ErrorKind::Port,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serial::{mock, Serial};
+    use crate::telegram::Telegram;
+    use serialport::UsbPortInfo;
+
+    fn usb(product: Option<&str>) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: "/dev/ttyUSB0".to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some("A50285BI".to_string()),
+                manufacturer: Some("FTDI".to_string()),
+                product: product.map(str::to_string),
+            }),
+        }
+    }
+
+    #[test]
+    fn describe_usb() {
+        assert_eq!(
+            describe(&usb(Some("FT232R USB UART"))),
+            "/dev/ttyUSB0 (USB 0403:6001, FT232R USB UART)"
+        );
+        assert_eq!(describe(&usb(None)), "/dev/ttyUSB0 (USB 0403:6001)");
+    }
+
+    #[test]
+    fn probe_keeps_responding_ports() {
+        let mut silent = Serial::builder();
+        for address in 0..=15 {
+            silent
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .time_out();
+        }
+        mock::register("/dev/ttyProbeSilent", silent.build());
+        mock::register(
+            "/dev/ttyProbeSign",
+            Serial::builder()
+                .expect_write(Telegram::display_status(0).as_bytes())
+                .time_out()
+                .expect_write(Telegram::display_status(1).as_bytes())
+                .respond(b"a3\r ")
+                .expect_write(Telegram::display_version(1).as_bytes())
+                .time_out()
+                .build(),
+        );
+        let ports = [
+            "/dev/ttyProbeSilent",
+            "/dev/ttyProbeSign",
+            "/dev/ttyProbeGone",
+        ]
+        .iter()
+        .map(|name| SerialPortInfo {
+            port_name: name.to_string(),
+            port_type: SerialPortType::Unknown,
+        })
+        .collect();
+
+        let probed: Vec<String> = probe(ports).into_iter().map(|p| p.port_name).collect();
+
+        assert_eq!(probed, vec!["/dev/ttyProbeSign".to_string()]);
+    }
+
+    #[test]
+    fn describe_native() {
+        let port = SerialPortInfo {
+            port_name: "/dev/ttyS0".to_string(),
+            port_type: SerialPortType::PciPort,
+        };
+        assert_eq!(describe(&port), "/dev/ttyS0 (PCI)");
+        let port = SerialPortInfo {
+            port_name: "COM1".to_string(),
+            port_type: SerialPortType::Unknown,
+        };
+        assert_eq!(describe(&port), "COM1 (unknown type)");
+    }
+}
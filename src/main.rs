@@ -1,32 +1,95 @@
 #![feature(backtrace)]
 
-use tracing::Level;
-
+mod active;
+mod argfile;
 mod args;
+mod blank;
+mod check;
+mod checksum;
+mod coverage;
 mod cycle;
 mod destination;
 mod devices;
+mod dump;
+mod echo;
+mod error_kind;
+mod firmware;
 mod flash;
+mod groups;
+mod hex;
+mod json;
+mod line;
 mod list;
-mod parity;
-mod plan;
-mod range;
-mod record;
+mod monitor;
+mod output;
+mod port;
+mod presence;
+mod preview;
 mod run;
 mod scan;
+mod script;
+mod send;
 mod serial;
-mod slot;
+mod shuffle;
+mod shutdown;
 mod status;
-mod telegram;
+mod summary;
+mod trace;
+mod version;
+
+// modules shared with the library, re-imported so that they are available
+// under the same paths as the modules of the binary
+use ibisibi::{interval, parity, plan, range, record, slot, telegram};
 
-fn main() -> Result<(), String> {
+fn main() {
     std::env::set_var("RUST_BACKTRACE", "1"); // always enable backtraces
 
+    // looked for before parsing so that invalid command lines are reported
+    // as JSON too
+    let args = std::env::args().collect::<Vec<_>>();
+    let args = match argfile::expand(args.clone()) {
+        Ok(args) => args,
+        Err(error) => fail(error.into(), wants_json_errors(&args)),
+    };
+    let args = match args::from_strings(&args) {
+        Ok(parsed) => parsed,
+        Err(output) => fail(run::CmdError::Args(output), wants_json_errors(&args)),
+    };
+    let json_errors = args.json_errors;
+
     tracing_subscriber::fmt()
-        .with_max_level(Level::TRACE)
+        .with_max_level(args.log_level)
         .with_writer(std::io::stderr)
         .init();
 
-    let args: args::TopLevel = argh::from_env();
-    run::run(args.invocation)
+    if args.echo {
+        echo::enable();
+    }
+    if let Some(path) = &args.trace_file {
+        if let Err(source) = trace::enable(path) {
+            let path = path.clone();
+            fail(run::CmdError::Trace { path, source }, json_errors);
+        }
+    }
+    let retry = serial::OpenRetry::from(&args);
+    if let Err(error) = run::run(args.invocation, &retry) {
+        fail(error, json_errors);
+    }
+}
+
+fn wants_json_errors(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "--json-errors")
+}
+
+/// Reports the error on stderr and exits with its exit code.
+fn fail(error: run::CmdError, json_errors: bool) -> ! {
+    let code = error.exit_code();
+    match error {
+        _ if json_errors => eprintln!("{}", error.json()),
+        // help for invalid command lines is printed as argh formats it
+        run::CmdError::Args(output) => eprintln!("{}", output),
+        // formatted like an error returned from main
+        error => eprintln!("Error: {:?}", error.to_string()),
+    }
+    std::process::exit(code)
 }
@@ -0,0 +1,164 @@
+//! Passive listening on the bus, printing the telegrams that other masters
+//! and devices send, without sending anything.
+//!
+//! Telegrams have no fixed length, so the received bytes are split into
+//! frames after each carriage return and the parity byte that follows it.
+
+use crate::args::Monitor;
+use crate::error_kind::ErrorKind;
+use crate::hex::format_hex;
+use crate::port::{self, PortError};
+use crate::serial::{open, OpenRetry};
+use crate::telegram::{Telegram, TelegramParseError};
+use std::convert::TryFrom;
+use std::io::{self, Read};
+use std::time::Duration;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, MonitorError>;
+
+/// The bus may be idle for a long time, so reads time out regularly and are
+/// just tried again.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Upper bound for bytes without a carriage return before they are reported
+/// as a malformed frame, so that noise does not pile up.
+const MAX_FRAME_LEN: usize = 256;
+
+pub fn monitor(opts: &Monitor, retry: &OpenRetry) -> Result<()> {
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial = open(&port, READ_TIMEOUT, retry).map_err(|source| MonitorError::Serial {
+        source,
+        port: port.clone(),
+    })?;
+
+    let mut framer = Framer::default();
+    let mut buf = [0_u8; 64];
+    loop {
+        let len = match serial.read(&mut buf) {
+            Ok(len) => len,
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => continue,
+            Err(source) => return Err(MonitorError::IO { source, port }),
+        };
+        for &byte in &buf[..len] {
+            if let Some(frame) = framer.push(byte) {
+                println!("{}", describe(&frame));
+            }
+        }
+    }
+}
+
+/// Splits received bytes into frames that end in a carriage return and a
+/// parity byte.
+#[derive(Debug, Default)]
+struct Framer {
+    buf: Vec<u8>,
+    after_cr: bool,
+}
+
+impl Framer {
+    /// Adds the byte to the current frame and returns the frame if it is
+    /// complete.
+    ///
+    /// The byte after a carriage return is always taken as the parity byte,
+    /// even if it is a carriage return itself.
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        self.buf.push(byte);
+        if self.after_cr || self.buf.len() >= MAX_FRAME_LEN {
+            self.after_cr = false;
+            return Some(std::mem::take(&mut self.buf));
+        }
+        self.after_cr = byte == b'\r';
+        None
+    }
+}
+
+/// Describes a received frame as the decoded telegram with the state of its
+/// parity, or as hex if it is malformed.
+fn describe(frame: &[u8]) -> String {
+    match Telegram::try_from(frame) {
+        Ok(telegram) => format!("{} parity ok", telegram),
+        Err(TelegramParseError::Parity { expected, received }) => format!(
+            "{} parity error, expected {:02X}, received {:02X}",
+            String::from_utf8_lossy(&frame[..frame.len() - 2]),
+            expected,
+            received
+        ),
+        Err(TelegramParseError::Malformed) => format!("malformed {}", format_hex(frame)),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MonitorError {
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("Could not read from port: {port}, due to I/O error: {source}")]
+    IO {
+        source: std::io::Error,
+        port: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+}
+
+impl MonitorError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            MonitorError::Serial { .. } => ErrorKind::Port,
+            MonitorError::IO { source, .. } => ErrorKind::of_io(source),
+            MonitorError::Port(error) => error.kind(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frames(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut framer = Framer::default();
+        bytes.iter().filter_map(|&b| framer.push(b)).collect()
+    }
+
+    #[test]
+    fn frames_after_parity() {
+        let status = Telegram::display_status(1);
+        let line = Telegram::line(26);
+        let bytes: Vec<u8> = status
+            .as_bytes()
+            .iter()
+            .chain(line.as_bytes())
+            .chain(b"a3")
+            .copied()
+            .collect();
+        assert_eq!(
+            frames(&bytes),
+            vec![status.as_bytes().to_vec(), line.as_bytes().to_vec()]
+        );
+    }
+
+    #[test]
+    fn parity_byte_can_be_cr() {
+        assert_eq!(frames(b"x\r\r"), vec![b"x\r\r".to_vec()]);
+    }
+
+    #[test]
+    fn frames_noise() {
+        let noise = vec![0xFF; MAX_FRAME_LEN];
+        assert_eq!(frames(&noise), vec![noise]);
+    }
+
+    #[test]
+    fn describe_frames() {
+        assert_eq!(describe(b"a3\r "), "a3<CR><P:20> parity ok");
+        assert_eq!(
+            describe(b"a3\r?"),
+            "a3 parity error, expected 20, received 3F"
+        );
+        assert_eq!(describe(&[0xFF, 0xFF]), "malformed FF FF");
+    }
+}
@@ -0,0 +1,29 @@
+//! Writing telegrams to a file or to stdout with `--output` instead of sending
+//! them over a serial port, e.g. to capture the exact bytes for documentation
+//! or to replay them over another transport.
+
+use std::fs::File;
+use std::io::{self, stdout, Write};
+use std::path::Path;
+
+/// Path that stands for stdout instead of a file when given as the output.
+pub const STDOUT_PATH: &str = "-";
+
+/// Creates the file at the path, replacing any previous content, or writes to
+/// stdout if the path is [`STDOUT_PATH`].
+pub fn create(path: &Path) -> io::Result<Box<dyn Write>> {
+    if path == Path::new(STDOUT_PATH) {
+        Ok(Box::new(stdout()))
+    } else {
+        Ok(Box::new(File::create(path)?))
+    }
+}
+
+/// Names the output for messages, e.g. `stdout` or the path of the file.
+pub fn name(path: &Path) -> String {
+    if path == Path::new(STDOUT_PATH) {
+        "stdout".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
@@ -1,48 +1,77 @@
+use crate::interval::{Interval, ParseIntervalError};
 use crate::range::{ParseRangeError, Range};
 use crate::slot::{ParseSlotError, Slot};
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer};
+use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 use thiserror::Error;
 
-/// A range with an optinal associated time range.
+/// Ranges of destinations with an optional associated time range.
 #[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(from = "PlanConfig")]
 pub struct Plan {
+    destinations: Vec<DestinationRange>,
+    slots: Vec<Slot>,
+}
+
+/// A plan as written in configuration files, where a line and a dwell time
+/// can be set for all ranges that do not specify one.
+#[derive(Deserialize)]
+struct PlanConfig {
     line: Option<u16>,
-    destinations: Vec<Range>,
+    dwell: Option<Interval>,
+    destinations: Vec<DestinationRange>,
     #[serde(default)]
     slots: Vec<Slot>,
 }
 
-impl Plan {
-    #[cfg(test)]
-    pub fn range(range_str: &str) -> Plan {
+impl From<PlanConfig> for Plan {
+    fn from(config: PlanConfig) -> Self {
+        let line = config.line;
+        let dwell = config.dwell;
+        let destinations = config
+            .destinations
+            .into_iter()
+            .map(|destinations| DestinationRange {
+                line: destinations.line.or(line),
+                dwell: destinations.dwell.or(dwell),
+                ..destinations
+            })
+            .collect();
         Plan {
-            line: None,
-            destinations: vec![range_str
-                .parse()
-                .expect("could not parse range for test plan")],
-            slots: vec![],
+            destinations,
+            slots: config.slots,
         }
     }
+}
 
-    #[cfg(test)]
-    pub fn range_start_end(range_str: &str, slot_str: &str) -> Plan {
-        Plan {
-            line: None,
-            destinations: vec![range_str
-                .parse()
-                .expect("could not parse range for test plan")],
-            slots: vec![slot_str
-                .parse()
-                .expect("could not parse time range for test plan")],
-        }
-    }
+/// A range of destinations, optionally shown along with a line number and
+/// for a different time than the interval of the cycle, e.g. `6:0-3*10s`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DestinationRange {
+    line: Option<u16>,
+    range: Range,
+    dwell: Option<Interval>,
+}
 
+impl DestinationRange {
     pub fn line(&self) -> Option<u16> {
         self.line
     }
 
-    pub fn destinations(&self) -> &[Range] {
+    pub fn range(&self) -> &Range {
+        &self.range
+    }
+
+    /// How long to show each destination of the range, if it differs from
+    /// the interval of the cycle.
+    pub fn dwell(&self) -> Option<Interval> {
+        self.dwell
+    }
+}
+
+impl Plan {
+    pub fn destinations(&self) -> &[DestinationRange] {
         &self.destinations[..]
     }
 
@@ -51,6 +80,40 @@ impl Plan {
     }
 }
 
+impl Display for Plan {
+    /// Formats the plan in the notation it is parsed from, e.g.
+    /// `1:0-10,2:11@2021-06-03T00:00:00/2021-06-04T00:00:00`.
+    ///
+    /// Plans with multiple slots, which can only be specified in
+    /// configuration files, separate them with commas.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (idx, destinations) in self.destinations.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", destinations)?;
+        }
+        for (idx, slot) in self.slots.iter().enumerate() {
+            f.write_str(if idx == 0 { "@" } else { "," })?;
+            write!(f, "{}", slot)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DestinationRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        write!(f, "{}", self.range)?;
+        if let Some(dwell) = self.dwell {
+            write!(f, "*{}", dwell)?;
+        }
+        Ok(())
+    }
+}
+
 impl FromStr for Plan {
     type Err = ParsePlanError;
 
@@ -60,23 +123,24 @@ impl FromStr for Plan {
         }
 
         let mut tokens = source.split('@');
-        let (line, range) = {
-            let mut optional_line_then_range = tokens.next().unwrap().split(':'); // unwrap is safe because we checked for empty above
-            let line_or_range = match optional_line_then_range.next() {
-                Some(line) => line,
-                None => return Err(ParsePlanError::Blank),
-            };
-            let range_when_line_defined = optional_line_then_range.next();
-            match range_when_line_defined {
-                Some(range) => (Some(line_or_range.parse::<u16>()?), range),
-                None => (None, line_or_range),
-            }
-        };
-
-        let destinations = vec![range.parse()?]; // unwrap is safe because we checked for empty above
+        let destinations = tokens
+            .next()
+            .unwrap() // unwrap is safe because we checked for empty above
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<DestinationRange>, _>>()?;
         let slots = match tokens.next() {
             Some(scheduled_slot) => {
-                let slot: Slot = scheduled_slot.parse()?;
+                let slot: Slot = match tokens.next() {
+                    // weekdays are also separated with an @, e.g. in
+                    // 0@08:00-17:00*daily@mon-fri, and are parsed with the
+                    // slot so that misspelled days are reported as such
+                    Some(weekdays) if looks_like_weekdays(weekdays) => {
+                        format!("{}@{}", scheduled_slot, weekdays).parse()?
+                    }
+                    Some(_) => return Err(ParsePlanError::too_much(source)),
+                    None => scheduled_slot.parse()?,
+                };
                 vec![slot]
             }
             None => vec![],
@@ -87,13 +151,59 @@ impl FromStr for Plan {
         }
 
         Ok(Plan {
-            line,
             destinations,
             slots,
         })
     }
 }
 
+/// Checks whether the part after a second `@` holds weekdays rather than
+/// another scheduled time, going by its characters only.
+fn looks_like_weekdays(source: &str) -> bool {
+    source
+        .chars()
+        .all(|c| c.is_ascii_alphabetic() || c == ',' || c == '-')
+}
+
+impl FromStr for DestinationRange {
+    type Err = ParsePlanError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        if source.is_empty() {
+            return Err(ParsePlanError::Blank);
+        }
+
+        let (source, dwell) = match source.split_once('*') {
+            Some((source, dwell)) => (source, Some(dwell.parse()?)),
+            None => (source, None),
+        };
+
+        // the step of a range is also separated with a colon, so only take
+        // the part before the first colon as the line if it is not a range,
+        // e.g. in 6:0-10:2 but not in 0-10:2
+        let (line, range) = match source.split_once(':') {
+            Some((line, range)) if !line.contains('-') => (Some(line.parse::<u16>()?), range),
+            _ => (None, source),
+        };
+
+        Ok(DestinationRange {
+            line,
+            range: range.parse()?,
+            dwell,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for DestinationRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum ParsePlanError {
     #[error("Could not parse blank string as a number or number range")]
@@ -106,6 +216,8 @@ pub enum ParsePlanError {
     ParseRange(#[from] ParseRangeError),
     #[error("{0}")]
     ParseSlot(#[from] ParseSlotError),
+    #[error("Could not parse dwell time: {0}")]
+    ParseDwell(#[from] ParseIntervalError),
 }
 
 impl ParsePlanError {
@@ -128,8 +240,7 @@ mod test {
         assert_eq!(
             input,
             Plan {
-                line: Some(1),
-                destinations: vec!["0-10".parse().unwrap()],
+                destinations: vec!["1:0-10".parse().unwrap()],
                 slots: vec!["2020-01-01T00:00:00/2020-01-01T00:00:00".parse().unwrap()]
             }
         );
@@ -141,8 +252,7 @@ mod test {
         assert_eq!(
             input,
             Plan {
-                line: Some(1),
-                destinations: vec!["0".parse().unwrap()],
+                destinations: vec!["1:0".parse().unwrap()],
                 slots: vec![]
             }
         );
@@ -156,7 +266,6 @@ mod test {
         assert_eq!(
             input,
             Plan {
-                line: None,
                 destinations: vec!["0-10".parse().unwrap()],
                 slots: vec!["2020-01-01T00:00:00/2020-01-01T00:00:00".parse().unwrap()]
             }
@@ -169,13 +278,116 @@ mod test {
         assert_eq!(
             input,
             Plan {
-                line: None,
                 destinations: vec!["0".parse().unwrap()],
                 slots: vec![]
             }
         )
     }
 
+    #[test]
+    fn parse_with_line_per_range() {
+        let input: Plan = "6:0-3,12:4-7,8".parse().unwrap();
+        let lines: Vec<Option<u16>> = input.destinations().iter().map(|d| d.line()).collect();
+        let ranges: Vec<Range> = input.destinations().iter().map(|d| *d.range()).collect();
+        assert_eq!(lines, vec![Some(6), Some(12), None]);
+        assert_eq!(
+            ranges,
+            vec![
+                "0-3".parse().unwrap(),
+                "4-7".parse().unwrap(),
+                "8".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_mixed_singles_and_ranges() {
+        let input = "1,5,8-10@2020-01-01T00:00:00/2020-01-02T00:00:00";
+        let plan: Plan = input.parse().unwrap();
+        let indexes: Vec<usize> = plan
+            .destinations()
+            .iter()
+            .flat_map(|d| d.range().iter())
+            .collect();
+        assert_eq!(indexes, vec![1, 5, 8, 9, 10]);
+        // all ranges share the slot
+        assert_eq!(plan.slots().len(), 1);
+        assert_eq!(plan.to_string(), input);
+    }
+
+    #[test]
+    fn empty_range_in_list() {
+        assert_eq!("1,,3".parse::<Plan>().unwrap_err(), ParsePlanError::Blank);
+    }
+
+    #[test]
+    fn parse_stepped_ranges() {
+        let input: Plan = "0-10:2,6:1-9:2".parse().unwrap();
+        let lines: Vec<Option<u16>> = input.destinations().iter().map(|d| d.line()).collect();
+        let ranges: Vec<Range> = input.destinations().iter().map(|d| *d.range()).collect();
+        assert_eq!(lines, vec![None, Some(6)]);
+        assert_eq!(
+            ranges,
+            vec!["0-10:2".parse().unwrap(), "1-9:2".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn deserialize_line_for_ranges_without_line() {
+        let plan: Plan = serde_yaml::from_str(
+            "line: 6
+destinations: [0-3, \"12:4-7\"]
+",
+        )
+        .unwrap();
+        assert_eq!(plan, "6:0-3,12:4-7".parse().unwrap());
+    }
+
+    #[test]
+    fn parse_with_weekdays() {
+        let input: Plan = "0@08:00-17:00*daily@sat,sun".parse().unwrap();
+        assert_eq!(
+            input.slots(),
+            &["08:00-17:00*daily@sat,sun".parse::<Slot>().unwrap()][..]
+        );
+    }
+
+    #[test]
+    fn parse_dwell() {
+        let input: Plan = "6:0-3*10s,4-7,8*1.5s".parse().unwrap();
+        let dwells: Vec<Option<Interval>> =
+            input.destinations().iter().map(|d| d.dwell()).collect();
+        assert_eq!(
+            dwells,
+            vec![
+                Some("10s".parse().unwrap()),
+                None,
+                Some("1.5s".parse().unwrap())
+            ]
+        );
+        assert_eq!(input.destinations()[0].line(), Some(6));
+        assert_eq!(input.destinations()[0].range(), &"0-3".parse().unwrap());
+    }
+
+    #[test]
+    fn malformed_dwell() {
+        match "0-3*10x".parse::<Plan>().unwrap_err() {
+            ParsePlanError::ParseDwell(_) => (),
+            error => panic!("Unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn deserialize_dwell_for_ranges_without_dwell() {
+        let plan: Plan = serde_yaml::from_str(
+            "dwell: 20s
+destinations: [0-3, \"4*5s\"]
+",
+        )
+        .unwrap();
+        assert_eq!(plan, "0-3*20s,4*5s".parse().unwrap());
+    }
+
     #[test]
     fn blank() {
         let plan_error = "".parse::<Plan>().unwrap_err();
@@ -195,6 +407,17 @@ mod test {
         )
     }
 
+    #[test]
+    fn misspelled_weekday() {
+        let input = "0@08:00-17:00*daily@mon-frx";
+        assert_eq!(
+            input.parse::<Plan>().unwrap_err(),
+            ParsePlanError::ParseSlot(ParseSlotError::Weekday {
+                not_parsed: "frx".to_string()
+            })
+        )
+    }
+
     #[test]
     fn malformed_range() {
         let input = "0--9@2020-01-01T00:00:00/2020-01-01T00:00:00";
@@ -212,4 +435,21 @@ mod test {
             error => panic!("Unexpected error: {:?}", error),
         }
     }
+
+    #[test]
+    fn display_roundtrip() {
+        for source in &[
+            "0",
+            "1:0-10",
+            "1:0-10@2020-01-01T00:00:00/2020-01-02T00:00:00",
+            "6:0-3,12:4-7,8",
+            "0-10:2,6:1-9:2",
+            "0@08:00:00-17:00:00*daily",
+            "0@08:00:00-17:00:00*daily@mon-fri",
+            "6:0-3*10s,4*500ms@08:00:00-17:00:00*daily",
+        ] {
+            let plan: Plan = source.parse().unwrap();
+            assert_eq!(&plan.to_string(), source)
+        }
+    }
 }
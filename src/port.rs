@@ -0,0 +1,148 @@
+//! Picks the serial port to use when none was specified.
+//!
+//! If exactly one port is available, that port is used. If there are
+//! multiple, the port with the most typical name for a USB serial adapter on
+//! the current platform is chosen, and the alternatives are printed:
+//!
+//! * Linux: `/dev/ttyUSB*`, then `/dev/ttyACM*`, then anything else,
+//! * macOS: `/dev/tty.usbserial*`, then `/dev/cu.usbserial*`, then
+//!   `/dev/tty.usbmodem*`, then anything else,
+//! * Windows: the `COM` port with the highest number, since adapters plugged
+//!   in last get the highest numbers.
+//!
+//! Ports with equal precedence are chosen in the order they are listed by
+//! the operating system.
+
+use crate::error_kind::ErrorKind;
+use thiserror::Error;
+
+/// Returns the explicitly specified port, or picks one of the available
+/// ports if `None`.
+pub fn resolve(explicit: Option<&str>) -> Result<String, PortError> {
+    if let Some(port) = explicit {
+        return Ok(port.to_string());
+    }
+
+    let ports: Vec<String> = serialport::available_ports()?
+        .into_iter()
+        .map(|p| p.port_name)
+        .collect();
+    let chosen = choose(&ports, std::env::consts::OS).ok_or(PortError::NoPorts)?;
+    let alternatives: Vec<&str> = ports
+        .iter()
+        .filter(|&p| p != chosen)
+        .map(|p| &p[..])
+        .collect();
+    if alternatives.is_empty() {
+        eprintln!("Using serial port {}", chosen);
+    } else {
+        eprintln!(
+            "Using serial port {}, use --serial to pick any of: {}",
+            chosen,
+            alternatives.join(", ")
+        );
+    }
+    Ok(chosen.to_string())
+}
+
+/// Chooses the port with the highest precedence for the given operating
+/// system, as named by [`std::env::consts::OS`].
+fn choose<'a>(ports: &'a [String], os: &str) -> Option<&'a str> {
+    ports
+        .iter()
+        .enumerate()
+        // max_by_key returns the last maximum, so prefer earlier ports on ties
+        .max_by_key(|(idx, port)| (precedence(port, os), std::cmp::Reverse(*idx)))
+        .map(|(_, port)| &port[..])
+}
+
+fn precedence(port: &str, os: &str) -> u32 {
+    match os {
+        "linux" => {
+            if port.starts_with("/dev/ttyUSB") {
+                2
+            } else if port.starts_with("/dev/ttyACM") {
+                1
+            } else {
+                0
+            }
+        }
+        "macos" => {
+            if port.starts_with("/dev/tty.usbserial") {
+                3
+            } else if port.starts_with("/dev/cu.usbserial") {
+                2
+            } else if port.starts_with("/dev/tty.usbmodem") {
+                1
+            } else {
+                0
+            }
+        }
+        // COM1 has precedence 2, so that anything not named COM comes last
+        "windows" => port
+            .strip_prefix("COM")
+            .and_then(|number| number.parse::<u32>().ok())
+            .map(|number| number + 1)
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PortError {
+    #[error("Could not list serial ports to pick one: {0}")]
+    List(#[from] serialport::Error),
+    #[error("No serial port specified and no serial ports available")]
+    NoPorts,
+}
+
+impl PortError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            PortError::List(_) | PortError::NoPorts => ErrorKind::Port,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ports(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn single_port() {
+        let ports = ports(&["/dev/ttyS0"]);
+        assert_eq!(choose(&ports, "linux"), Some("/dev/ttyS0"));
+    }
+
+    #[test]
+    fn no_ports() {
+        assert_eq!(choose(&[], "linux"), None);
+    }
+
+    #[test]
+    fn linux_prefers_usb() {
+        let ports = ports(&["/dev/ttyS0", "/dev/ttyACM0", "/dev/ttyUSB1", "/dev/ttyUSB0"]);
+        assert_eq!(choose(&ports, "linux"), Some("/dev/ttyUSB1"));
+    }
+
+    #[test]
+    fn macos_prefers_usbserial() {
+        let ports = ports(&[
+            "/dev/tty.Bluetooth-Incoming-Port",
+            "/dev/cu.usbserial-1420",
+            "/dev/tty.usbserial-1420",
+        ]);
+        assert_eq!(choose(&ports, "macos"), Some("/dev/tty.usbserial-1420"));
+    }
+
+    #[test]
+    fn windows_prefers_highest_com() {
+        let ports = ports(&["COM1", "COM12", "COM3"]);
+        assert_eq!(choose(&ports, "windows"), Some("COM12"));
+    }
+}
@@ -0,0 +1,181 @@
+//! Checks whether a display device responds to a status query, with the exit
+//! code telling scripts whether one did, e.g. `if ibisibi status
+//! --any-address; then ...`.
+//!
+//! The module is not called `status` since that name is taken by the status
+//! query itself.
+
+use crate::{
+    args::Status as Opts,
+    error_kind::ErrorKind,
+    port::{self, PortError},
+    scan::first_responding,
+    serial::{open, OpenRetry, Serial},
+    status::{self, status, Status},
+};
+use std::time::Duration;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, PresenceError>;
+
+/// Prints the address and status of the display at the address or, with
+/// `--any-address`, of the display with the lowest address that responds, or
+/// fails with [`PresenceError::NoResponse`] if none responds.
+pub fn presence(opts: Opts, retry: &OpenRetry) -> Result<()> {
+    let target = target(&opts)?;
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial = open(&port, Duration::from_secs(opts.timeout_secs), retry).map_err(|e| {
+        PresenceError::Serial {
+            source: e,
+            port: port.clone(),
+        }
+    })?;
+
+    let (address, status) = find(&mut serial, target)?.ok_or(PresenceError::NoResponse)?;
+    println!("{:X?}: {}", address, status);
+    Ok(())
+}
+
+/// Which addresses to query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Address(u8),
+    Any,
+}
+
+fn target(opts: &Opts) -> Result<Target> {
+    match (opts.address, opts.any_address) {
+        (Some(address), false) if address > 15 => Err(PresenceError::Address(address)),
+        (Some(address), false) => Ok(Target::Address(address)),
+        (None, true) => Ok(Target::Any),
+        _ => Err(PresenceError::Target),
+    }
+}
+
+/// Queries the status at the targeted addresses, returning `None` if no
+/// display responded in time.
+fn find(serial: &mut Serial, target: Target) -> Result<Option<(u8, Status)>> {
+    match target {
+        Target::Address(address) => match status(serial, address) {
+            Ok(status) => Ok(Some((address, status))),
+            Err(error) if error.is_timed_out() => Ok(None),
+            Err(error) => Err(error.into()),
+        },
+        Target::Any => Ok(first_responding(serial).map(|find| (find.address(), find.status()))),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PresenceError {
+    #[error("Address {0} is out of range 0-15")]
+    Address(u8),
+    #[error("Specify either --address or --any-address")]
+    Target,
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+    #[error("{0}")]
+    Status(#[from] status::Error),
+    #[error("No display device responded")]
+    NoResponse,
+}
+
+impl PresenceError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            PresenceError::Address(_) | PresenceError::Target => ErrorKind::Usage,
+            PresenceError::Serial { .. } => ErrorKind::Port,
+            PresenceError::Port(error) => error.kind(),
+            PresenceError::Status(error) => error.kind(),
+            PresenceError::NoResponse => ErrorKind::NoResponse,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::telegram::Telegram;
+
+    fn opts(address: Option<u8>, any_address: bool) -> Opts {
+        Opts {
+            address,
+            any_address,
+            timeout_secs: 3,
+            serial: Some("/dev/ttyStatusMock".to_string()),
+        }
+    }
+
+    #[test]
+    fn address_or_any() {
+        assert_eq!(target(&opts(Some(1), false)).unwrap(), Target::Address(1));
+        assert_eq!(target(&opts(None, true)).unwrap(), Target::Any);
+        for (address, any_address) in &[(None, false), (Some(1), true)] {
+            match target(&opts(*address, *any_address)) {
+                Err(PresenceError::Target) => {}
+                other => panic!("Expected target error, got: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn status_at_address() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .build();
+
+        let found = find(&mut serial, Target::Address(1)).unwrap();
+
+        assert_eq!(found, Some((1, Status::Ok)));
+    }
+
+    #[test]
+    fn no_response_at_address() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .time_out()
+            .build();
+
+        assert_eq!(find(&mut serial, Target::Address(1)).unwrap(), None);
+    }
+
+    #[test]
+    fn first_responder() {
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(0).as_bytes())
+            .time_out()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .time_out()
+            .expect_write(Telegram::display_status(2).as_bytes())
+            .respond(b"a0\r#")
+            .expect_write(Telegram::display_version(2).as_bytes())
+            .time_out()
+            .build();
+
+        let found = find(&mut serial, Target::Any).unwrap();
+
+        assert_eq!(found, Some((2, Status::ReadyForData)));
+    }
+
+    #[test]
+    fn fails_without_responder() {
+        let mut serial = Serial::builder();
+        for address in 0..=15 {
+            serial
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .time_out();
+        }
+        crate::serial::mock::register("/dev/ttyStatusMock", serial.build());
+
+        match presence(opts(None, true), &OpenRetry::default()) {
+            Err(PresenceError::NoResponse) => {}
+            other => panic!("Expected no response, got: {:?}", other),
+        }
+    }
+}
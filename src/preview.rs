@@ -0,0 +1,151 @@
+//! Prints when the plans of a cycle would be shown over the next hours,
+//! without connecting to any device, to check a schedule before going live.
+
+use crate::args::Cycle;
+use crate::cycle::is_active;
+use crate::slot::Slot;
+use chrono::{DateTime, Duration as ChronoDuration, Local, TimeZone};
+use std::fmt::Display;
+
+/// Resolution of the preview, slot boundaries in between are rounded up to
+/// the next step.
+const STEP_MINUTES: i64 = 1;
+
+/// Format of the start and end of each time span in the preview.
+const SPAN_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+pub fn preview(cycle: &Cycle, hours: u32) {
+    for line in preview_lines(cycle, &Local::now(), hours) {
+        println!("{}", line);
+    }
+}
+
+/// Lists each plan followed by the indented time spans in which it would be
+/// shown, in the order of the plans.
+fn preview_lines<Tz>(cycle: &Cycle, from: &DateTime<Tz>, hours: u32) -> Vec<String>
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    let mut lines = vec![];
+    for plan in &cycle.plan {
+        lines.push(plan.to_string());
+        let spans = active_spans(cycle, plan.slots(), from, hours);
+        if spans.is_empty() {
+            lines.push("  not shown".to_string());
+        }
+        for (start, end) in spans {
+            lines.push(format!(
+                "  {} - {}",
+                start.format(SPAN_FORMAT),
+                end.format(SPAN_FORMAT)
+            ));
+        }
+    }
+    lines
+}
+
+/// Finds the time spans between `from` and the given number of hours later
+/// in which a plan with the given slots is active, merging consecutive steps.
+fn active_spans<Tz: TimeZone>(
+    cycle: &Cycle,
+    slots: &[Slot],
+    from: &DateTime<Tz>,
+    hours: u32,
+) -> Vec<(DateTime<Tz>, DateTime<Tz>)> {
+    let lookahead = ChronoDuration::hours(cycle.lookahead as i64);
+    let step = ChronoDuration::minutes(STEP_MINUTES);
+    let until = from.clone() + ChronoDuration::hours(hours as i64);
+
+    let mut spans = vec![];
+    let mut span_start: Option<DateTime<Tz>> = None;
+    let mut now = from.clone();
+    while now < until {
+        let active = is_active(slots, lookahead, &now);
+        match (active, span_start.take()) {
+            (true, None) => span_start = Some(now.clone()),
+            (true, Some(start)) => span_start = Some(start),
+            (false, Some(start)) => spans.push((start, now.clone())),
+            (false, None) => {}
+        }
+        now = now + step;
+    }
+    if let Some(start) = span_start {
+        spans.push((start, until));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::args::Invocation;
+    use crate::slot::parse_slot_time;
+    use chrono::Utc;
+    use serde_yaml::from_str;
+
+    fn at(source: &str) -> DateTime<Utc> {
+        parse_slot_time(source).unwrap().in_zone(&Utc)
+    }
+
+    fn cycle(yaml: &str) -> Cycle {
+        match from_str(yaml).unwrap() {
+            Invocation::Cycle(cycle) => cycle,
+            _ => panic!("Expected cycle"),
+        }
+    }
+
+    #[test]
+    fn example_timeline() {
+        let cycle = cycle(include_str!("../examples/cycle.yaml"));
+        assert_eq!(
+            preview_lines(&cycle, &at("2021-09-09T12:00:00"), 24),
+            vec![
+                "0",
+                "  2021-09-09 12:00 - 2021-09-10 12:00",
+                "6@2021-09-09T18:00:00/2021-09-10T00:00:00",
+                // the lookahead of 12 hours makes it active right away
+                "  2021-09-09 12:00 - 2021-09-10 00:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn daily_without_lookahead() {
+        let cycle = cycle(
+            "cycle:
+  plan:
+    - destinations: [\"1\"]
+      slots: [\"08:00-17:00*daily\"]
+  interval_secs: 5
+  lookahead: 0
+",
+        );
+        assert_eq!(
+            preview_lines(&cycle, &at("2021-09-09T12:00:00"), 24),
+            vec![
+                "1@08:00:00-17:00:00*daily",
+                "  2021-09-09 12:00 - 2021-09-09 17:00",
+                // slots become active only after their start
+                "  2021-09-10 08:01 - 2021-09-10 12:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn not_shown_within_preview() {
+        let cycle = cycle(
+            "cycle:
+  plan:
+    - destinations: [\"2\"]
+      slots: [\"2021-09-20T18:00:00/2021-09-20T20:00:00\"]
+  interval_secs: 5
+  lookahead: 0
+",
+        );
+        assert_eq!(
+            preview_lines(&cycle, &at("2021-09-09T12:00:00"), 24),
+            vec!["2@2021-09-20T18:00:00/2021-09-20T20:00:00", "  not shown"]
+        );
+    }
+}
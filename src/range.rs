@@ -3,10 +3,14 @@
 //!
 //! Can be parsed from strings like `0-10` but also single numbers like `4`.
 //! The notation is inclusive for both the start and the end element.
+//!
+//! A step can be added after a colon, e.g. `0-10:2` for every second index.
+//! The end is then only included if the step lands on it.
 
 use serde::{de, Deserialize, Deserializer};
 use std::cmp::Ordering;
-use std::iter::Iterator;
+use std::fmt::{self, Display, Formatter};
+use std::iter::{DoubleEndedIterator, ExactSizeIterator, Iterator};
 use std::str::FromStr;
 use thiserror::Error;
 
@@ -14,6 +18,7 @@ use thiserror::Error;
 pub struct Range {
     from: usize,
     to: usize,
+    step: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -33,12 +38,92 @@ impl<'de> Deserialize<'de> for Range {
 }
 
 impl Range {
+    /// A range that contains only the given index.
+    pub fn single(index: usize) -> Range {
+        Range {
+            from: index,
+            to: index,
+            step: 1,
+        }
+    }
+
+    /// Number of indexes in the range, including the start and the end if
+    /// the step lands on it.
+    ///
+    /// Saturates at `usize::MAX` for the one range that has more elements,
+    /// `0-` followed by `usize::MAX`.
+    pub fn len(&self) -> usize {
+        (self.distance() / self.step).saturating_add(1)
+    }
+
+    /// Always false, since ranges contain at least their start.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
     pub fn iter(&self) -> RangeIter {
+        // move the end to the last index that is actually reached, so that
+        // iterating from both ends meets in the same place
+        let last_distance = self.distance() / self.step * self.step;
+        let to = if self.from > self.to {
+            self.from - last_distance
+        } else {
+            self.from + last_distance
+        };
         RangeIter {
-            range: *self,
+            range: Range { to, ..*self },
             exhausted: false,
         }
     }
+
+    /// Drops the indexes above `max` from the range, or returns `None` if no
+    /// index of the range is left.
+    ///
+    /// Backward ranges that start above `max` then start at the first index
+    /// their step lands on below it, e.g. `20-0:3` clamped to 15 is `14-0:3`.
+    pub fn clamp_max(&self, max: usize) -> Option<Range> {
+        if self.from <= self.to {
+            if self.from > max {
+                return None;
+            }
+            Some(Range {
+                to: self.to.min(max),
+                ..*self
+            })
+        } else {
+            if self.to > max {
+                return None;
+            }
+            let from = if self.from > max {
+                let steps = (self.from - max).div_ceil(self.step);
+                self.from - steps * self.step
+            } else {
+                self.from
+            };
+            if from < self.to {
+                return None;
+            }
+            Some(Range { from, ..*self })
+        }
+    }
+
+    fn distance(&self) -> usize {
+        self.from.max(self.to) - self.from.min(self.to)
+    }
+}
+
+impl Display for Range {
+    /// Formats the range in the same notation it is parsed from, e.g. `0-10`,
+    /// or just `4` for a range that contains a single element.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.from == self.to {
+            write!(f, "{}", self.from)
+        } else if self.step == 1 {
+            write!(f, "{}-{}", self.from, self.to)
+        } else {
+            write!(f, "{}-{}:{}", self.from, self.to, self.step)
+        }
+    }
 }
 
 impl Iterator for RangeIter {
@@ -56,12 +141,46 @@ impl Iterator for RangeIter {
             }
             Ordering::Less => {
                 let next = self.range.from;
-                self.range.from = self.range.from.saturating_add(1);
+                self.range.from = self.range.from.saturating_add(self.range.step);
                 Some(next)
             }
             Ordering::Greater => {
                 let next = self.range.from;
-                self.range.from = self.range.from.saturating_sub(1);
+                self.range.from = self.range.from.saturating_sub(self.range.step);
+                Some(next)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = if self.exhausted { 0 } else { self.range.len() };
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for RangeIter {}
+
+impl DoubleEndedIterator for RangeIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+
+        // `to` moves towards `from` and is a whole number of steps away from
+        // it, so it cannot pass the bounds of `usize`
+        match self.range.from.cmp(&self.range.to) {
+            Ordering::Equal => {
+                self.exhausted = true;
+                Some(self.range.to)
+            }
+            Ordering::Less => {
+                let next = self.range.to;
+                self.range.to -= self.range.step;
+                Some(next)
+            }
+            Ordering::Greater => {
+                let next = self.range.to;
+                self.range.to += self.range.step;
                 Some(next)
             }
         }
@@ -76,6 +195,11 @@ impl FromStr for Range {
             return Err(ParseRangeError::Blank);
         }
 
+        let (source, step) = match source.split_once(':') {
+            Some((range, step)) => (range, parse_step(step)?),
+            None => (source, 1),
+        };
+
         if source == "-" {
             return Err(ParseRangeError::malformed(source));
         }
@@ -100,10 +224,21 @@ impl FromStr for Range {
         Ok(Range {
             from: first,
             to: second,
+            step,
         })
     }
 }
 
+fn parse_step(source: &str) -> Result<usize, ParseRangeError> {
+    match source.parse::<usize>() {
+        Ok(0) => Err(ParseRangeError::ZeroStep),
+        Ok(step) => Ok(step),
+        Err(_) => Err(ParseRangeError::MalformedStep {
+            offending_input: source.to_string(),
+        }),
+    }
+}
+
 fn parse_num_or_zero_when_empty(source: &str) -> Result<usize, ParseRangeError> {
     Ok(if source.is_empty() {
         0
@@ -120,6 +255,10 @@ pub enum ParseRangeError {
     NumberFormat(#[from] std::num::ParseIntError),
     #[error("Could not parse blank string as a range")]
     Blank,
+    #[error("Could not parse `{offending_input}` as the step of a range")]
+    MalformedStep { offending_input: String },
+    #[error("The step of a range must not be zero")]
+    ZeroStep,
 }
 
 impl ParseRangeError {
@@ -134,40 +273,101 @@ impl ParseRangeError {
 mod test {
     use super::*;
 
+    #[test]
+    fn clamp_max() {
+        let clamped = |range: &str| {
+            range
+                .parse::<Range>()
+                .unwrap()
+                .clamp_max(15)
+                .map(|range| range.to_string())
+        };
+        assert_eq!(clamped("0-3"), Some("0-3".to_string()));
+        assert_eq!(clamped("4"), Some("4".to_string()));
+        assert_eq!(clamped("10-99"), Some("10-15".to_string()));
+        assert_eq!(clamped("99-10"), Some("15-10".to_string()));
+        assert_eq!(clamped("20-0:3"), Some("14-0:3".to_string()));
+        assert_eq!(clamped("20-14:10"), None);
+        assert_eq!(clamped("16-99"), None);
+        assert_eq!(clamped("99-16"), None);
+    }
+
     #[test]
     fn parse_single_num() {
         let range: Range = "0".parse().unwrap();
-        assert_eq!(range, Range { from: 0, to: 0 })
+        assert_eq!(
+            range,
+            Range {
+                from: 0,
+                to: 0,
+                step: 1
+            }
+        )
     }
 
     #[test]
     fn parse_single_elem_range() {
         let range: Range = "0-0".parse().unwrap();
-        assert_eq!(range, Range { from: 0, to: 0 })
+        assert_eq!(
+            range,
+            Range {
+                from: 0,
+                to: 0,
+                step: 1
+            }
+        )
     }
 
     #[test]
     fn parse_forward_range() {
         let range: Range = "10-100".parse().unwrap();
-        assert_eq!(range, Range { from: 10, to: 100 })
+        assert_eq!(
+            range,
+            Range {
+                from: 10,
+                to: 100,
+                step: 1
+            }
+        )
     }
 
     #[test]
     fn parse_backward_range() {
         let range: Range = "100-10".parse().unwrap();
-        assert_eq!(range, Range { from: 100, to: 10 })
+        assert_eq!(
+            range,
+            Range {
+                from: 100,
+                to: 10,
+                step: 1
+            }
+        )
     }
 
     #[test]
     fn parse_missing_start() {
         let range: Range = "-10".parse().unwrap();
-        assert_eq!(range, Range { from: 0, to: 10 })
+        assert_eq!(
+            range,
+            Range {
+                from: 0,
+                to: 10,
+                step: 1
+            }
+        )
     }
 
     #[test]
     fn parse_missing_end() {
         let range: Range = "10-".parse().unwrap();
-        assert_eq!(range, Range { from: 10, to: 0 })
+        assert_eq!(
+            range,
+            Range {
+                from: 10,
+                to: 0,
+                step: 1
+            }
+        )
     }
 
     #[test]
@@ -250,22 +450,124 @@ mod test {
 
     #[test]
     fn iterate_single_elem() {
-        let range: Vec<usize> = Range { from: 0, to: 0 }.iter().collect();
+        let range: Vec<usize> = Range {
+            from: 0,
+            to: 0,
+            step: 1,
+        }
+        .iter()
+        .collect();
         assert_eq!(range, vec![0])
     }
 
     #[test]
     fn iterate_forward() {
-        let range: Vec<usize> = Range { from: 5, to: 7 }.iter().collect();
+        let range: Vec<usize> = Range {
+            from: 5,
+            to: 7,
+            step: 1,
+        }
+        .iter()
+        .collect();
         assert_eq!(range, vec![5, 6, 7])
     }
 
     #[test]
     fn iterate_backward() {
-        let range: Vec<usize> = Range { from: 2, to: 0 }.iter().collect();
+        let range: Vec<usize> = Range {
+            from: 2,
+            to: 0,
+            step: 1,
+        }
+        .iter()
+        .collect();
         assert_eq!(range, vec![2, 1, 0])
     }
 
+    #[test]
+    fn iterate_forward_reversed() {
+        let range: Vec<usize> = Range {
+            from: 5,
+            to: 7,
+            step: 1,
+        }
+        .iter()
+        .rev()
+        .collect();
+        assert_eq!(range, vec![7, 6, 5])
+    }
+
+    #[test]
+    fn iterate_backward_reversed() {
+        let range: Vec<usize> = Range {
+            from: 2,
+            to: 0,
+            step: 1,
+        }
+        .iter()
+        .rev()
+        .collect();
+        assert_eq!(range, vec![0, 1, 2])
+    }
+
+    #[test]
+    fn iterate_single_elem_reversed() {
+        let mut iter = Range {
+            from: 3,
+            to: 3,
+            step: 1,
+        }
+        .iter();
+        assert_eq!(iter.next_back(), Some(3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn iterate_both_ends_meet_in_middle() {
+        for &(from, to) in &[(5, 7), (7, 5), (0, 3), (3, 0)] {
+            let mut iter = Range { from, to, step: 1 }.iter();
+            let mut front = vec![];
+            let mut back = vec![];
+            while let Some(index) = iter.next() {
+                front.push(index);
+                match iter.next_back() {
+                    Some(index) => back.push(index),
+                    None => break,
+                }
+            }
+            back.reverse();
+            front.extend(back);
+            assert_eq!(
+                front,
+                Range { from, to, step: 1 }.iter().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn iterate_saturating_edges_from_both_ends() {
+        let mut iter = Range {
+            from: usize::MAX - 1,
+            to: usize::MAX,
+            step: 1,
+        }
+        .iter();
+        assert_eq!(iter.next_back(), Some(usize::MAX));
+        assert_eq!(iter.next(), Some(usize::MAX - 1));
+        assert_eq!(iter.next(), None);
+
+        let range: Vec<usize> = Range {
+            from: 1,
+            to: 0,
+            step: 1,
+        }
+        .iter()
+        .rev()
+        .collect();
+        assert_eq!(range, vec![0, 1]);
+    }
+
     #[test]
     fn iterate_flattened_cycled_vec() {
         let vec = vec![
@@ -280,4 +582,121 @@ mod test {
             .collect::<Vec<_>>();
         assert_eq!(flattened, vec![0, 1, 0, 0, 1])
     }
+
+    #[test]
+    fn parse_step() {
+        let range: Range = "0-10:2".parse().unwrap();
+        assert_eq!(
+            range,
+            Range {
+                from: 0,
+                to: 10,
+                step: 2
+            }
+        )
+    }
+
+    #[test]
+    fn parse_zero_step() {
+        assert_eq!("0-10:0".parse::<Range>(), Err(ParseRangeError::ZeroStep));
+    }
+
+    #[test]
+    fn parse_malformed_step() {
+        for source in &["0-10:", "0-10:x", "0-10:2:3"] {
+            match source.parse::<Range>() {
+                Err(ParseRangeError::MalformedStep { .. }) => (),
+                other => panic!(
+                    "parse unexpectedly succeeded or had unexpected error type: {:?}",
+                    other
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn iterate_forward_stepped() {
+        let range: Vec<usize> = "0-10:2".parse::<Range>().unwrap().iter().collect();
+        assert_eq!(range, vec![0, 2, 4, 6, 8, 10]);
+        let range: Vec<usize> = "0-10:3".parse::<Range>().unwrap().iter().collect();
+        assert_eq!(range, vec![0, 3, 6, 9]);
+    }
+
+    #[test]
+    fn iterate_backward_stepped() {
+        let range: Vec<usize> = "10-0:3".parse::<Range>().unwrap().iter().collect();
+        assert_eq!(range, vec![10, 7, 4, 1]);
+    }
+
+    #[test]
+    fn iterate_stepped_reversed() {
+        let range: Vec<usize> = "0-10:3".parse::<Range>().unwrap().iter().rev().collect();
+        assert_eq!(range, vec![9, 6, 3, 0]);
+        let range: Vec<usize> = "10-0:3".parse::<Range>().unwrap().iter().rev().collect();
+        assert_eq!(range, vec![1, 4, 7, 10]);
+    }
+
+    #[test]
+    fn iterate_step_beyond_end() {
+        let range: Vec<usize> = "5-7:10".parse::<Range>().unwrap().iter().collect();
+        assert_eq!(range, vec![5]);
+    }
+
+    #[test]
+    fn len_matches_iter() {
+        for source in &["4", "0-10", "100-10", "0-10:3", "10-0:2", "5-7:10"] {
+            let range: Range = source.parse().unwrap();
+            assert_eq!(range.len(), range.iter().count())
+        }
+    }
+
+    #[test]
+    fn len_of_single_forward_and_backward() {
+        assert_eq!(Range::single(4).len(), 1);
+        assert_eq!("0-10".parse::<Range>().unwrap().len(), 11);
+        assert_eq!("10-0".parse::<Range>().unwrap().len(), 11);
+    }
+
+    #[test]
+    fn exact_size_while_iterating() {
+        for source in &["4", "0-10", "100-10", "0-10:3", "10-0:2"] {
+            let mut iter = source.parse::<Range>().unwrap().iter();
+            let mut remaining = iter.clone().count();
+            while remaining > 0 {
+                assert_eq!(iter.len(), remaining);
+                if remaining % 2 == 0 {
+                    iter.next_back();
+                } else {
+                    iter.next();
+                }
+                remaining -= 1;
+            }
+            assert_eq!(iter.len(), 0);
+        }
+    }
+
+    #[test]
+    fn len_at_usize_bounds() {
+        let top = Range {
+            from: usize::MAX - 2,
+            to: usize::MAX,
+            step: 1,
+        };
+        assert_eq!(top.len(), 3);
+        assert_eq!(top.iter().len(), 3);
+        let everything = Range {
+            from: 0,
+            to: usize::MAX,
+            step: 1,
+        };
+        assert_eq!(everything.len(), usize::MAX);
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for source in &["4", "0-10", "100-10", "0-10:2"] {
+            let range: Range = source.parse().unwrap();
+            assert_eq!(&range.to_string(), source)
+        }
+    }
 }
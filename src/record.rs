@@ -13,6 +13,13 @@ pub struct Record {
 }
 
 impl Record {
+    /// Produces a record with a single message with the given payload, by
+    /// prepending the length and appending the checksum, e.g. for messages
+    /// not supported with a dedicated function yet.
+    pub fn custom(payload: &[u8]) -> Result<Record> {
+        Builder::new().buf(payload).build()
+    }
+
     /// The bytes of the full record, including the lengths and the checksums of all contained messages.
     ///
     /// Guaranteed to have a size of two bytes or more.
@@ -24,9 +31,6 @@ impl Record {
     ///
     /// If there are multiple messages in the record, returns only the first
     /// payload.
-    ///
-    /// Only use this method for tests.
-    #[cfg(test)]
     pub fn payload(&self) -> &[u8] {
         let record_len = self.data[0] as usize;
         assert!(
@@ -59,6 +63,7 @@ mod error;
 
 use builder::Builder;
 
+pub use checksum::checksum;
 pub use error::Error;
 pub type Result<T> = std::result::Result<T, Error>;
 pub use db::DatabaseChunk;
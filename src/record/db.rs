@@ -1,4 +1,14 @@
-use super::{Builder, Error, Record, Result};
+use super::{checksum::checksum, Builder, Error, Record, Result};
+
+/// First byte of the payload of every database chunk, of unknown purpose.
+const CHUNK_MARKER: u8 = 0x05;
+
+/// Record type of database chunks that hold data, as in IHEX.
+const RECORD_TYPE_DATA: u8 = 0;
+
+/// Bytes of a chunk without any data: length, marker, address, record type
+/// and checksum.
+const CHUNK_MIN_LEN: usize = 6;
 
 /// A record that represents a chunk from the line database, on the granularity of
 /// a single IHEX record, which can be sent over the wire for flashing of a flipdot
@@ -16,11 +26,11 @@ impl DatabaseChunk {
 
         Builder::new()
             // 1 byte 5 (unknown purpose)
-            .u8(0x05)
+            .u8(CHUNK_MARKER)
             // 2 bytes address (little endian)
             .u16(address)
             // record type 0 (data record)
-            .u8(0)
+            .u8(RECORD_TYPE_DATA)
             // then the actual database content
             .buf(content)
             .build()
@@ -34,14 +44,54 @@ impl DatabaseChunk {
         self.0.as_bytes()
     }
 
-    #[cfg(test)]
+    /// Decodes the address and the data from the bytes of a chunk as sent over
+    /// the wire, e.g. when reading back from a sign, checking the length,
+    /// the record type and the checksum.
+    pub fn parse(bytes: &[u8]) -> Result<(u16, Vec<u8>)> {
+        if bytes.len() < CHUNK_MIN_LEN {
+            return Err(Error::ChunkTooShort { len: bytes.len() });
+        }
+
+        let (record, received_checksum) = bytes.split_at(bytes.len() - 1);
+        let received_checksum = received_checksum[0];
+        let expected_checksum = checksum(record);
+        if received_checksum != expected_checksum {
+            return Err(Error::ResponseChecksumMismatch {
+                expected: expected_checksum,
+                received: received_checksum,
+            });
+        }
+
+        let payload = &record[1..];
+        if payload.len() > 0xFF {
+            return Err(Error::ResponseRecordLengthOutOfBounds { len: payload.len() });
+        }
+        if payload.len() as u8 != record[0] {
+            return Err(Error::ResponsePayloadLenMismatch {
+                expected: record[0],
+                received: payload.len() as u8,
+            });
+        }
+
+        let (marker, record_type) = (payload[0], payload[3]);
+        if marker != CHUNK_MARKER || record_type != RECORD_TYPE_DATA {
+            return Err(Error::ChunkNotData {
+                marker,
+                record_type,
+            });
+        }
+
+        let address = u16::from_le_bytes([payload[1], payload[2]]);
+        Ok((address, payload[4..].to_vec()))
+    }
+
+    /// The address the data is written to.
     pub fn address(&self) -> u16 {
         let payload = self.0.payload();
         u16::from_le_bytes([payload[1], payload[2]])
     }
 
     /// The data part of the record.
-    #[cfg(test)]
     pub fn data(&self) -> &[u8] {
         &self.0.payload()[4..]
     }
@@ -79,6 +129,81 @@ mod test {
         )
     }
 
+    #[test]
+    fn parse_round_trip() {
+        for &(address, data) in &[
+            (0x0000, &[][..]),
+            (0x0020, &[0xAA, 0xBB][..]),
+            (0xFF01, &[0x4F; 32][..]),
+            (0x1234, &[0x00; 0xFB][..]),
+        ] {
+            let chunk = DatabaseChunk::new(address, data).unwrap();
+            assert_eq!(
+                DatabaseChunk::parse(chunk.as_bytes()),
+                Ok((address, data.to_vec()))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_corrupt_checksum() {
+        let chunk = DatabaseChunk::new(0x20, &[0xAA, 0xBB]).unwrap();
+        let mut bytes = chunk.as_bytes().to_vec();
+        bytes[5] ^= 0x01;
+        match DatabaseChunk::parse(&bytes) {
+            Err(Error::ResponseChecksumMismatch { .. }) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_wrong_length() {
+        // length byte says 4 but there are 5 payload bytes, checksum adjusted
+        let mut bytes = vec![0x04, 0x05, 0x00, 0x00, 0x00, 0xAA];
+        bytes.push(checksum(&bytes));
+        assert_eq!(
+            DatabaseChunk::parse(&bytes),
+            Err(Error::ResponsePayloadLenMismatch {
+                expected: 4,
+                received: 5
+            })
+        );
+    }
+
+    #[test]
+    fn parse_not_data() {
+        let mut bytes = Record::custom(&[0x05, 0x00, 0x00, 0x01])
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        assert_eq!(
+            DatabaseChunk::parse(&bytes),
+            Err(Error::ChunkNotData {
+                marker: 0x05,
+                record_type: 0x01
+            })
+        );
+        bytes = Record::custom(&[0x06, 0x00, 0x00, 0x00])
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        assert_eq!(
+            DatabaseChunk::parse(&bytes),
+            Err(Error::ChunkNotData {
+                marker: 0x06,
+                record_type: 0x00
+            })
+        );
+    }
+
+    #[test]
+    fn parse_too_short() {
+        assert_eq!(
+            DatabaseChunk::parse(&[0x01, 0x05, 0xFA]),
+            Err(Error::ChunkTooShort { len: 3 })
+        );
+    }
+
     #[test]
     fn mini0_first_record_with_two_byte_address() {
         // The raw data part of the first record in mini0.hex
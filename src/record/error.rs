@@ -19,4 +19,8 @@ pub enum Error {
     ResponsePayloadLenMismatch { expected: u8, received: u8 },
     #[error("Response from sign corrupt, expected checksum: {expected:X?}, got: {received:X?}")]
     ResponseChecksumMismatch { expected: u8, received: u8 },
+    #[error("Database chunk is too short with {len} bytes, expected at least 6")]
+    ChunkTooShort { len: usize },
+    #[error("Not a database chunk, found marker {marker:X?} and record type {record_type:X?}")]
+    ChunkNotData { marker: u8, record_type: u8 },
 }
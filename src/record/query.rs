@@ -4,7 +4,7 @@
 //! process, but we do it in any case because it also verifies that what we are
 //! talking to behaves like a BS210 sign.
 
-use super::Record;
+use super::{Builder, Record, Result};
 use lazy_static::lazy_static;
 
 lazy_static! {
@@ -69,6 +69,28 @@ pub fn clear() -> &'static Record {
     &CLEAR
 }
 
+/// Byte that the clear message writes, and presumably the content of the
+/// cleared region afterwards.
+pub const CLEAR_FILL: u8 = 0x01;
+
+/// Start address of the bytes written by the clear message.
+pub const CLEAR_ADDRESS: u16 = 0x0000;
+
+/// Number of bytes written by the clear message.
+pub const CLEAR_LEN: u8 = 0x20;
+
+/// Requests `len` bytes of memory starting at the given address.
+///
+/// The device is expected to send back `4f`, then a record with the requested
+/// bytes.
+///
+/// This is a guess: `prepare_clear_1` looks like such a request for a single
+/// byte at `0x2000`, with `0x08` in the place where database chunks have `0x05`,
+/// but no other reads have been observed. Use for optional checks only.
+pub fn read(address: u16, len: u8) -> Result<Record> {
+    Builder::new().u8(0x08).u16(address).u8(len).build()
+}
+
 /// First record to be sent after sending the four clear messages.
 ///
 /// Device should send back `0x4f` (O) when sending this query.
@@ -145,4 +167,20 @@ mod test {
         check_integrity("finish_flash_0", finish_flash_0());
         check_integrity("finish_flash_1", finish_flash_1());
     }
+
+    #[test]
+    fn read_matches_prepare_clear_1() {
+        assert_eq!(
+            read(0x2000, 1).unwrap().as_bytes(),
+            prepare_clear_1().as_bytes()
+        );
+    }
+
+    #[test]
+    fn clear_writes_fill() {
+        let payload = clear().payload();
+        assert_eq!(payload[0], 0x03);
+        assert_eq!(&payload[1..3], &CLEAR_ADDRESS.to_le_bytes());
+        assert_eq!(&payload[3..], &[CLEAR_FILL; CLEAR_LEN as usize][..]);
+    }
 }
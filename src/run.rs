@@ -1,19 +1,44 @@
+use crate::argfile::ArgFileError;
 use crate::args::{Invocation, Run};
+use crate::error_kind::ErrorKind;
+use crate::presence::PresenceError;
+use crate::serial::OpenRetry;
 use serde_yaml::from_reader;
-use std::fs::File;
+use std::fs::{read_to_string, File};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use tracing::{event, Level};
 
-pub fn run(invocation: Invocation) -> Result<(), String> {
+pub fn run(invocation: Invocation, retry: &OpenRetry) -> Result<(), CmdError> {
     let result = match invocation {
-        Invocation::Run(run) => run_yaml(run).map_err(|e| format!("{}", e)),
-        Invocation::List(list) => crate::list::list(list).map_err(|e| format!("{}", e)),
-        Invocation::Scan(scan) => crate::devices::scan(scan).map_err(|e| format!("{}", e)),
+        Invocation::Run(run) => run_yaml(run, retry).map_err(CmdError::from),
+        Invocation::List(list) => crate::list::list(list).map_err(CmdError::from),
+        Invocation::Scan(scan) => crate::devices::scan(scan, retry).map_err(CmdError::from),
         Invocation::Destination(destination) => {
-            crate::destination::destination(&destination).map_err(|e| format!("{}", e))
+            crate::destination::destination(&destination, retry).map_err(CmdError::from)
+        }
+        Invocation::Line(line) => crate::line::line(&line, retry).map_err(CmdError::from),
+        Invocation::Blank(blank) => crate::blank::blank(&blank, retry).map_err(CmdError::from),
+        Invocation::Monitor(monitor) => {
+            crate::monitor::monitor(&monitor, retry).map_err(CmdError::from)
+        }
+        Invocation::Cycle(cycle) => crate::cycle::cycle(&cycle, retry).map_err(CmdError::from),
+        Invocation::Flash(flash) => crate::flash::flash(flash, retry).map_err(CmdError::from),
+        Invocation::Checksum(checksum) => {
+            crate::checksum::checksum(checksum).map_err(CmdError::from)
+        }
+        Invocation::Active(active) => crate::active::active(active).map_err(CmdError::from),
+        Invocation::Version(version) => {
+            crate::firmware::firmware(version, retry).map_err(CmdError::from)
+        }
+        Invocation::SendScript(script) => {
+            crate::script::send_script(script, retry).map_err(CmdError::from)
+        }
+        Invocation::Send(send) => crate::send::send(send, retry).map_err(CmdError::from),
+        Invocation::Dump(dump) => crate::dump::dump(dump, retry).map_err(CmdError::from),
+        Invocation::Status(status) => {
+            crate::presence::presence(status, retry).map_err(CmdError::from)
         }
-        Invocation::Cycle(cycle) => crate::cycle::cycle(&cycle).map_err(|e| format!("{}", e)),
-        Invocation::Flash(flash) => crate::flash::flash(flash).map_err(|e| format!("{}", e)),
     };
     if let Err(ref error) = result {
         event!(Level::DEBUG, ?error, "Failure")
@@ -21,10 +46,28 @@ pub fn run(invocation: Invocation) -> Result<(), String> {
     result
 }
 
-fn run_yaml(opts: Run) -> Result<(), RunError> {
-    let file = File::open(opts.config)?;
-    let invocation = from_reader(file)?;
-    run(invocation).map_err(RunError::Cmd)
+fn run_yaml(opts: Run, retry: &OpenRetry) -> Result<(), RunError> {
+    let invocation: Invocation = if is_toml(&opts.config) {
+        toml::from_str(&read_to_string(&opts.config)?)?
+    } else {
+        from_reader(File::open(&opts.config)?)?
+    };
+    if opts.check {
+        crate::check::check(&invocation)?;
+    }
+    if opts.summary || opts.check {
+        println!("{}", invocation);
+        return Ok(());
+    }
+    run(invocation, retry).map_err(|e| RunError::Cmd(Box::new(e)))
+}
+
+/// Checks whether the configuration file is in TOML rather than YAML format,
+/// going by its extension.
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .iter()
+        .any(|extension| extension.eq_ignore_ascii_case("toml"))
 }
 
 #[derive(Error, Debug)]
@@ -33,6 +76,263 @@ pub enum RunError {
     IO(#[from] std::io::Error),
     #[error("Could not parse specified YAML configuration file: {0}")]
     Deserialize(#[from] serde_yaml::Error),
+    #[error("Could not parse specified TOML configuration file: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid configuration: {0}")]
+    Check(#[from] crate::check::CheckError),
+    #[error("{0}")]
+    Cmd(Box<CmdError>),
+}
+
+impl RunError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            RunError::IO(_) | RunError::Deserialize(_) | RunError::Toml(_) | RunError::Check(_) => {
+                ErrorKind::Config
+            }
+            RunError::Cmd(cmd) => cmd.kind(),
+        }
+    }
+}
+
+/// Failure of any command, keeping the error of the command that failed so
+/// that it can be told apart with `--json-errors`, or of reading the command
+/// line before any command ran.
+#[derive(Error, Debug)]
+pub enum CmdError {
+    /// Output of the argument parser for invalid command lines.
+    #[error("{0}")]
+    Args(String),
+    #[error("{0}")]
+    ArgFile(#[from] ArgFileError),
+    #[error("Could not open trace file {}: {source}", path.display())]
+    Trace {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{0}")]
+    Run(#[from] RunError),
+    #[error("{0}")]
+    List(#[from] crate::list::ListError),
+    #[error("{0}")]
+    Scan(#[from] crate::devices::ScanError),
+    #[error("{0}")]
+    Destination(#[from] crate::destination::DestinationError),
+    #[error("{0}")]
+    Line(#[from] crate::line::LineError),
+    #[error("{0}")]
+    Blank(#[from] crate::blank::BlankError),
+    #[error("{0}")]
+    Monitor(#[from] crate::monitor::MonitorError),
+    #[error("{0}")]
+    Cycle(#[from] crate::cycle::CycleError),
+    #[error("{0}")]
+    Flash(#[from] crate::flash::FlashError),
+    #[error("{0}")]
+    Checksum(#[from] crate::checksum::ChecksumError),
+    #[error("{0}")]
+    Active(#[from] crate::active::ActiveError),
+    #[error("{0}")]
+    Version(#[from] crate::firmware::FirmwareError),
+    #[error("{0}")]
+    SendScript(#[from] crate::script::ScriptError),
     #[error("{0}")]
-    Cmd(String),
+    Send(#[from] crate::send::SendError),
+    #[error("{0}")]
+    Dump(#[from] crate::dump::DumpError),
+    #[error("{0}")]
+    Status(#[from] PresenceError),
+}
+
+impl CmdError {
+    /// Category of the error for `--json-errors`, e.g. `no_response` if no
+    /// display responded, whichever command failed.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CmdError::Args(_) => ErrorKind::Usage,
+            CmdError::ArgFile(_) => ErrorKind::Config,
+            CmdError::Trace { .. } => ErrorKind::Io,
+            CmdError::Run(error) => error.kind(),
+            CmdError::List(error) => error.kind(),
+            CmdError::Scan(error) => error.kind(),
+            CmdError::Destination(error) => error.kind(),
+            CmdError::Line(error) => error.kind(),
+            CmdError::Blank(error) => error.kind(),
+            CmdError::Monitor(error) => error.kind(),
+            CmdError::Cycle(error) => error.kind(),
+            CmdError::Flash(error) => error.kind(),
+            CmdError::Checksum(error) => error.kind(),
+            CmdError::Active(error) => error.kind(),
+            CmdError::Version(error) => error.kind(),
+            CmdError::SendScript(error) => error.kind(),
+            CmdError::Send(error) => error.kind(),
+            CmdError::Dump(error) => error.kind(),
+            CmdError::Status(error) => error.kind(),
+        }
+    }
+
+    /// Names the subcommand that failed, e.g. `destination`, which is the one
+    /// run from the configuration rather than `run` itself, unless reading
+    /// the configuration failed. `None` if the command line was invalid.
+    pub fn command(&self) -> Option<&'static str> {
+        match self {
+            CmdError::Args(_) | CmdError::ArgFile(_) | CmdError::Trace { .. } => None,
+            CmdError::Run(RunError::Cmd(cmd)) => cmd.command(),
+            CmdError::Run(_) => Some("run"),
+            CmdError::List(_) => Some("list"),
+            CmdError::Scan(_) => Some("scan"),
+            CmdError::Destination(_) => Some("destination"),
+            CmdError::Line(_) => Some("line"),
+            CmdError::Blank(_) => Some("blank"),
+            CmdError::Monitor(_) => Some("monitor"),
+            CmdError::Cycle(_) => Some("cycle"),
+            CmdError::Flash(_) => Some("flash"),
+            CmdError::Checksum(_) => Some("checksum"),
+            CmdError::Active(_) => Some("active"),
+            CmdError::Version(_) => Some("version"),
+            CmdError::SendScript(_) => Some("send-script"),
+            CmdError::Send(_) => Some("send"),
+            CmdError::Dump(_) => Some("dump"),
+            CmdError::Status(_) => Some("status"),
+        }
+    }
+
+    /// Exit code of the process, which is 1 for failed commands, except that
+    /// `status` exits with 1 only if no display responded and with 2 if the
+    /// query itself failed, so that scripts can tell absence from breakage.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CmdError::Run(RunError::Cmd(cmd)) => cmd.exit_code(),
+            CmdError::Status(PresenceError::NoResponse) => 1,
+            CmdError::Status(_) => 2,
+            _ => 1,
+        }
+    }
+
+    /// Formats the error as a JSON object with its kind, the command that
+    /// failed and the message, e.g.
+    /// `{"kind":"usage","command":"line","message":"Line 1000 is out of range 1-999"}`.
+    pub fn json(&self) -> String {
+        let command = match self.command() {
+            Some(command) => crate::json::string(command),
+            None => "null".to_string(),
+        };
+        format!(
+            r#"{{"kind":{},"command":{},"message":{}}}"#,
+            crate::json::string(self.kind().name()),
+            command,
+            crate::json::string(&self.to_string())
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::args::Cycle;
+    use crate::line::LineError;
+    use crate::plan::Plan;
+
+    #[test]
+    fn deserialize_toml_cycle() {
+        let invocation: Invocation =
+            toml::from_str(include_str!("../examples/cycle.toml")).unwrap();
+        match invocation {
+            Invocation::Cycle(Cycle {
+                plan,
+                interval_secs,
+                lookahead,
+                serial,
+                ..
+            }) => {
+                assert_eq!(
+                    plan,
+                    vec![
+                        "0".parse::<Plan>().unwrap(),
+                        "6@2021-09-09T18:00:00/2021-09-10T00:00:00".parse().unwrap()
+                    ]
+                );
+                assert_eq!(interval_secs, 9.0);
+                assert_eq!(lookahead, 12);
+                assert_eq!(serial.as_deref(), Some("/dev/ttyUSB0"));
+            }
+            _ => panic!("Unexpected invocation kind"),
+        }
+    }
+
+    #[test]
+    fn toml_example_same_as_yaml() {
+        let toml: Invocation = toml::from_str(include_str!("../examples/cycle.toml")).unwrap();
+        let yaml: Invocation =
+            serde_yaml::from_str(include_str!("../examples/cycle.yaml")).unwrap();
+        assert_eq!(toml.to_string(), yaml.to_string());
+    }
+
+    #[test]
+    fn json_names_failed_command_and_message() {
+        let error = CmdError::from(LineError::OutOfRange(1000));
+        assert_eq!(
+            error.json(),
+            r#"{"kind":"usage","command":"line","message":"Line 1000 is out of range 1-999"}"#
+        );
+    }
+
+    #[test]
+    fn json_names_command_that_failed_inside_run() {
+        let error = CmdError::from(RunError::Cmd(Box::new(CmdError::from(
+            LineError::OutOfRange(0),
+        ))));
+        assert_eq!(error.kind(), ErrorKind::Usage);
+        assert_eq!(error.command(), Some("line"));
+        assert_eq!(error.to_string(), "Line 0 is out of range 1-999");
+    }
+
+    #[test]
+    fn status_tells_absence_from_failure() {
+        assert_eq!(CmdError::from(PresenceError::NoResponse).exit_code(), 1);
+        assert_eq!(CmdError::from(PresenceError::Target).exit_code(), 2);
+        let in_run = RunError::Cmd(Box::new(CmdError::from(PresenceError::Target)));
+        assert_eq!(CmdError::from(in_run).exit_code(), 2);
+        assert_eq!(CmdError::from(LineError::OutOfRange(0)).exit_code(), 1);
+    }
+
+    #[test]
+    fn json_names_unreadable_configuration() {
+        let error = CmdError::from(RunError::from(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "missing \"file\"",
+        )));
+        assert_eq!(
+            error.json(),
+            r#"{"kind":"config","command":"run","message":"Could not open specified YAML configuration file: missing \"file\""}"#
+        );
+    }
+
+    #[test]
+    fn json_tells_absent_display_from_broken_port() {
+        assert_eq!(
+            CmdError::from(PresenceError::NoResponse).kind(),
+            ErrorKind::NoResponse
+        );
+        let timeout = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        assert_eq!(
+            CmdError::from(crate::send::SendError::IO(timeout)).kind(),
+            ErrorKind::NoResponse
+        );
+        let missing = crate::port::PortError::NoPorts;
+        assert_eq!(
+            CmdError::from(crate::line::LineError::Port(missing)).kind(),
+            ErrorKind::Port
+        );
+    }
+
+    #[test]
+    fn json_for_invalid_command_line() {
+        let error = CmdError::Args("Unrecognized argument: --nope".into());
+        assert_eq!(
+            error.json(),
+            r#"{"kind":"usage","command":null,"message":"Unrecognized argument: --nope"}"#
+        );
+    }
 }
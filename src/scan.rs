@@ -1,29 +1,131 @@
-use crate::serial::Serial;
-use crate::status::{status, Status};
+use crate::range::Range;
+use crate::serial::{drain, Serial};
+use crate::status::{status, status_with_parity_retries, Status};
+use crate::version::{version, Version};
+use std::io;
+use std::time::Duration;
+use tracing::{event, Level};
 
 pub type Result<T> = std::result::Result<T, crate::status::Error>;
 
 pub struct Scan<'a> {
     serial: &'a mut Serial,
-    next_address: u8,
+    addresses: std::vec::IntoIter<u8>,
+    parity_retries: u32,
+    quick_pass: Option<QuickPass>,
 }
 
+/// Timeouts for a first pass that only checks which addresses respond at all.
+#[derive(Debug, Clone, Copy)]
+struct QuickPass {
+    timeout: Duration,
+    /// Regular timeout of the port to set again for the confirming pass.
+    restore: Duration,
+}
+
+/// Most bytes to discard after the quick pass, more than any device sends
+/// in response to a status query.
+const QUICK_PASS_DRAIN_MAX: usize = 64;
+
 const ADDRESS_MIN: u8 = 0;
-const ADDRESS_MAX: u8 = 15;
+pub const ADDRESS_MAX: u8 = 15;
+
+/// Address that the standard does not seem to allow, but that we have seen
+/// in use by software in the wild, see [`crate::telegram::Telegram::display_status`].
+///
+/// It is probed in every scan, since telegrams cannot address anything outside
+/// of `ADDRESS_MIN..=ADDRESS_MAX` anyway.
+const ADDRESS_RESERVED: u8 = 0;
 
 impl<'a> Scan<'a> {
     pub fn new(serial: &'a mut Serial) -> Self {
         Self {
             serial,
-            next_address: ADDRESS_MIN,
+            addresses: (ADDRESS_MIN..=ADDRESS_MAX).collect::<Vec<_>>().into_iter(),
+            parity_retries: 0,
+            quick_pass: None,
+        }
+    }
+
+    /// Sends a status query to every address first, waiting only up to
+    /// `quick_timeout` for each response, and then scans just the addresses
+    /// that responded with the regular `timeout` of the port.
+    ///
+    /// This makes sweeping a mostly empty bus quick, while slow devices still
+    /// get the full timeout to confirm their status and version.
+    pub fn quick_pass(mut self, quick_timeout: Duration, timeout: Duration) -> Self {
+        self.quick_pass = Some(QuickPass {
+            timeout: quick_timeout,
+            restore: timeout,
+        });
+        self
+    }
+
+    /// Narrows the addresses down to the ones where anything responded within
+    /// the quick timeout, even if corrupt.
+    fn run_quick_pass(&mut self, pass: QuickPass) -> Result<()> {
+        self.serial
+            .set_timeout(pass.timeout)
+            .map_err(io::Error::from)?;
+        let mut responding = Vec::new();
+        for address in self.addresses.by_ref() {
+            match status(self.serial, address) {
+                Err(error) if error.is_timed_out() => {}
+                _ => responding.push(address),
+            }
         }
+        // a device slower than the quick timeout may still be responding
+        drain(self.serial, QUICK_PASS_DRAIN_MAX)?;
+        event!(Level::DEBUG, ?responding, "Quick pass done, confirming");
+        self.serial
+            .set_timeout(pass.restore)
+            .map_err(io::Error::from)?;
+        self.addresses = responding.into_iter();
+        Ok(())
+    }
+
+    /// Only probes the addresses in the range instead of all of them, in the
+    /// order of the range, skipping the ones above `ADDRESS_MAX`.
+    pub fn addresses(mut self, range: Range) -> Self {
+        self.addresses = range
+            .clamp_max(ADDRESS_MAX.into())
+            .map(|range| {
+                range
+                    .iter()
+                    .map(|address| address as u8)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+            .into_iter();
+        self
     }
+
+    /// Repeats status queries with corrupt responses up to the given number
+    /// of times before reporting a parity error for the address.
+    pub fn parity_retries(mut self, parity_retries: u32) -> Self {
+        self.parity_retries = parity_retries;
+        self
+    }
+}
+
+/// Scans for the first address where a device responds to the status query,
+/// e.g. to talk to the only device attached without knowing its address.
+pub fn first_responding(serial: &mut Serial) -> Option<Find> {
+    Scan::new(serial).find_map(Result::ok)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Scans all addresses and returns the devices that responded to the status
+/// query.
+pub fn all_responding(serial: &mut Serial) -> Vec<Find> {
+    Scan::new(serial).filter_map(Result::ok).collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Find {
     address: u8,
     status: Status,
+    /// Software version, if the device responded to the version query.
+    version: Option<Version>,
 }
 
 impl Find {
@@ -34,19 +136,43 @@ impl Find {
     pub fn status(&self) -> Status {
         self.status
     }
+
+    pub fn version(&self) -> Option<&Version> {
+        self.version.as_ref()
+    }
+
+    /// Checks whether the device responded at an address that the standard
+    /// does not allow.
+    pub fn is_reserved(&self) -> bool {
+        self.address == ADDRESS_RESERVED
+    }
 }
 
 impl<'a> Iterator for Scan<'a> {
     type Item = Result<Find>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.next_address > ADDRESS_MAX {
-            return None;
+        if let Some(pass) = self.quick_pass.take() {
+            if let Err(error) = self.run_quick_pass(pass) {
+                return Some(Err(error));
+            }
         }
-
-        let address = self.next_address;
-        let item = status(self.serial, address).map(|s| Find { address, status: s });
-        self.next_address += 1;
+        let address = self.addresses.next()?;
+        let item =
+            status_with_parity_retries(self.serial, address, self.parity_retries).map(|status| {
+                // not all devices that answer the status query support the
+                // version query, which does not make them any less found
+                let version = version(self.serial, address)
+                    .map_err(|error| {
+                        event!(Level::DEBUG, address, %error, "Could not query software version")
+                    })
+                    .ok();
+                Find {
+                    address,
+                    status,
+                    version,
+                }
+            });
         Some(item)
     }
 }
@@ -65,7 +191,11 @@ mod test {
             if address != available_address {
                 serial.time_out();
             } else {
-                serial.respond(b"a0\r#");
+                serial
+                    .respond(b"a0\r#")
+                    .expect_write(Telegram::display_version(address).as_bytes())
+                    .respond(b"aVX\r")
+                    .respond(&[0x7F ^ b'a' ^ b'V' ^ b'X' ^ b'\r']);
             }
         }
         let mut serial = serial.build();
@@ -74,10 +204,129 @@ mod test {
                 let find = result.unwrap();
                 assert_eq!(find.address(), available_address);
                 assert_eq!(find.status(), Status::ReadyForData);
+                assert_eq!(find.version().unwrap().as_str(), "X");
             } else {
                 let err = result.unwrap_err();
                 assert!(err.is_timed_out());
             }
         }
     }
+
+    #[test]
+    fn first_responding_stops_at_find() {
+        let mut serial = Serial::builder();
+        for address in 0..3 {
+            serial
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .time_out();
+        }
+        serial
+            .expect_write(Telegram::display_status(3).as_bytes())
+            .respond(b"a3\r ")
+            .expect_write(Telegram::display_version(3).as_bytes())
+            .time_out();
+        let mut serial = serial.build();
+        assert_eq!(first_responding(&mut serial).unwrap().address(), 3);
+    }
+
+    #[test]
+    fn scan_sub_range() {
+        let mut serial = Serial::builder();
+        // the mock panics on any write beyond these four queries
+        for address in 0..=3 {
+            serial
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .time_out();
+        }
+        let mut serial = serial.build();
+        let range = "0-3".parse().unwrap();
+        assert_eq!(Scan::new(&mut serial).addresses(range).count(), 4);
+    }
+
+    #[test]
+    fn scan_single_address() {
+        let mut serial = Serial::builder();
+        serial
+            .expect_write(Telegram::display_status(7).as_bytes())
+            .respond(b"a3\r ")
+            .expect_write(Telegram::display_version(7).as_bytes())
+            .time_out();
+        let mut serial = serial.build();
+        let finds: Vec<Find> = Scan::new(&mut serial)
+            .addresses(Range::single(7))
+            .filter_map(Result::ok)
+            .collect();
+        assert_eq!(finds.len(), 1);
+        assert_eq!(finds[0].address(), 7);
+    }
+
+    #[test]
+    fn scan_range_clamped_to_highest_address() {
+        let mut serial = Serial::builder();
+        for address in 14..=15 {
+            serial
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .time_out();
+        }
+        let mut serial = serial.build();
+        let range = "14-99".parse().unwrap();
+        assert_eq!(Scan::new(&mut serial).addresses(range).count(), 2);
+    }
+
+    #[test]
+    fn quick_pass_then_confirm_responding() {
+        let quick = Duration::from_millis(100);
+        let regular = Duration::from_secs(3);
+        let mut serial = Serial::builder();
+        for address in ADDRESS_MIN..=ADDRESS_MAX {
+            serial.expect_write(Telegram::display_status(address).as_bytes());
+            match address {
+                4 => serial.respond(b"a3\r "),
+                // corrupt, but something is there
+                9 => serial.respond(b"a3\r0"),
+                _ => serial.time_out(),
+            };
+        }
+        // nothing late to drain
+        serial.time_out();
+        serial
+            .expect_write(Telegram::display_status(4).as_bytes())
+            .respond(b"a3\r ")
+            .expect_write(Telegram::display_version(4).as_bytes())
+            .time_out()
+            .expect_write(Telegram::display_status(9).as_bytes())
+            .time_out();
+        let mut serial = serial.build();
+
+        let results: Vec<Result<Find>> =
+            Scan::new(&mut serial).quick_pass(quick, regular).collect();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().address(), 4);
+        assert!(results[1].as_ref().unwrap_err().is_timed_out());
+        assert_eq!(serial.timeouts(), &[quick, regular]);
+    }
+
+    #[test]
+    fn discover_reserved_address_0() {
+        let mut serial = Serial::builder();
+        serial
+            .expect_write(Telegram::display_status(0).as_bytes())
+            .respond(b"a3\r ")
+            // device does not support the version query
+            .expect_write(Telegram::display_version(0).as_bytes())
+            .time_out();
+        for address in 1..=ADDRESS_MAX {
+            serial
+                .expect_write(Telegram::display_status(address).as_bytes())
+                .time_out();
+        }
+        let mut serial = serial.build();
+        let finds: Vec<Find> = Scan::new(&mut serial).filter_map(Result::ok).collect();
+        assert_eq!(finds.len(), 1);
+        assert_eq!(finds[0].address(), 0);
+        assert_eq!(finds[0].status(), Status::Ok);
+        assert!(finds[0].is_reserved());
+        assert_eq!(finds[0].version(), None);
+    }
 }
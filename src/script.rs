@@ -0,0 +1,359 @@
+//! Sends a sequence of telegrams and records from a script file, e.g. to
+//! replay captured command sequences or to experiment with the protocol.
+//!
+//! Each line of a script holds one command:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! raw 0d 72           # bytes in hexadecimal notation, sent as they are
+//! 0x0d 0x72           # same as above, the 0x tells bytes from commands
+//! ibis 61 31          # IBIS telegram payload, CR and parity byte are appended
+//! record 08 00 20 01  # BS210 record payload, length and checksum are added
+//! status 1            # mnemonics for known telegrams: status, version,
+//! line 6              # line, destination, empty
+//! expect 61 33 0d 20  # read exactly these bytes or fail
+//! wait 500ms          # pause before the next line
+//! ```
+
+use crate::args::SendScript;
+use crate::echo;
+use crate::error_kind::ErrorKind;
+use crate::hex::{parse_hex, ParseHexError};
+use crate::interval::{Interval, ParseIntervalError};
+use crate::port::{self, PortError};
+use crate::record::{self, Record};
+use crate::serial::{open, OpenRetry, Serial};
+use crate::telegram::Telegram;
+use std::fs::read_to_string;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, ScriptError>;
+
+pub fn send_script(opts: SendScript, retry: &OpenRetry) -> Result<()> {
+    let source = read_to_string(&opts.script).map_err(|source| ScriptError::Read {
+        source,
+        path: opts.script.clone(),
+    })?;
+    let script = parse(&source)?;
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial =
+        open(&port, Duration::from_secs(opts.timeout_secs), retry).map_err(|source| {
+            ScriptError::Serial {
+                source,
+                port: port.clone(),
+            }
+        })?;
+    execute(&mut serial, &script)
+}
+
+/// A single command from a script, along with the line it was on.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Step {
+    /// Line number, starting at 1.
+    line: usize,
+    action: Action,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Send(Vec<u8>),
+    Expect(Vec<u8>),
+    Wait(Duration),
+}
+
+pub fn parse(source: &str) -> Result<Vec<Step>> {
+    let mut steps = vec![];
+    for (idx, line) in source.lines().enumerate() {
+        let line_nr = idx + 1;
+        let line = match line.find('#') {
+            Some(comment_start) => &line[..comment_start],
+            None => line,
+        }
+        .trim();
+        if line.is_empty() {
+            continue;
+        }
+        let action = parse_action(line).map_err(|source| ScriptError::Syntax {
+            line: line_nr,
+            source,
+        })?;
+        steps.push(Step {
+            line: line_nr,
+            action,
+        });
+    }
+    Ok(steps)
+}
+
+fn parse_action(line: &str) -> std::result::Result<Action, LineError> {
+    let (command, args) = match line.find(char::is_whitespace) {
+        Some(end) => (&line[..end], line[end..].trim()),
+        None => (line, ""),
+    };
+    let action = match command {
+        "raw" => Action::Send(parse_hex(args)?),
+        "ibis" => Action::Send(Telegram::custom(&parse_hex(args)?).as_bytes().to_vec()),
+        "record" => Action::Send(Record::custom(&parse_hex(args)?)?.as_bytes().to_vec()),
+        "expect" => Action::Expect(parse_hex(args)?),
+        "wait" => Action::Wait(args.parse::<Interval>()?.as_duration()),
+        "empty" => Action::Send(Telegram::empty().as_bytes().to_vec()),
+        "status" => Action::Send(
+            Telegram::display_status(parse_number(args, 0, 15)? as u8)
+                .as_bytes()
+                .to_vec(),
+        ),
+        "version" => Action::Send(
+            Telegram::display_version(parse_number(args, 0, 15)? as u8)
+                .as_bytes()
+                .to_vec(),
+        ),
+        "line" => Action::Send(
+            Telegram::line(parse_number(args, 1, 999)?)
+                .as_bytes()
+                .to_vec(),
+        ),
+        "destination" => Action::Send(
+            Telegram::destination(parse_number(args, 0, 999)?)
+                .as_bytes()
+                .to_vec(),
+        ),
+        _ if command.starts_with("0x") || command.starts_with("0X") => {
+            Action::Send(parse_hex(line)?)
+        }
+        // words like add or face could be misspelled commands as well
+        _ if command.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            return Err(LineError::BareHex(command.to_string()))
+        }
+        _ => return Err(LineError::UnknownCommand(command.to_string())),
+    };
+    Ok(action)
+}
+
+fn parse_number(source: &str, min: u16, max: u16) -> std::result::Result<u16, LineError> {
+    let number = source.parse::<u16>()?;
+    if number < min || number > max {
+        return Err(LineError::OutOfRange { number, min, max });
+    }
+    Ok(number)
+}
+
+/// Executes the steps in order, stopping at the first failure.
+pub fn execute(serial: &mut Serial, script: &[Step]) -> Result<()> {
+    for step in script {
+        let io_err = |source| ScriptError::IO {
+            line: step.line,
+            source,
+        };
+        match step.action {
+            Action::Send(ref bytes) => {
+                echo::bytes(bytes);
+                serial.write_all(bytes).map_err(io_err)?;
+                serial.flush().map_err(io_err)?;
+            }
+            Action::Expect(ref expected) => {
+                let mut received = vec![0_u8; expected.len()];
+                serial.read_exact(&mut received).map_err(io_err)?;
+                if &received != expected {
+                    return Err(ScriptError::Mismatch {
+                        line: step.line,
+                        expected: expected.clone(),
+                        received,
+                    });
+                }
+            }
+            Action::Wait(duration) => sleep(duration),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("Could not read script {path}: {source}")]
+    Read {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+    #[error("Syntax error in script line {line}: {source}")]
+    Syntax { line: usize, source: LineError },
+    #[error("Script line {line} failed due to I/O error: {source}")]
+    IO { line: usize, source: std::io::Error },
+    #[error("Script line {line} expected response {expected:02X?}, but received {received:02X?}")]
+    Mismatch {
+        line: usize,
+        expected: Vec<u8>,
+        received: Vec<u8>,
+    },
+}
+
+impl ScriptError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ScriptError::Read { .. } | ScriptError::Syntax { .. } => ErrorKind::Config,
+            ScriptError::Serial { .. } => ErrorKind::Port,
+            ScriptError::Port(error) => error.kind(),
+            ScriptError::IO { source, .. } => ErrorKind::of_io(source),
+            ScriptError::Mismatch { .. } => ErrorKind::Device,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LineError {
+    #[error("unknown command `{0}`, expected any of raw, ibis, record, expect, wait, empty, status, version, line, destination, or bytes in hexadecimal notation with a 0x prefix")]
+    UnknownCommand(String),
+    #[error("ambiguous command `{0}`, write bytes as `raw {0}` or `0x{0}`")]
+    BareHex(String),
+    #[error("{0}")]
+    Hex(#[from] ParseHexError),
+    #[error("{0}")]
+    Interval(#[from] ParseIntervalError),
+    #[error("could not parse number: {0}")]
+    Number(#[from] std::num::ParseIntError),
+    #[error("{number} is out of range {min}-{max}")]
+    OutOfRange { number: u16, min: u16, max: u16 },
+    #[error("could not build record: {0}")]
+    Record(#[from] record::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCRIPT: &str = "# query status of device 1
+status 1
+expect 61 33 0d 20
+
+ibis 7a 30 30 35 # destination 5
+wait 1ms
+raw 0d72
+";
+
+    #[test]
+    fn parse_script() {
+        let script = parse(SCRIPT).unwrap();
+        assert_eq!(
+            script,
+            vec![
+                Step {
+                    line: 2,
+                    action: Action::Send(Telegram::display_status(1).as_bytes().to_vec())
+                },
+                Step {
+                    line: 3,
+                    action: Action::Expect(b"a3\r ".to_vec())
+                },
+                Step {
+                    line: 5,
+                    action: Action::Send(Telegram::destination(5).as_bytes().to_vec())
+                },
+                Step {
+                    line: 6,
+                    action: Action::Wait(Duration::from_millis(1))
+                },
+                Step {
+                    line: 7,
+                    action: Action::Send(Telegram::empty().as_bytes().to_vec())
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_example() {
+        let script = parse(include_str!("../examples/status.script")).unwrap();
+        assert_eq!(script.len(), 5);
+    }
+
+    #[test]
+    fn parse_record() {
+        let script = parse("record 05 00 00 00 00").unwrap();
+        assert_eq!(
+            script[0].action,
+            Action::Send(vec![0x05, 0x05, 0x00, 0x00, 0x00, 0x00, 0xf6])
+        );
+    }
+
+    #[test]
+    fn parse_unknown_command() {
+        match parse("\nsned 1").unwrap_err() {
+            ScriptError::Syntax {
+                line: 2,
+                source: LineError::UnknownCommand(command),
+            } => assert_eq!(command, "sned"),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn parse_prefixed_hex() {
+        let script = parse("0x0d 0x72\n0X0D72").unwrap();
+        assert_eq!(script[0].action, Action::Send(vec![0x0d, 0x72]));
+        assert_eq!(script[1].action, Action::Send(vec![0x0d, 0x72]));
+    }
+
+    #[test]
+    fn parse_bare_hex_is_ambiguous() {
+        for line in &["dead", "add 1", "0d 72"] {
+            match parse(line).unwrap_err() {
+                ScriptError::Syntax {
+                    line: 1,
+                    source: LineError::BareHex(_),
+                } => {}
+                err => panic!("Unexpected error for {}: {:?}", line, err),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_address_out_of_range() {
+        match parse("status 16").unwrap_err() {
+            ScriptError::Syntax {
+                line: 1,
+                source: LineError::OutOfRange { number: 16, .. },
+            } => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn execute_script() {
+        let script = parse(SCRIPT).unwrap();
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .expect_write(Telegram::destination(5).as_bytes())
+            .expect_write(Telegram::empty().as_bytes())
+            .build();
+
+        execute(&mut serial, &script).unwrap();
+    }
+
+    #[test]
+    fn execute_reports_failed_line() {
+        let script = parse(SCRIPT).unwrap();
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a0\r#")
+            .build();
+
+        match execute(&mut serial, &script).unwrap_err() {
+            ScriptError::Mismatch {
+                line: 3, received, ..
+            } => assert_eq!(received, b"a0\r#"),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Sends a single telegram with an arbitrary payload and prints whatever the
+//! device sends back, e.g. to probe undocumented commands.
+
+use crate::args::SendTelegram;
+use crate::echo;
+use crate::error_kind::ErrorKind;
+use crate::hex::{format_hex, parse_hex, ParseHexError};
+use crate::output;
+use crate::port::{self, PortError};
+use crate::serial::{drain, open, OpenRetry, Serial};
+use crate::telegram::Telegram;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, SendError>;
+
+/// Upper bound for bytes to read back, in case the device keeps sending.
+const MAX_RESPONSE_LEN: usize = 256;
+
+pub fn send(opts: SendTelegram, retry: &OpenRetry) -> Result<()> {
+    let telegram = Telegram::custom(&payload(&opts)?);
+    if let Some(path) = &opts.output {
+        return write_output(path, &telegram).map_err(|source| SendError::Output {
+            source,
+            path: output::name(path),
+        });
+    }
+    let port = port::resolve(opts.serial.as_deref())?;
+    let mut serial =
+        open(&port, Duration::from_secs(opts.timeout_secs), retry).map_err(|source| {
+            SendError::Serial {
+                source,
+                port: port.clone(),
+            }
+        })?;
+
+    let response = exchange(&mut serial, &telegram)?;
+    if response.is_empty() {
+        println!("No response");
+        return Ok(());
+    }
+    match Telegram::try_from(&response[..]) {
+        Ok(telegram) => println!("{} {}", format_hex(&response), telegram),
+        Err(_) => println!("{}", format_hex(&response)),
+    }
+    Ok(())
+}
+
+/// Gets the payload from the arguments, either the words joined with spaces
+/// or the bytes in hexadecimal notation.
+fn payload(opts: &SendTelegram) -> Result<Vec<u8>> {
+    let payload = opts.payload.join(" ");
+    if opts.hex {
+        Ok(parse_hex(&payload)?)
+    } else {
+        Ok(payload.into_bytes())
+    }
+}
+
+/// Writes the telegram to the output instead of a serial port, without
+/// waiting for any response.
+fn write_output(path: &Path, telegram: &Telegram) -> std::io::Result<()> {
+    echo::telegram(telegram);
+    let mut out = output::create(path)?;
+    out.write_all(telegram.as_bytes())?;
+    out.flush()
+}
+
+/// Writes the telegram and reads until the device stops sending.
+fn exchange(serial: &mut Serial, telegram: &Telegram) -> Result<Vec<u8>> {
+    echo::telegram(telegram);
+    serial.write_all(telegram.as_bytes())?;
+    serial.flush()?;
+    Ok(drain(serial, MAX_RESPONSE_LEN)?)
+}
+
+#[derive(Error, Debug)]
+pub enum SendError {
+    #[error("{0}")]
+    Hex(#[from] ParseHexError),
+    #[error("Could not open serial port connection to: {port}, due to error: {source}")]
+    Serial {
+        source: serialport::Error,
+        port: String,
+    },
+    #[error("Could not write telegram to: {path}, due to I/O error: {source}")]
+    Output {
+        source: std::io::Error,
+        path: String,
+    },
+    #[error("{0}")]
+    Port(#[from] PortError),
+    #[error("Failed to communicate over serial port, error: {0}")]
+    IO(#[from] std::io::Error),
+}
+
+impl SendError {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            SendError::Hex(_) => ErrorKind::Usage,
+            SendError::Serial { .. } => ErrorKind::Port,
+            SendError::Output { .. } => ErrorKind::Io,
+            SendError::Port(error) => error.kind(),
+            SendError::IO(error) => ErrorKind::of_io(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn opts(payload: &[&str], hex: bool) -> SendTelegram {
+        SendTelegram {
+            payload: payload.iter().map(|p| p.to_string()).collect(),
+            hex,
+            timeout_secs: 3,
+            output: None,
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn ascii_payload() {
+        assert_eq!(payload(&opts(&["aV1"], false)).unwrap(), b"aV1");
+    }
+
+    #[test]
+    fn hex_payload() {
+        assert_eq!(payload(&opts(&["61", "56", "31"], true)).unwrap(), b"aV1");
+    }
+
+    #[test]
+    fn malformed_hex_payload() {
+        assert!(payload(&opts(&["aV1"], true)).is_err());
+    }
+
+    #[test]
+    fn exchange_status() {
+        let telegram = Telegram::custom(b"a1");
+        let mut serial = Serial::builder()
+            .expect_write(Telegram::display_status(1).as_bytes())
+            .respond(b"a3\r ")
+            .time_out()
+            .build();
+        assert_eq!(exchange(&mut serial, &telegram).unwrap(), b"a3\r ");
+    }
+
+    #[test]
+    fn writes_telegram_to_output() {
+        let path = std::env::temp_dir().join(format!("ibisibi-send-{}.bin", std::process::id()));
+        let mut opts = opts(&["61", "56", "31"], true);
+        opts.output = Some(path.clone());
+        let result = send(opts, &OpenRetry::default());
+        let written = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+        assert_eq!(written.unwrap(), Telegram::custom(b"aV1").as_bytes());
+    }
+}
@@ -1,12 +1,112 @@
+use crate::telegram::{Telegram, TelegramParseError};
 use serialport::Result;
 #[cfg(not(test))]
 use serialport::{new, DataBits, FlowControl, Parity, StopBits};
-#[cfg(not(test))]
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read};
+use std::thread::sleep;
 use std::time::Duration;
 use std::{borrow::Cow, convert::Into};
+use thiserror::Error;
+use tracing::{event, Level};
 
-#[cfg(not(test))]
-const TIMEOUT_SECS: u64 = 3;
+/// Time to wait after the first failed attempt to open a port, if no other
+/// delay was specified.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest time to wait between attempts, so that doubling the delay does not
+/// end up waiting for hours after many retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Upper bound for the length of a received telegram, including carriage
+/// return and parity byte, so that we do not read forever from a device that
+/// never sends a carriage return.
+pub const MAX_TELEGRAM_LEN: usize = 64;
+
+/// How to retry opening a serial port that is not available yet, e.g. because
+/// a USB adapter is still being enumerated after boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenRetry {
+    /// Additional attempts after the first one has failed, zero for a single
+    /// attempt.
+    pub retries: u32,
+    /// Time to wait after the first failed attempt, doubled after each
+    /// further failed attempt up to a minute.
+    pub delay: Duration,
+}
+
+impl Default for OpenRetry {
+    fn default() -> Self {
+        OpenRetry {
+            retries: 0,
+            delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+}
+
+impl From<&crate::args::TopLevel> for OpenRetry {
+    fn from(args: &crate::args::TopLevel) -> Self {
+        OpenRetry {
+            retries: args.open_retries,
+            delay: args
+                .open_retry_delay
+                .map(|d| d.as_duration())
+                .unwrap_or(DEFAULT_RETRY_DELAY),
+        }
+    }
+}
+
+/// Calls `open` until it succeeds or until the configured retries are used up,
+/// returning the last error in the latter case.
+fn with_retries<T, F>(retry: &OpenRetry, mut open: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut delay = retry.delay;
+    let mut retries_left = retry.retries;
+    loop {
+        match open() {
+            Ok(serial) => return Ok(serial),
+            Err(error) if retries_left > 0 => {
+                event!(
+                    Level::WARN,
+                    %error,
+                    ?delay,
+                    retries_left,
+                    "Could not open serial port, retrying"
+                );
+                sleep(delay);
+                delay = backoff(delay);
+                retries_left -= 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Doubles the delay before the next attempt up to [`MAX_RETRY_DELAY`],
+/// keeping delays that were configured to be even longer.
+pub fn backoff(delay: Duration) -> Duration {
+    delay
+        .checked_mul(2)
+        .map_or(delay, |doubled| doubled.min(MAX_RETRY_DELAY))
+        .max(delay)
+}
+
+/// Checks whether opening the port might succeed later, e.g. once another
+/// program releases it, rather than failing because the port does not exist
+/// or the path is not a port at all.
+///
+/// A port that is missing may still show up later, e.g. when a USB adapter
+/// is plugged back in, so callers decide whether that counts as recoverable.
+pub fn is_recoverable(error: &serialport::Error) -> bool {
+    !matches!(
+        error.kind(),
+        serialport::ErrorKind::NoDevice
+            | serialport::ErrorKind::InvalidInput
+            | serialport::ErrorKind::Io(ErrorKind::NotFound)
+    )
+}
 
 /// Shorter type alias for handles to serial ports.
 ///
@@ -19,30 +119,108 @@ pub type Serial = Box<dyn serialport::SerialPort>;
 #[cfg(test)]
 pub type Serial = mock::MockSerial;
 
+/// Reads and discards whatever the device sends until a read times out or
+/// `max_len` bytes have been received, so that stray responses do not end up
+/// in the response to the next command.
+///
+/// Returns the discarded bytes. Note that this always waits for the configured
+/// read timeout unless `max_len` bytes are received earlier.
+pub fn drain<R: Read + ?Sized>(serial: &mut R, max_len: usize) -> std::io::Result<Vec<u8>> {
+    let mut drained = Vec::new();
+    let mut buf = [0_u8; 64];
+    while drained.len() < max_len {
+        let len = buf.len().min(max_len - drained.len());
+        match serial.read(&mut buf[..len]) {
+            Ok(0) => break,
+            Ok(read) => drained.extend_from_slice(&buf[..read]),
+            Err(err) if err.kind() == ErrorKind::TimedOut => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(drained)
+}
+
+/// Reads byte by byte until a carriage return and the parity byte after it
+/// have been received, so that responses of any length are framed the same,
+/// even if a slow device sends them a byte at a time, and then checks the
+/// parity of the frame.
+///
+/// Bytes the device sends after the parity byte are left for the next read.
+pub fn read_telegram<R: Read + ?Sized>(
+    serial: &mut R,
+) -> std::result::Result<Telegram, FrameError> {
+    let mut frame = Vec::with_capacity(MAX_TELEGRAM_LEN);
+    let mut byte = [0_u8; 1];
+    loop {
+        serial.read_exact(&mut byte)?;
+        let cr_received = frame.last() == Some(&b'\r');
+        frame.push(byte[0]);
+        if cr_received {
+            return Ok(Telegram::try_from(&frame[..])?);
+        }
+        if frame.len() >= MAX_TELEGRAM_LEN {
+            return Err(FrameError::TooLong);
+        }
+    }
+}
+
+/// Reasons why [`read_telegram`] could not read a telegram.
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+    #[error("received {MAX_TELEGRAM_LEN} bytes without carriage return")]
+    TooLong,
+    #[error("{0}")]
+    Corrupt(#[from] TelegramParseError),
+}
+
+/// Opens the port with the settings used by IBIS, waiting up to `timeout` for
+/// each read before failing with a timeout.
 #[cfg(not(test))]
-pub fn open<'a, D>(device: D) -> Result<Serial>
+pub fn open<'a, D>(device: D, timeout: Duration, retry: &OpenRetry) -> Result<Serial>
 where
     D: Into<Cow<'a, str>>,
 {
-    new(device, 1200)
-        .data_bits(DataBits::Seven)
-        .stop_bits(StopBits::Two)
-        .parity(Parity::Even)
-        .timeout(Duration::new(TIMEOUT_SECS, 0))
-        .open()
+    let device = device.into();
+    with_retries(retry, || {
+        new(device.clone(), 1200)
+            .data_bits(DataBits::Seven)
+            .stop_bits(StopBits::Two)
+            .parity(Parity::Even)
+            .timeout(timeout)
+            .open()
+            .map(|serial| crate::trace::wrap(serial, &device))
+    })
 }
 
+/// Opens the next mock registered for the device with [`mock::register`],
+/// failing like a missing device if there is none left.
 #[cfg(test)]
-pub fn open<'a, D>(_device: D) -> Result<Serial>
+pub fn open<'a, D>(device: D, timeout: Duration, retry: &OpenRetry) -> Result<Serial>
 where
     D: Into<Cow<'a, str>>,
 {
-    todo!("mocking of open function for test currently not needed")
+    let device = device.into();
+    let mut serial = with_retries(retry, || mock::take(&device))?;
+    serial.set_timeout(timeout)?;
+    mock::record_open(&device, timeout);
+    Ok(serial)
+}
+
+#[cfg(not(test))]
+pub fn open_for_flashing(
+    flash: &crate::args::Flash,
+    port: &str,
+    retry: &OpenRetry,
+) -> Result<Serial> {
+    with_retries(retry, || open_for_flashing_once(flash, port))
+        .map(|serial| crate::trace::wrap(serial, port))
 }
 
 #[cfg(not(test))]
-pub fn open_for_flashing(flash: &crate::args::Flash) -> Result<Serial> {
-    new(&flash.serial, flash.baudrate)
+fn open_for_flashing_once(flash: &crate::args::Flash, port: &str) -> Result<Serial> {
+    new(port, flash.baudrate)
         .data_bits(match flash.data_bits {
             5 => DataBits::Five,
             6 => DataBits::Six,
@@ -71,23 +249,274 @@ pub fn open_for_flashing(flash: &crate::args::Flash) -> Result<Serial> {
         .open()
 }
 
+/// Opens the next mock registered for the port like [`open`], ignoring the
+/// serial settings of the flash options.
 #[cfg(test)]
-pub fn open_for_flashing(_flash: &crate::args::Flash) -> Result<Serial> {
-    todo!("mocking of open_for_flashing function for test currently not needed")
+pub fn open_for_flashing(
+    flash: &crate::args::Flash,
+    port: &str,
+    retry: &OpenRetry,
+) -> Result<Serial> {
+    open(port, Duration::new(flash.timeout, 0), retry)
 }
 
 #[cfg(test)]
-mod mock {
+mod test {
+    use super::*;
+    use serialport::{Error, ErrorKind};
+
+    fn no_device() -> Error {
+        Error::new(ErrorKind::NoDevice, "not enumerated yet")
+    }
+
+    #[test]
+    fn open_fails_once_then_succeeds() {
+        let retry = OpenRetry {
+            retries: 2,
+            delay: Duration::from_millis(1),
+        };
+        let mut attempts = 0;
+        let opened = with_retries(&retry, || {
+            attempts += 1;
+            if attempts == 1 {
+                Err(no_device())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(opened.unwrap(), 2);
+    }
+
+    #[test]
+    fn single_attempt_by_default() {
+        let mut attempts = 0;
+        let opened: Result<()> = with_retries(&OpenRetry::default(), || {
+            attempts += 1;
+            Err(no_device())
+        });
+        assert!(opened.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn drain_until_timeout() {
+        let mut serial = Serial::builder()
+            .respond(b"O")
+            .respond(b"OO")
+            .time_out()
+            .build();
+        assert_eq!(drain(&mut serial, 16).unwrap(), b"OOO");
+    }
+
+    #[test]
+    fn drain_at_most_max_len() {
+        let mut serial = Serial::builder().respond(b"OOOO").build();
+        assert_eq!(drain(&mut serial, 2).unwrap(), b"OO");
+    }
+
+    #[test]
+    fn read_telegram_byte_at_a_time() {
+        let mut serial = Serial::builder()
+            .respond(b"a")
+            .respond(b"3")
+            .respond(b"\r")
+            .respond(b" ")
+            .build();
+        let telegram = read_telegram(&mut serial).unwrap();
+        assert_eq!(telegram.as_bytes(), b"a3\r ");
+    }
+
+    #[test]
+    fn read_telegram_leaves_trailing_bytes() {
+        let mut serial = Serial::builder().respond(b"a3\r a0\r#").build();
+        assert_eq!(read_telegram(&mut serial).unwrap().as_bytes(), b"a3\r ");
+        assert_eq!(read_telegram(&mut serial).unwrap().as_bytes(), b"a0\r#");
+    }
+
+    #[test]
+    fn read_telegram_parity_err() {
+        let mut serial = Serial::builder().respond(b"a3\r0").build();
+        match read_telegram(&mut serial).unwrap_err() {
+            FrameError::Corrupt(TelegramParseError::Parity { .. }) => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn read_telegram_without_carriage_return() {
+        let mut serial = Serial::builder().respond(&[b'a'; MAX_TELEGRAM_LEN]).build();
+        match read_telegram(&mut serial).unwrap_err() {
+            FrameError::TooLong => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn read_telegram_timeout() {
+        let mut serial = Serial::builder().respond(b"a3").time_out().build();
+        match read_telegram(&mut serial).unwrap_err() {
+            FrameError::IO(err) if err.kind() == std::io::ErrorKind::TimedOut => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn open_registered_mocks_in_order() {
+        mock::register("/dev/ttyMock", Serial::builder().respond(b"1").build());
+        mock::register("/dev/ttyMock", Serial::builder().respond(b"2").build());
+        assert_eq!(mock::registered("/dev/ttyMock"), 2);
+        let timeout = Duration::from_secs(3);
+        for expected in &[b"1", b"2"] {
+            let mut serial = open("/dev/ttyMock", timeout, &OpenRetry::default()).unwrap();
+            assert_eq!(drain(&mut serial, 1).unwrap(), *expected);
+            assert_eq!(serial.timeouts(), &[timeout]);
+        }
+        let error = open("/dev/ttyMock", timeout, &OpenRetry::default())
+            .err()
+            .expect("no more mocks registered");
+        assert_eq!(error.kind(), ErrorKind::NoDevice);
+    }
+
+    #[test]
+    fn missing_port_not_recoverable() {
+        assert!(!is_recoverable(&no_device()));
+        assert!(!is_recoverable(&Error::new(
+            ErrorKind::Io(std::io::ErrorKind::NotFound),
+            "No such file or directory"
+        )));
+        assert!(!is_recoverable(&Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid input"
+        )));
+        assert!(is_recoverable(&Error::new(
+            ErrorKind::Unknown,
+            "Device or resource busy"
+        )));
+        assert!(is_recoverable(&Error::new(
+            ErrorKind::Io(std::io::ErrorKind::TimedOut),
+            "Timed out"
+        )));
+    }
+
+    #[test]
+    fn give_up_after_retries() {
+        let retry = OpenRetry {
+            retries: 2,
+            delay: Duration::from_millis(1),
+        };
+        let mut attempts = 0;
+        let opened: Result<()> = with_retries(&retry, || {
+            attempts += 1;
+            Err(no_device())
+        });
+        assert_eq!(opened.unwrap_err().kind(), ErrorKind::NoDevice);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_limit() {
+        assert_eq!(backoff(Duration::from_secs(1)), Duration::from_secs(2));
+        assert_eq!(backoff(Duration::from_secs(40)), MAX_RETRY_DELAY);
+        assert_eq!(backoff(MAX_RETRY_DELAY), MAX_RETRY_DELAY);
+        // longer delays are left as configured
+        assert_eq!(backoff(Duration::from_secs(90)), Duration::from_secs(90));
+    }
+}
+
+/// Serial ports that respond as planned by tests.
+///
+/// Functions that take a serial port can be tested with a mock from
+/// [`MockSerial::builder`]. To test whole commands that open a port by name,
+/// register the mock for the name first, e.g. for `destination`:
+///
+/// ```ignore
+/// mock::register(
+///     "/dev/ttyMock",
+///     Serial::builder().expect_write(Telegram::destination(1).as_bytes()).build(),
+/// );
+/// destination(&args_with_serial("/dev/ttyMock"), &OpenRetry::default())?;
+/// ```
+///
+/// Each call to [`open`] takes the next mock registered for the name, so
+/// commands that open the port repeatedly need one mock per opening. Mocks
+/// are registered per thread and thus per test.
+#[cfg(test)]
+pub mod mock {
     use std::{
+        cell::RefCell,
+        collections::{HashMap, VecDeque},
         io::{Error, ErrorKind, Read, Result, Write},
         mem::replace,
+        time::Duration,
     };
 
+    thread_local! {
+        static REGISTERED: RefCell<HashMap<String, VecDeque<MockSerial>>> =
+            RefCell::new(HashMap::new());
+        static OPENED: RefCell<HashMap<String, Vec<Duration>>> = RefCell::new(HashMap::new());
+    }
+
+    /// Makes the next [`super::open`] of the device on this thread return
+    /// the given mock, after any mocks registered for it before.
+    pub fn register(device: &str, serial: MockSerial) {
+        REGISTERED.with(|registered| {
+            registered
+                .borrow_mut()
+                .entry(device.to_string())
+                .or_default()
+                .push_back(serial)
+        });
+    }
+
+    /// Number of mocks registered for the device that have not been opened
+    /// yet, e.g. to check that a command opened the port as often as
+    /// expected.
+    pub fn registered(device: &str) -> usize {
+        REGISTERED.with(|registered| registered.borrow().get(device).map_or(0, VecDeque::len))
+    }
+
+    /// Read timeouts that mocks of the device were opened with on this
+    /// thread so far, in order, e.g. to check the settings of a command
+    /// that keeps the mock to itself.
+    pub fn opened_timeouts(device: &str) -> Vec<Duration> {
+        OPENED.with(|opened| opened.borrow().get(device).cloned().unwrap_or_default())
+    }
+
+    pub(super) fn record_open(device: &str, timeout: Duration) {
+        OPENED.with(|opened| {
+            opened
+                .borrow_mut()
+                .entry(device.to_string())
+                .or_default()
+                .push(timeout)
+        });
+    }
+
+    /// Removes the next mock registered for the device, or fails like a
+    /// device that does not exist.
+    pub(super) fn take(device: &str) -> serialport::Result<MockSerial> {
+        REGISTERED
+            .with(|registered| {
+                registered
+                    .borrow_mut()
+                    .get_mut(device)
+                    .and_then(VecDeque::pop_front)
+            })
+            .ok_or_else(|| {
+                serialport::Error::new(
+                    serialport::ErrorKind::NoDevice,
+                    format!("no mock registered for {}", device),
+                )
+            })
+    }
+
     pub struct MockSerial {
         /// We expect these buffers to be written in sequence.
         expected_writes: Vec<Vec<u8>>,
         /// Scheduled responses for the next reads.
         read_results: Vec<ReadResult>,
+        /// Read timeouts set on the mock so far, in order.
+        timeouts: Vec<Duration>,
     }
 
     impl MockSerial {
@@ -97,6 +526,18 @@ mod mock {
                 read_results: vec![],
             }
         }
+
+        /// Records the timeout so tests can check it, but otherwise has no
+        /// effect since timeouts are planned with [`Builder::time_out`].
+        pub fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+            self.timeouts.push(timeout);
+            Ok(())
+        }
+
+        /// Timeouts set with [`MockSerial::set_timeout`] so far, in order.
+        pub fn timeouts(&self) -> &[Duration] {
+            &self.timeouts[..]
+        }
     }
 
     impl Read for MockSerial {
@@ -113,6 +554,11 @@ mod mock {
                     self.read_results.remove(0);
                     Err(Error::from(ErrorKind::TimedOut))
                 }
+                ReadResult::Stall(duration) => {
+                    self.read_results.remove(0);
+                    std::thread::sleep(duration);
+                    self.read(buf)
+                }
                 ReadResult::Data(ref mut first) => {
                     let first_len = first.len();
                     let first = if first_len <= buf.len() {
@@ -185,6 +631,7 @@ mod mock {
     enum ReadResult {
         Data(Vec<u8>),
         Timeout,
+        Stall(Duration),
     }
 
     pub struct Builder {
@@ -207,12 +654,25 @@ mod mock {
             self
         }
 
+        /// Same as [`Builder::respond`], named from the point of view of
+        /// the code under test.
+        pub fn receive(&mut self, response: &[u8]) -> &mut Self {
+            self.respond(response)
+        }
+
         /// Plans the next read attempt to time out.
         pub fn time_out(&mut self) -> &mut Self {
             self.read_results.push(ReadResult::Timeout);
             self
         }
 
+        /// Plans the next read attempt to block for the given time before
+        /// continuing with the next planned read result.
+        pub fn stall(&mut self, duration: Duration) -> &mut Self {
+            self.read_results.push(ReadResult::Stall(duration));
+            self
+        }
+
         /// Finish the build.
         ///
         /// Can safely be called multiple times.
@@ -220,7 +680,31 @@ mod mock {
             MockSerial {
                 expected_writes: self.expected_writes.clone(),
                 read_results: self.read_results.clone(),
+                timeouts: vec![],
             }
         }
     }
+
+    #[test]
+    fn receive_same_as_respond() {
+        let read_all = |serial: &mut MockSerial| {
+            let mut buf = [0; 2];
+            let mut read = vec![];
+            while let Ok(len) = serial.read(&mut buf) {
+                read.push(buf[..len].to_vec());
+            }
+            read
+        };
+        let mut responding = MockSerial::builder()
+            .respond(b"a3\r")
+            .respond(b" ")
+            .time_out()
+            .build();
+        let mut receiving = MockSerial::builder()
+            .receive(b"a3\r")
+            .receive(b" ")
+            .time_out()
+            .build();
+        assert_eq!(read_all(&mut responding), read_all(&mut receiving));
+    }
 }
@@ -0,0 +1,98 @@
+//! Random order of destinations for `cycle --shuffle`.
+//!
+//! Uses a small xorshift generator rather than a cryptographic one, which is
+//! plenty for signage and can be seeded to make runs reproducible.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct Shuffler {
+    state: u64,
+}
+
+impl Shuffler {
+    /// Creates a shuffler that always produces the same orders for the same
+    /// seed.
+    pub fn new(seed: u64) -> Self {
+        // scramble the seed with a splitmix64 step so that similar seeds do
+        // not start out with similar orders, and since xorshift never leaves
+        // a zero state
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+        Shuffler {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    /// Creates a shuffler seeded with the current time.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_nanos() as u64)
+            .unwrap_or(0);
+        Shuffler::new(nanos)
+    }
+
+    /// Puts the items into a random order.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        // Fisher-Yates
+        for idx in (1..items.len()).rev() {
+            let other = self.below(idx + 1);
+            items.swap(idx, other);
+        }
+    }
+
+    /// Picks a number from zero up to, but excluding, `bound`.
+    fn below(&mut self, bound: usize) -> usize {
+        // the slight bias of the modulo does not matter for bounds this small
+        (self.next() % bound as u64) as usize
+    }
+
+    /// Advances the xorshift64* generator.
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shuffled(seed: u64) -> Vec<usize> {
+        let mut items: Vec<usize> = (0..20).collect();
+        Shuffler::new(seed).shuffle(&mut items);
+        items
+    }
+
+    #[test]
+    fn same_seed_same_order() {
+        assert_eq!(shuffled(42), shuffled(42));
+    }
+
+    #[test]
+    fn different_seeds_different_orders() {
+        assert_ne!(shuffled(0), shuffled(1));
+        assert_ne!(shuffled(0), (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn keeps_all_items() {
+        let mut items = shuffled(7);
+        items.sort_unstable();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_and_single() {
+        let mut shuffler = Shuffler::new(0);
+        let mut empty: [u8; 0] = [];
+        shuffler.shuffle(&mut empty);
+        let mut single = [1];
+        shuffler.shuffle(&mut single);
+        assert_eq!(single, [1]);
+    }
+}
@@ -0,0 +1,69 @@
+//! Stopping long-running commands like `cycle` cleanly on Ctrl-C or SIGTERM,
+//! rather than in the middle of sending a telegram.
+//!
+//! Only supported on Unix. Elsewhere, the process is terminated right away
+//! as before.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Longest time between checks for shutdown while sleeping.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Handles SIGINT and SIGTERM for the rest of the process by requesting
+/// shutdown. A second signal terminates the process immediately.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn handle(signal: libc::c_int) {
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        // only async-signal-safe calls are allowed here
+        unsafe { libc::_exit(128 + signal) };
+    }
+}
+
+/// Checks whether shutdown has been requested.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Sleeps for the given duration or until shutdown is requested, and returns
+/// whether it was requested.
+pub fn sleep(duration: Duration) -> bool {
+    // durations too long to represent as an instant are slept until shutdown
+    let deadline = Instant::now().checked_add(duration);
+    loop {
+        if requested() {
+            return true;
+        }
+        let now = Instant::now();
+        let remaining = match deadline {
+            Some(deadline) if now >= deadline => return false,
+            Some(deadline) => deadline - now,
+            None => POLL_INTERVAL,
+        };
+        thread::sleep(POLL_INTERVAL.min(remaining));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sleep_without_shutdown() {
+        let start = Instant::now();
+        assert!(!sleep(Duration::from_millis(10)));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+}
@@ -1,22 +1,327 @@
 //! A time slot.
-use chrono::NaiveDateTime;
+//!
+//! Start and end can be given with an offset from UTC, e.g.
+//! `2021-09-09T20:00:00+02:00`, to refer to the same instant regardless of
+//! where and when the cycle runs. Without an offset, they are in the local
+//! time of the machine running the cycle. The end can be left out to show the
+//! slot until further notice, e.g. `2021-09-09T08:00:00/`.
+//!
+//! Slots that recur every day are written as times of day, e.g.
+//! `08:00:00-17:00:00*daily`. If the end is before the start, the slot lasts
+//! past midnight.
+//!
+//! Slots can be limited to some days of the week by appending them after an
+//! `@`, e.g. `08:00-17:00*daily@mon-fri` or `2021-09-09T08:00/2021-09-19T17:00@sat,sun`.
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDateTime, NaiveTime, Offset, TimeZone, Weekday,
+};
 use serde::{de, Deserialize, Deserializer};
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::iter::{once, FromIterator};
 use std::str::FromStr;
 use thiserror::Error;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Slot {
-    start: NaiveDateTime,
-    end: NaiveDateTime,
+    window: Window,
+    /// Days of the week the slot is limited to, or `None` for every day.
+    weekdays: Option<Weekdays>,
+}
+
+/// When a slot happens, regardless of weekdays.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Window {
+    /// From one point in time to another, or without an end if `end` is
+    /// `None`.
+    Once {
+        start: SlotTime,
+        end: Option<SlotTime>,
+    },
+    /// The same hours on every day, in the time zone the slot is checked in.
+    Daily { start: NaiveTime, end: NaiveTime },
+}
+
+impl From<Window> for Slot {
+    fn from(window: Window) -> Self {
+        Slot {
+            window,
+            weekdays: None,
+        }
+    }
 }
 
 impl Slot {
-    pub fn start(&self) -> NaiveDateTime {
-        self.start
+    /// Checks whether the slot is happening at `now` or starts within the
+    /// lookahead.
+    ///
+    /// Slots that happen once are only active on their weekdays, while each
+    /// day of a daily slot counts as the weekday it starts on, even if it
+    /// lasts past midnight.
+    pub fn is_active<Tz: TimeZone>(&self, lookahead: Duration, now: &DateTime<Tz>) -> bool {
+        let zone = now.timezone();
+        let soonest_to_show = now.clone() + lookahead;
+        let on_weekday = |day: Weekday| self.weekdays.iter().all(|days| days.contains(day));
+        match self.window {
+            Window::Once { start, end } => {
+                // cease to show events when already over
+                end.iter().all(|end| *now < end.in_zone(&zone))
+                    // show when currently happening or within lookahead
+                    && soonest_to_show > start.in_zone(&zone)
+                    && on_weekday(now.weekday())
+            }
+            Window::Daily { start, end } => {
+                // start with yesterday, which may last past midnight, and
+                // check every day that could start within the lookahead
+                let today = now.naive_local().date();
+                (-1..=lookahead.num_days() + 1).any(|day| {
+                    let date = today + Duration::days(day);
+                    if !on_weekday(date.weekday()) {
+                        return false;
+                    }
+                    let end_date = if end > start { date } else { date.succ() };
+                    let start = SlotTime::Local(date.and_time(start)).in_zone(&zone);
+                    let end = SlotTime::Local(end_date.and_time(end)).in_zone(&zone);
+                    *now < end && soonest_to_show > start
+                })
+            }
+        }
+    }
+
+    /// Finds when a slot that is not active at `now` will become active,
+    /// counting the lookahead, or `None` if it never will.
+    ///
+    /// Like in [`Slot::is_active`], the slot is active only after the
+    /// returned instant, not at the instant itself.
+    pub fn next_activation<Tz: TimeZone>(
+        &self,
+        lookahead: Duration,
+        now: &DateTime<Tz>,
+    ) -> Option<DateTime<Tz>> {
+        let zone = now.timezone();
+        let on_weekday = |day: Weekday| self.weekdays.iter().all(|days| days.contains(day));
+        match self.window {
+            Window::Once { start, end } => {
+                let earliest = (start.in_zone(&zone) - lookahead).max(now.clone());
+                // if not on one of the weekdays, wait for the next one
+                let midnight = earliest.naive_local().date().and_hms(0, 0, 0);
+                let activation =
+                    once(earliest)
+                        .chain((1..7).map(|day| {
+                            SlotTime::Local(midnight + Duration::days(day)).in_zone(&zone)
+                        }))
+                        .find(|time| on_weekday(time.weekday()))?;
+                match end {
+                    Some(end) if activation >= end.in_zone(&zone) => None,
+                    _ => Some(activation),
+                }
+            }
+            Window::Daily { start, .. } => {
+                // a week after the lookahead is enough to find the next one
+                // of the weekdays
+                let today = now.naive_local().date();
+                (-1..=lookahead.num_days() + 8)
+                    .map(|day| today + Duration::days(day))
+                    .filter(|date| on_weekday(date.weekday()))
+                    .map(|date| SlotTime::Local(date.and_time(start)).in_zone(&zone) - lookahead)
+                    .find(|activation| activation > now)
+            }
+        }
+    }
+}
+
+/// Start or end of a slot.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SlotTime {
+    /// Wall clock time in whatever time zone the slot is checked in.
+    Local(NaiveDateTime),
+    /// Instant given with an offset from UTC.
+    Fixed(DateTime<FixedOffset>),
+}
+
+impl SlotTime {
+    /// Gets the instant in the given time zone, interpreting local times as
+    /// wall clock times in that zone.
+    ///
+    /// Local times that occur twice when clocks go back are taken to be the
+    /// earlier one. Local times that are skipped when clocks go forward are
+    /// taken as if the clocks had not changed yet, i.e. shifted forward by
+    /// the length of the gap.
+    pub fn in_zone<Tz: TimeZone>(&self, tz: &Tz) -> DateTime<Tz> {
+        match *self {
+            SlotTime::Fixed(fixed) => fixed.with_timezone(tz),
+            SlotTime::Local(local) => {
+                tz.from_local_datetime(&local)
+                    .earliest()
+                    .unwrap_or_else(|| {
+                        let before_gap = tz.offset_from_utc_datetime(&(local - Duration::days(1)));
+                        tz.from_utc_datetime(&(local - before_gap.fix()))
+                    })
+            }
+        }
+    }
+
+    /// Wall clock time, ignoring the offset if there is one.
+    fn naive(&self) -> NaiveDateTime {
+        match *self {
+            SlotTime::Local(local) => local,
+            SlotTime::Fixed(fixed) => fixed.naive_local(),
+        }
+    }
+}
+
+impl Display for SlotTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotTime::Local(local) => write!(f, "{}", local.format(DATE_FORMAT)),
+            SlotTime::Fixed(fixed) => write!(f, "{}", fixed.format(DATE_FORMAT_WITH_OFFSET)),
+        }
+    }
+}
+
+/// Format of the start and end dates, as accepted by the parser.
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Format of the start and end dates that were given with an offset.
+const DATE_FORMAT_WITH_OFFSET: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// Format of the start and end of daily slots.
+const TIME_FORMAT: &str = "%H:%M:%S";
+
+/// Format accepted for the start and end of daily slots in addition to
+/// [`TIME_FORMAT`], which leaves out the seconds.
+const TIME_FORMAT_WITHOUT_SECS: &str = "%H:%M";
+
+/// Only recurrence that is supported, after the `*` of a recurring slot.
+const DAILY: &str = "daily";
+
+/// Abbreviated weekday names, starting with Monday, as used when formatting
+/// weekdays.
+const WEEKDAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Set of days of the week, e.g. `mon-fri` or `sat,sun`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Weekdays(u8);
+
+impl Weekdays {
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.0 & (1 << day.num_days_from_monday()) != 0
+    }
+
+    pub fn insert(&mut self, day: Weekday) {
+        self.0 |= 1 << day.num_days_from_monday();
     }
+}
 
-    pub fn end(&self) -> NaiveDateTime {
-        self.end
+impl FromIterator<Weekday> for Weekdays {
+    fn from_iter<I: IntoIterator<Item = Weekday>>(days: I) -> Self {
+        let mut weekdays = Weekdays::default();
+        for day in days {
+            weekdays.insert(day);
+        }
+        weekdays
+    }
+}
+
+impl Display for Weekdays {
+    /// Lists the days starting with Monday, joining three or more
+    /// consecutive days into a range, e.g. `mon-wed,fri`. Ranges wrap
+    /// around the end of the week, e.g. `fri-mon`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = |day: Weekday| WEEKDAY_NAMES[day.num_days_from_monday() as usize];
+        if self.0 == 0b111_1111 {
+            return f.write_str("mon-sun");
+        }
+        // start with a day that does not continue a range from the day before
+        let mut day = match std::iter::successors(Some(Weekday::Mon), |day| Some(day.succ()))
+            .take(7)
+            .find(|&day| self.contains(day) && !self.contains(day.pred()))
+        {
+            Some(day) => day,
+            None => return Ok(()),
+        };
+        let mut remaining = 7;
+        let mut first = true;
+        while remaining > 0 {
+            if !self.contains(day) {
+                day = day.succ();
+                remaining -= 1;
+                continue;
+            }
+            let start = day;
+            let mut len = 0;
+            while len < remaining && self.contains(day) {
+                day = day.succ();
+                len += 1;
+            }
+            remaining -= len;
+            if !first {
+                f.write_str(",")?;
+            }
+            first = false;
+            match len {
+                1 => f.write_str(name(start))?,
+                2 => write!(f, "{},{}", name(start), name(start.succ()))?,
+                _ => write!(f, "{}-{}", name(start), name(day.pred()))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Weekdays {
+    type Err = ParseSlotError;
+
+    /// Parses a comma-separated list of weekdays or ranges of them, e.g.
+    /// `mon-wed,fri`.
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut weekdays = Weekdays::default();
+        for part in source.split(',') {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (parse_weekday(first)?, parse_weekday(last)?),
+                None => (parse_weekday(part)?, parse_weekday(part)?),
+            };
+            let mut day = first;
+            weekdays.insert(day);
+            while day != last {
+                day = day.succ();
+                weekdays.insert(day);
+            }
+        }
+        Ok(weekdays)
+    }
+}
+
+/// Parses a weekday name like `mon` or `monday`, ignoring case.
+fn parse_weekday(source: &str) -> Result<Weekday, ParseSlotError> {
+    source.parse().map_err(|_| ParseSlotError::Weekday {
+        not_parsed: source.to_string(),
+    })
+}
+
+impl Display for Slot {
+    /// Formats the slot in the same notation it is parsed from, e.g.
+    /// `2021-06-03T00:00:00/2021-06-04T00:00:00`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.window {
+            Window::Once { start, end } => {
+                write!(f, "{}/", start)?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+            }
+            Window::Daily { start, end } => write!(
+                f,
+                "{}-{}*{}",
+                start.format(TIME_FORMAT),
+                end.format(TIME_FORMAT),
+                DAILY
+            )?,
+        }
+        match self.weekdays {
+            Some(weekdays) => write!(f, "@{}", weekdays),
+            None => Ok(()),
+        }
     }
 }
 
@@ -38,30 +343,115 @@ impl FromStr for Slot {
             return Err(ParseSlotError::Blank);
         }
 
-        let mut dates = source.split('/');
-        let start = dates
-            .next()
-            .ok_or_else(|| ParseSlotError::incomplete(source))?;
-        let end = dates
-            .next()
-            .ok_or_else(|| ParseSlotError::incomplete(source))?;
-        if dates.next().is_some() {
-            return Err(ParseSlotError::too_much(source));
-        }
+        let (window, weekdays) = match source.split_once('@') {
+            Some((window, weekdays)) => (window, Some(weekdays.parse()?)),
+            None => (source, None),
+        };
+        let window = parse_window(window)?;
+        Ok(Slot { window, weekdays })
+    }
+}
+
+/// Parses a slot without weekdays.
+fn parse_window(source: &str) -> Result<Window, ParseSlotError> {
+    if let Some((times, recurrence)) = source.split_once('*') {
+        return parse_daily(source, times, recurrence);
+    }
+
+    let mut dates = source.split('/');
+    let start = dates
+        .next()
+        .ok_or_else(|| ParseSlotError::incomplete(source))?;
+    let end = dates
+        .next()
+        .ok_or_else(|| ParseSlotError::incomplete(source))?;
+    if dates.next().is_some() {
+        return Err(ParseSlotError::too_much(source));
+    }
 
-        let start = start
-            .parse::<NaiveDateTime>()
-            .map_err(|err| ParseSlotError::date_format(start, err))?;
-        let end = end
-            .parse::<NaiveDateTime>()
-            .map_err(|err| ParseSlotError::date_format(end, err))?;
+    let start = parse_slot_time(start)?;
+    // an empty end means the slot lasts until further notice
+    let end = match end {
+        "" => None,
+        end => Some(parse_slot_time(end)?),
+    };
 
-        if start > end {
+    if let Some(end) = end {
+        let backwards = match (start, end) {
+            (SlotTime::Fixed(start), SlotTime::Fixed(end)) => start > end,
+            // without a time zone, wall clock times are the best guess
+            _ => start.naive() > end.naive(),
+        };
+        if backwards {
             return Err(ParseSlotError::from_after_to(start, end));
         }
+    }
+
+    Ok(Window::Once { start, end })
+}
 
-        let slot = Slot { start, end };
-        Ok(slot)
+fn parse_daily(source: &str, times: &str, recurrence: &str) -> Result<Window, ParseSlotError> {
+    if recurrence != DAILY {
+        return Err(ParseSlotError::UnknownRecurrence {
+            recurrence: recurrence.to_string(),
+        });
+    }
+    let (start, end) = times
+        .split_once('-')
+        .ok_or_else(|| ParseSlotError::incomplete(source))?;
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    if start == end {
+        return Err(ParseSlotError::EmptyDaily {
+            input: source.to_string(),
+        });
+    }
+    Ok(Window::Daily { start, end })
+}
+
+/// Parses a time of day with or without seconds, e.g. `08:00:00` or `08:00`.
+fn parse_time(source: &str) -> Result<NaiveTime, ParseSlotError> {
+    NaiveTime::parse_from_str(source, TIME_FORMAT).or_else(|err| {
+        NaiveTime::parse_from_str(source, TIME_FORMAT_WITHOUT_SECS).map_err(|_| {
+            ParseSlotError::TimeFormat {
+                not_parsed: source.to_string(),
+                cause: err,
+            }
+        })
+    })
+}
+
+/// Format accepted for the start and end of a slot in addition to the
+/// canonical [`DATE_FORMAT`], which leaves out the seconds.
+const DATE_FORMAT_WITHOUT_SECS: &str = "%Y-%m-%dT%H:%M";
+
+/// Parses a date and time in the canonical format, with optional fractional
+/// seconds, or without seconds. Date and time may also be separated with a
+/// space instead of a `T`, so that operators can write e.g. `2021-09-09 20:00`.
+pub fn parse_date_time(source: &str) -> Result<NaiveDateTime, ParseSlotError> {
+    let normalized = normalize(source);
+    normalized.parse::<NaiveDateTime>().or_else(|err| {
+        NaiveDateTime::parse_from_str(&normalized, DATE_FORMAT_WITHOUT_SECS)
+            .map_err(|_| ParseSlotError::date_format(source, err))
+    })
+}
+
+/// Parses a date and time with an offset in RFC 3339 format, e.g.
+/// `2021-09-09T20:00:00+02:00` or `2021-09-09T18:00:00Z`, or without an
+/// offset in any of the formats accepted by [`parse_date_time`].
+pub fn parse_slot_time(source: &str) -> Result<SlotTime, ParseSlotError> {
+    match DateTime::parse_from_rfc3339(&normalize(source)) {
+        Ok(fixed) => Ok(SlotTime::Fixed(fixed)),
+        Err(_) => parse_date_time(source).map(SlotTime::Local),
+    }
+}
+
+/// Replaces a single space between date and time with a `T`.
+fn normalize(source: &str) -> Cow<'_, str> {
+    // only accept a single space exactly between date and time, since spaces
+    // in chrono formats also match no space at all
+    match source.split_once(' ') {
+        Some((date, time)) if date.len() == 10 => Cow::Owned(format!("{}T{}", date, time)),
+        _ => Cow::Borrowed(source),
     }
 }
 
@@ -74,15 +464,25 @@ pub enum ParseSlotError {
     #[error("Time slot contains more than two dates: `{input}`")]
     TooMuch { input: String },
     #[error("Time slot end {end} was before time slot start {start}")]
-    FromAfterTo {
-        start: NaiveDateTime,
-        end: NaiveDateTime,
-    },
-    #[error("Could not parse time part in timeslot `{not_parsed}`: {cause}")]
+    FromAfterTo { start: SlotTime, end: SlotTime },
+    #[error("Could not parse time part in timeslot `{not_parsed}`: {cause}, expected any of the formats 2021-09-09T20:00:00, 2021-09-09 20:00:00, 2021-09-09T20:00, 2021-09-09 20:00, or with an offset like 2021-09-09T20:00:00+02:00")]
     DateFormat {
         not_parsed: String,
         cause: chrono::ParseError,
     },
+    #[error("Could not parse time of day in daily slot `{not_parsed}`: {cause}, expected e.g. 08:00:00 or 08:00")]
+    TimeFormat {
+        not_parsed: String,
+        cause: chrono::ParseError,
+    },
+    #[error("Unknown recurrence `{recurrence}`, only daily is supported")]
+    UnknownRecurrence { recurrence: String },
+    #[error("Daily slot `{input}` starts and ends at the same time")]
+    EmptyDaily { input: String },
+    #[error(
+        "Could not parse weekday `{not_parsed}`, expected e.g. mon, tue or a range like mon-fri"
+    )]
+    Weekday { not_parsed: String },
 }
 
 impl ParseSlotError {
@@ -98,7 +498,7 @@ impl ParseSlotError {
         }
     }
 
-    fn from_after_to(start: NaiveDateTime, end: NaiveDateTime) -> Self {
+    fn from_after_to(start: SlotTime, end: SlotTime) -> Self {
         Self::FromAfterTo { start, end }
     }
 
@@ -123,10 +523,10 @@ mod test {
         let expected_end = "2021-09-10T00:00:00".parse::<NaiveDateTime>().unwrap();
         assert_eq!(
             slot,
-            Slot {
-                start: expected_start,
-                end: expected_end
-            }
+            Slot::from(Window::Once {
+                start: SlotTime::Local(expected_start),
+                end: Some(SlotTime::Local(expected_end))
+            })
         )
     }
 
@@ -139,10 +539,10 @@ mod test {
         let expected_end = "2021-09-10T21:00:00".parse::<NaiveDateTime>().unwrap();
         assert_eq!(
             slot,
-            Slot {
-                start: expected_start,
-                end: expected_end
-            }
+            Slot::from(Window::Once {
+                start: SlotTime::Local(expected_start),
+                end: Some(SlotTime::Local(expected_end))
+            })
         )
     }
 
@@ -154,8 +554,8 @@ mod test {
         assert_eq!(
             slot,
             ParseSlotError::from_after_to(
-                "2021-09-19T20:00:00".parse().unwrap(),
-                "2021-09-09T21:00:00".parse().unwrap()
+                SlotTime::Local("2021-09-19T20:00:00".parse().unwrap()),
+                SlotTime::Local("2021-09-09T21:00:00".parse().unwrap())
             )
         )
     }
@@ -209,4 +609,317 @@ mod test {
         let slot = "".parse::<Slot>().unwrap_err();
         assert_eq!(slot, ParseSlotError::Blank)
     }
+
+    #[test]
+    fn display_roundtrip() {
+        let source = "2021-06-03T00:00:00/2021-06-04T12:30:00";
+        let slot: Slot = source.parse().unwrap();
+        assert_eq!(slot.to_string(), source)
+    }
+
+    #[test]
+    fn alternative_date_formats() {
+        let expected_start = "2021-09-09T20:00:00".parse::<NaiveDateTime>().unwrap();
+        let expected_end = "2021-09-09T21:30:00".parse::<NaiveDateTime>().unwrap();
+        for source in &[
+            "2021-09-09T20:00:00/2021-09-09T21:30:00",
+            "2021-09-09 20:00:00/2021-09-09 21:30:00",
+            "2021-09-09T20:00/2021-09-09T21:30",
+            "2021-09-09 20:00/2021-09-09 21:30",
+        ] {
+            let slot = source.parse::<Slot>().unwrap();
+            assert_eq!(
+                slot,
+                Slot::from(Window::Once {
+                    start: SlotTime::Local(expected_start),
+                    end: Some(SlotTime::Local(expected_end))
+                }),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn alternative_format_displayed_canonically() {
+        let slot = "2021-09-09 20:00/2021-09-09 21:30".parse::<Slot>().unwrap();
+        assert_eq!(slot.to_string(), "2021-09-09T20:00:00/2021-09-09T21:30:00");
+    }
+
+    #[test]
+    fn unsupported_date_format() {
+        match "09.09.2021 20:00/2021-09-09 21:30"
+            .parse::<Slot>()
+            .unwrap_err()
+        {
+            ParseSlotError::DateFormat { not_parsed, .. } => {
+                assert_eq!(not_parsed, "09.09.2021 20:00")
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn with_offsets() {
+        let slot = "2021-09-09T20:00:00+02:00/2021-09-09 19:30:00Z"
+            .parse::<Slot>()
+            .unwrap();
+        match slot.window {
+            Window::Once { start, .. } => assert_eq!(
+                start,
+                SlotTime::Fixed(DateTime::parse_from_rfc3339("2021-09-09T18:00:00Z").unwrap())
+            ),
+            _ => panic!("Expected slot that happens once"),
+        }
+        assert_eq!(
+            slot.to_string(),
+            "2021-09-09T20:00:00+02:00/2021-09-09T19:30:00+00:00"
+        );
+    }
+
+    #[test]
+    fn from_after_to_with_offsets() {
+        // 21:00 in UTC-02:00 is after 22:00 in UTC
+        match "2021-09-09T21:00:00-02:00/2021-09-09T22:00:00Z"
+            .parse::<Slot>()
+            .unwrap_err()
+        {
+            ParseSlotError::FromAfterTo { .. } => (),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn local_time_in_zone() {
+        let local = SlotTime::Local("2021-09-09T20:00:00".parse().unwrap());
+        let cest = FixedOffset::east(2 * 3600);
+        assert_eq!(
+            local.in_zone(&cest),
+            DateTime::parse_from_rfc3339("2021-09-09T20:00:00+02:00").unwrap()
+        );
+    }
+
+    fn at(source: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(source).unwrap()
+    }
+
+    #[test]
+    fn parse_daily() {
+        let slot = "08:00-17:00:00*daily".parse::<Slot>().unwrap();
+        assert_eq!(
+            slot,
+            Slot::from(Window::Daily {
+                start: NaiveTime::from_hms(8, 0, 0),
+                end: NaiveTime::from_hms(17, 0, 0)
+            })
+        );
+        assert_eq!(slot.to_string(), "08:00:00-17:00:00*daily");
+    }
+
+    #[test]
+    fn parse_daily_errors() {
+        assert_eq!(
+            "08:00-17:00*weekly".parse::<Slot>().unwrap_err(),
+            ParseSlotError::UnknownRecurrence {
+                recurrence: "weekly".to_string()
+            }
+        );
+        assert_eq!(
+            "08:00*daily".parse::<Slot>().unwrap_err(),
+            ParseSlotError::incomplete("08:00*daily")
+        );
+        assert_eq!(
+            "08:00-08:00*daily".parse::<Slot>().unwrap_err(),
+            ParseSlotError::EmptyDaily {
+                input: "08:00-08:00*daily".to_string()
+            }
+        );
+        match "8h-17h*daily".parse::<Slot>().unwrap_err() {
+            ParseSlotError::TimeFormat { not_parsed, .. } => assert_eq!(not_parsed, "8h"),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn daily_active_regardless_of_date() {
+        let slot: Slot = "08:00-17:00*daily".parse().unwrap();
+        let none = Duration::hours(0);
+        assert!(slot.is_active(none, &at("2021-09-09T08:00:01+02:00")));
+        assert!(slot.is_active(none, &at("2030-01-01T16:59:59+01:00")));
+        assert!(!slot.is_active(none, &at("2021-09-09T17:00:00+02:00")));
+        assert!(!slot.is_active(none, &at("2021-09-09T07:00:00+02:00")));
+        assert!(slot.is_active(Duration::hours(2), &at("2021-09-09T07:00:00+02:00")));
+        assert!(slot.is_active(Duration::hours(16), &at("2021-09-09T17:00:00+02:00")));
+    }
+
+    #[test]
+    fn next_activation_once() {
+        let slot: Slot = "2021-09-09T18:00:00Z/2021-09-09T20:00:00Z".parse().unwrap();
+        let none = Duration::hours(0);
+        assert_eq!(
+            slot.next_activation(none, &at("2021-09-09T12:00:00Z")),
+            Some(at("2021-09-09T18:00:00Z"))
+        );
+        assert_eq!(
+            slot.next_activation(Duration::hours(2), &at("2021-09-09T12:00:00Z")),
+            Some(at("2021-09-09T16:00:00Z"))
+        );
+        assert_eq!(
+            slot.next_activation(none, &at("2021-09-09T20:00:00Z")),
+            None
+        );
+    }
+
+    #[test]
+    fn next_activation_once_on_weekdays() {
+        // 2021-09-09 is a thursday
+        let slot: Slot = "2021-09-09T18:00:00Z/2021-09-20T00:00:00Z@sat,sun"
+            .parse()
+            .unwrap();
+        let none = Duration::hours(0);
+        assert_eq!(
+            slot.next_activation(none, &at("2021-09-09T12:00:00Z")),
+            Some(at("2021-09-11T00:00:00Z"))
+        );
+        // the last weekend day is after the end
+        let slot: Slot = "2021-09-13T00:00:00Z/2021-09-17T00:00:00Z@sat,sun"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            slot.next_activation(none, &at("2021-09-09T12:00:00Z")),
+            None
+        );
+    }
+
+    #[test]
+    fn next_activation_daily() {
+        let slot: Slot = "08:00-17:00*daily".parse().unwrap();
+        let none = Duration::hours(0);
+        assert_eq!(
+            slot.next_activation(none, &at("2021-09-09T18:00:00Z")),
+            Some(at("2021-09-10T08:00:00Z"))
+        );
+        assert_eq!(
+            slot.next_activation(Duration::hours(12), &at("2021-09-09T18:00:00Z")),
+            Some(at("2021-09-09T20:00:00Z"))
+        );
+        // friday evening waits for monday
+        let slot: Slot = "08:00-17:00*daily@mon-fri".parse().unwrap();
+        assert_eq!(
+            slot.next_activation(none, &at("2021-09-10T18:00:00Z")),
+            Some(at("2021-09-13T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn daily_past_midnight() {
+        let slot: Slot = "22:00-02:00*daily".parse().unwrap();
+        let none = Duration::hours(0);
+        assert!(slot.is_active(none, &at("2021-09-09T23:00:00Z")));
+        assert!(slot.is_active(none, &at("2021-09-10T01:00:00Z")));
+        assert!(!slot.is_active(none, &at("2021-09-10T02:00:00Z")));
+        assert!(!slot.is_active(none, &at("2021-09-10T12:00:00Z")));
+    }
+
+    #[test]
+    fn parse_weekdays() {
+        let slot: Slot = "08:00-17:00*daily@mon-wed,fri".parse().unwrap();
+        assert_eq!(
+            slot.weekdays,
+            Some(
+                [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Fri]
+                    .iter()
+                    .copied()
+                    .collect()
+            )
+        );
+        assert_eq!(slot.to_string(), "08:00:00-17:00:00*daily@mon-wed,fri");
+    }
+
+    #[test]
+    fn weekday_ranges_wrap_around() {
+        let weekdays: Weekdays = "fri-mon".parse().unwrap();
+        assert_eq!(weekdays.to_string(), "fri-mon");
+        assert!(weekdays.contains(Weekday::Sun));
+        assert!(!weekdays.contains(Weekday::Tue));
+    }
+
+    #[test]
+    fn unknown_weekday() {
+        assert_eq!(
+            "08:00-17:00*daily@mon-fry".parse::<Slot>().unwrap_err(),
+            ParseSlotError::Weekday {
+                not_parsed: "fry".to_string()
+            }
+        );
+        assert_eq!(
+            "08:00-17:00*daily@".parse::<Slot>().unwrap_err(),
+            ParseSlotError::Weekday {
+                not_parsed: "".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn saturday_outside_weekdays() {
+        let none = Duration::hours(0);
+        let daily: Slot = "08:00-17:00*daily@mon-fri".parse().unwrap();
+        // 2021-09-10 is a Friday
+        assert!(daily.is_active(none, &at("2021-09-10T12:00:00Z")));
+        assert!(!daily.is_active(none, &at("2021-09-11T12:00:00Z")));
+        // starts on Monday within the lookahead
+        assert!(daily.is_active(Duration::hours(12), &at("2021-09-12T23:00:00Z")));
+
+        let once: Slot = "2021-09-10T00:00:00/2021-09-13T00:00:00@mon-fri"
+            .parse()
+            .unwrap();
+        assert!(once.is_active(none, &at("2021-09-10T12:00:00Z")));
+        assert!(!once.is_active(none, &at("2021-09-11T12:00:00Z")));
+    }
+
+    #[test]
+    fn daily_past_midnight_on_weekdays() {
+        // the friday night slot ends on saturday
+        let slot: Slot = "22:00-02:00*daily@fri".parse().unwrap();
+        let none = Duration::hours(0);
+        assert!(slot.is_active(none, &at("2021-09-11T01:00:00Z")));
+        assert!(!slot.is_active(none, &at("2021-09-11T23:00:00Z")));
+    }
+
+    #[test]
+    fn open_ended() {
+        let slot: Slot = "2021-09-09T08:00:00/".parse().unwrap();
+        assert_eq!(
+            slot,
+            Slot::from(Window::Once {
+                start: SlotTime::Local("2021-09-09T08:00:00".parse().unwrap()),
+                end: None
+            })
+        );
+        assert_eq!(slot.to_string(), "2021-09-09T08:00:00/");
+    }
+
+    #[test]
+    fn open_ended_with_offset_and_weekdays() {
+        let slot: Slot = "2021-09-09 08:00:00+02:00/@mon-fri".parse().unwrap();
+        assert_eq!(slot.to_string(), "2021-09-09T08:00:00+02:00/@mon-fri");
+    }
+
+    #[test]
+    fn open_ended_never_over() {
+        let slot: Slot = "2021-09-09T08:00:00Z/".parse().unwrap();
+        let none = Duration::hours(0);
+        assert!(!slot.is_active(none, &at("2021-09-09T07:00:00Z")));
+        assert!(slot.is_active(Duration::hours(2), &at("2021-09-09T07:00:00Z")));
+        assert!(slot.is_active(none, &at("2021-09-09T08:00:01Z")));
+        assert!(slot.is_active(none, &at("2121-09-09T08:00:00Z")));
+    }
+
+    #[test]
+    fn open_start_is_incomplete() {
+        match "/2021-09-09T08:00:00".parse::<Slot>().unwrap_err() {
+            ParseSlotError::DateFormat { not_parsed, .. } => assert_eq!(not_parsed, ""),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
 }
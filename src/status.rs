@@ -1,32 +1,95 @@
-use crate::{parity::parity_byte, serial::Serial, telegram::Telegram};
+use crate::{
+    echo,
+    error_kind::ErrorKind,
+    serial::{read_telegram, FrameError, Serial, MAX_TELEGRAM_LEN},
+    telegram::{Telegram, TelegramParseError},
+};
+use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
-use std::io::{Read, Write};
+use std::io::Write;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tracing::{event, Level};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub fn status(serial: &mut Serial, address: u8) -> Result<Status> {
+    status_with_parity_retries(serial, address, 0)
+}
+
+/// Queries the status like [`status`], but repeats the query up to
+/// `parity_retries` times if the response has a parity error, since a single
+/// corrupted response on a noisy bus is often a one-off glitch.
+///
+/// Returns the last parity error if every response was corrupt.
+pub fn status_with_parity_retries(
+    serial: &mut Serial,
+    address: u8,
+    parity_retries: u32,
+) -> Result<Status> {
+    let mut retries_left = parity_retries;
+    loop {
+        match query_status(serial, address) {
+            Err(Error::Parity { expected, got }) if retries_left > 0 => {
+                event!(
+                    Level::DEBUG,
+                    address,
+                    expected,
+                    got,
+                    "Parity error in status response, retrying"
+                );
+                retries_left -= 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn query_status(serial: &mut Serial, address: u8) -> Result<Status> {
     assert!(address < 16, "Expected address in range 0..=15");
 
     let telegram = Telegram::display_status(address);
+    echo::telegram(&telegram);
     serial.write_all(telegram.as_bytes())?;
     serial.flush()?;
 
-    let mut response = [0_u8; 4];
-    serial.read_exact(&mut response)?;
+    let response = read_telegram(serial)?;
+    match response.payload() {
+        [_, status_char, ..] => Ok((*status_char).into()),
+        _ => Err(Error::Malformed),
+    }
+}
+
+/// Last seen status per address, so that repeated status checks within a
+/// short time do not have to query the device again.
+#[derive(Debug, Clone)]
+pub struct StatusCache {
+    ttl: Duration,
+    entries: HashMap<u8, (Instant, Status)>,
+}
 
-    let received_checksum = response[3];
-    let expected_checksum = parity_byte(&response[0..3]);
-    if received_checksum != expected_checksum {
-        return Err(Error::Parity {
-            expected: expected_checksum,
-            got: received_checksum,
-        });
+impl StatusCache {
+    /// Creates a cache that returns statuses for the given time after they
+    /// were inserted. A cache with zero time to live never returns anything.
+    pub fn new(ttl: Duration) -> Self {
+        StatusCache {
+            ttl,
+            entries: HashMap::new(),
+        }
     }
 
-    let status_char = response[1];
-    let status = status_char.into();
-    Ok(status)
+    /// Gets the status last seen for the address, if seen less than the time
+    /// to live before `now`.
+    pub fn get(&self, address: u8, now: Instant) -> Option<Status> {
+        self.entries
+            .get(&address)
+            .filter(|(seen, _)| now.saturating_duration_since(*seen) < self.ttl)
+            .map(|&(_, status)| status)
+    }
+
+    pub fn insert(&mut self, address: u8, status: Status, now: Instant) {
+        self.entries.insert(address, (now, status));
+    }
 }
 
 /// Responses from the display status command. Not well understood.
@@ -48,6 +111,17 @@ pub enum Status {
     Uncategorized(u8),
 }
 
+impl Status {
+    /// Whether a device with this status is known to show destinations sent
+    /// to it.
+    ///
+    /// Since the meaning of most statuses is not known, only `Ok` is assumed
+    /// to accept destinations.
+    pub fn accepts_destinations(&self) -> bool {
+        *self == Status::Ok
+    }
+}
+
 impl From<u8> for Status {
     fn from(status_byte: u8) -> Self {
         match status_byte {
@@ -74,10 +148,45 @@ pub enum Error {
     IO(#[from] std::io::Error),
     #[error("corrupt message, found parity byte {got}, expecting {expected}")]
     Parity { expected: u8, got: u8 },
+    #[error("status response does not contain a status")]
+    Malformed,
+    #[error("status response exceeds {MAX_TELEGRAM_LEN} bytes without carriage return")]
+    TooLong,
+}
+
+impl Error {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::IO(error) => ErrorKind::of_io(error),
+            Error::Parity { .. } | Error::Malformed | Error::TooLong => ErrorKind::Device,
+        }
+    }
+}
+
+impl From<TelegramParseError> for Error {
+    fn from(error: TelegramParseError) -> Self {
+        match error {
+            TelegramParseError::Parity { expected, received } => Error::Parity {
+                expected,
+                got: received,
+            },
+            TelegramParseError::Malformed => Error::Malformed,
+        }
+    }
+}
+
+impl From<FrameError> for Error {
+    fn from(error: FrameError) -> Self {
+        match error {
+            FrameError::IO(error) => Error::IO(error),
+            FrameError::TooLong => Error::TooLong,
+            FrameError::Corrupt(error) => error.into(),
+        }
+    }
 }
 
 impl Error {
-    #[cfg(test)]
     pub fn is_timed_out(&self) -> bool {
         match self {
             Error::IO(err) if err.kind() == std::io::ErrorKind::TimedOut => true,
@@ -89,6 +198,7 @@ impl Error {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::parity::parity_byte;
 
     #[test]
     fn timeout() {
@@ -114,6 +224,60 @@ mod test {
         }
     }
 
+    #[test]
+    fn parity_err_then_clean() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(b"a3\r0") // correct checksum would be a space, not 0
+            .expect_write(b"a0\r#")
+            .respond(b"a3\r ")
+            .build();
+
+        let status = status_with_parity_retries(&mut serial, 0, 1).unwrap();
+
+        assert_eq!(status, Status::Ok)
+    }
+
+    #[test]
+    fn persistent_parity_err() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(b"a3\r0")
+            .expect_write(b"a0\r#")
+            .respond(b"a3\r0")
+            .build();
+
+        match status_with_parity_retries(&mut serial, 0, 1).unwrap_err() {
+            Error::Parity { .. } => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn cache_reused_within_ttl() {
+        let mut cache = StatusCache::new(Duration::from_secs(10));
+        let start = Instant::now();
+        cache.insert(1, Status::Ok, start);
+
+        assert_eq!(
+            cache.get(1, start + Duration::from_secs(9)),
+            Some(Status::Ok)
+        );
+        assert_eq!(cache.get(2, start), None);
+    }
+
+    #[test]
+    fn cache_refreshed_after_ttl() {
+        let mut cache = StatusCache::new(Duration::from_secs(10));
+        let start = Instant::now();
+        cache.insert(1, Status::Ok, start);
+
+        let later = start + Duration::from_secs(10);
+        assert_eq!(cache.get(1, later), None);
+        cache.insert(1, Status::ReadyForData, later);
+        assert_eq!(cache.get(1, later), Some(Status::ReadyForData));
+    }
+
     #[test]
     fn ok() {
         let mut serial = Serial::builder()
@@ -162,6 +326,80 @@ mod test {
         )
     }
 
+    #[test]
+    fn byte_at_a_time() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(b"a")
+            .respond(b"3")
+            .respond(b"\r")
+            .respond(b" ")
+            .build();
+
+        let status = status(&mut serial, 0).unwrap();
+
+        assert_eq!(status, Status::Ok)
+    }
+
+    #[test]
+    fn byte_at_a_time_parity_err() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(b"a")
+            .respond(b"3")
+            .respond(b"\r")
+            .respond(b"0")
+            .build();
+
+        match status(&mut serial, 0).unwrap_err() {
+            Error::Parity { expected, got } => {
+                assert_eq!(expected, b' ');
+                assert_eq!(got, b'0');
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn longer_payload() {
+        let response = Telegram::custom(b"a3xy");
+        let mut serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(response.as_bytes())
+            .build();
+
+        let status = status(&mut serial, 0).unwrap();
+
+        assert_eq!(status, Status::Ok)
+    }
+
+    #[test]
+    fn missing_status() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(b"a\r")
+            .respond(&[parity_byte(b"a\r")])
+            .build();
+
+        match status(&mut serial, 0).unwrap_err() {
+            Error::Malformed => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn no_carriage_return() {
+        let mut serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(&[b'a'; MAX_TELEGRAM_LEN])
+            .build();
+
+        match status(&mut serial, 0).unwrap_err() {
+            Error::TooLong => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
     #[should_panic]
     #[test]
     fn address_out_of_bounds() {
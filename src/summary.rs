@@ -0,0 +1,736 @@
+//! Human-readable descriptions of what an invocation will do when executed,
+//! e.g. `flash mini0.hex to address 1 on /dev/ttyUSB0`.
+
+use crate::args::{
+    Active, Blank, Checksum, Cycle, Destination, Dump, Flash, Format, Invocation, Line, List,
+    Monitor, Run, Scan, SendScript, SendTelegram, Status, Version,
+};
+use crate::flash::STDIN_PATH;
+use crate::output;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+impl Display for Invocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Invocation::Run(run) => run.fmt(f),
+            Invocation::List(list) => list.fmt(f),
+            Invocation::Flash(flash) => flash.fmt(f),
+            Invocation::Scan(scan) => scan.fmt(f),
+            Invocation::Destination(destination) => destination.fmt(f),
+            Invocation::Line(line) => line.fmt(f),
+            Invocation::Blank(blank) => blank.fmt(f),
+            Invocation::Monitor(monitor) => monitor.fmt(f),
+            Invocation::Cycle(cycle) => cycle.fmt(f),
+            Invocation::Checksum(checksum) => checksum.fmt(f),
+            Invocation::SendScript(script) => script.fmt(f),
+            Invocation::Send(send) => send.fmt(f),
+            Invocation::Active(active) => active.fmt(f),
+            Invocation::Version(version) => version.fmt(f),
+            Invocation::Dump(dump) => dump.fmt(f),
+            Invocation::Status(status) => status.fmt(f),
+        }
+    }
+}
+
+impl Display for List {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("list available serial ports")?;
+        if self.probe {
+            f.write_str(" where a display device responds")?;
+        }
+        if self.verbose {
+            f.write_str(" with their types")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Run {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "run configuration {}", self.config.display())
+    }
+}
+
+impl Display for Flash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.dry_run {
+            f.write_str("pretend to ")?;
+        }
+        if self.sign_db_hex == Path::new(STDIN_PATH) {
+            f.write_str("flash the database from stdin to ")?;
+        } else {
+            write!(f, "flash {} to ", self.sign_db_hex.display())?;
+        }
+        match (self.address, &self.group) {
+            (Some(address), _) => write!(f, "address {}", address)?,
+            (None, Some(group)) => write!(f, "addresses in group {}", group)?,
+            (None, None) if self.any_address => f.write_str("the first device that responds")?,
+            (None, None) if self.all_addresses => f.write_str("every device that responds")?,
+            (None, None) => f.write_str("no address")?,
+        }
+        match (self.dry_run, &self.serial) {
+            (true, None) => f.write_str(" without opening a serial port")?,
+            _ => write!(f, " on {}", port(&self.serial))?,
+        }
+        if self.base_address != 0 {
+            write!(f, ", starting at offset 0x{:04X}", self.base_address)?;
+        }
+        if self.check_version {
+            f.write_str(", checking the software version first")?;
+        }
+        if self.require_ready {
+            f.write_str(", refusing devices that are not ready for data")?;
+        }
+        if self.force {
+            f.write_str(", ignoring failed checks")?;
+        }
+        if self.wake_count != 1 {
+            write!(f, ", sending {} wake telegrams first", self.wake_count)?;
+        }
+        if self.chunk_retries != 3 {
+            write!(
+                f,
+                ", sending unacknowledged chunks up to {} more times",
+                self.chunk_retries
+            )?;
+        }
+        if let Some(max_flash_time) = self.max_flash_time {
+            write!(f, ", giving up after {} per device", max_flash_time)?;
+        }
+        if self.strict {
+            f.write_str(", refusing databases with missing chunks")?;
+        }
+        if self.verify_clear {
+            f.write_str(", checking that the database was cleared")?;
+        }
+        if self.verify {
+            f.write_str(", reading it back afterwards")?;
+        }
+        if self.drain_finish {
+            f.write_str(", discarding any response to the last finishing step")?;
+        }
+        if self.check_status_after {
+            f.write_str(", checking the status afterwards")?;
+        }
+        if self.quiet {
+            f.write_str(", without reporting progress")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Scan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "scan for display devices on {}", port(&self.serial))?;
+        if let Some(addresses) = self.addresses {
+            write!(f, " at addresses {}", addresses)?;
+        }
+        write_timeout(f, self.timeout_secs, 1)?;
+        if let Some(quick_timeout) = self.quick_timeout {
+            write!(
+                f,
+                ", with a quick first pass waiting {} for each response",
+                quick_timeout
+            )?;
+        }
+        if self.include_reserved {
+            f.write_str(", flagging non-standard addresses")?;
+        }
+        if self.retry_parity {
+            f.write_str(", querying again on parity errors")?;
+        }
+        match self.format {
+            Format::Text => Ok(()),
+            Format::Json => f.write_str(", printing JSON"),
+            Format::Csv => f.write_str(", printing CSV"),
+        }
+    }
+}
+
+impl Display for Destination {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.index.iter().nth(1).is_some() {
+            write!(f, "show destinations {} once", self.index)?;
+            if let Some(interval) = self.interval {
+                write!(f, " every {}", interval)?;
+            }
+        } else {
+            write!(f, "show destination {}", self.index)?;
+        }
+        if let Some(line) = self.line {
+            write!(f, " for line {}", line)?;
+        }
+        write_target(f, &self.serial, &self.output)?;
+        write_timeout(f, self.timeout_secs, 3)?;
+        if self.retry > 0 {
+            write!(f, ", trying again up to {} times", self.retry)?;
+            if let Some(delay) = self.retry_delay {
+                write!(f, " starting after {}", delay)?;
+            }
+        }
+        if self.all_addresses {
+            if self.status_gated {
+                f.write_str(", waiting until every display that responds is ready")?;
+            } else {
+                f.write_str(", listing the displays that respond")?;
+            }
+            return Ok(());
+        }
+        write_status_gate(f, self.address, self.group.as_deref(), self.status_gated)?;
+        if self.verify && !self.status_gated {
+            f.write_str(", refusing to send if the display does not respond")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.line.iter().nth(1).is_some() {
+            write!(f, "set lines {} once", self.line)?;
+            if let Some(interval) = self.interval {
+                write!(f, " every {}", interval)?;
+            }
+        } else {
+            write!(f, "set line {}", self.line)?;
+        }
+        write_target(f, &self.serial, &self.output)
+    }
+}
+
+impl Display for Blank {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blank the display with destination {} on {}",
+            self.blank_index,
+            port(&self.serial)
+        )
+    }
+}
+
+impl Display for Monitor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "print telegrams received on {}", port(&self.serial))
+    }
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(hours) = self.preview {
+            write!(f, "preview {}h of ", hours)?;
+        }
+        f.write_str("cycle destinations ")?;
+        for (idx, plan) in self.plan.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            for (idx, destinations) in plan.destinations().iter().enumerate() {
+                if idx > 0 {
+                    f.write_str(",")?;
+                }
+                write!(f, "{}", destinations)?;
+            }
+        }
+        write!(
+            f,
+            " every {interval}s on {serial}",
+            interval = self.interval_secs,
+            serial = port(&self.serial)
+        )?;
+        write_timeout(f, self.timeout_secs, 3)?;
+
+        let slot_count: usize = self.plan.iter().map(|p| p.slots().len()).sum();
+        if slot_count > 0 {
+            write!(
+                f,
+                " with {count} scheduled {slots}, shown up to {lookahead}h ahead",
+                count = slot_count,
+                slots = if slot_count == 1 { "slot" } else { "slots" },
+                lookahead = self.lookahead
+            )?;
+        }
+        if self.skip_unchanged {
+            f.write_str(", skipping unchanged destinations")?;
+            if let Some(force_refresh) = self.force_refresh {
+                write!(f, " for up to {}", force_refresh)?;
+            }
+        }
+        if self.shuffle {
+            f.write_str(", in random order")?;
+            if let Some(seed) = self.seed {
+                write!(f, " with seed {}", seed)?;
+            }
+        }
+        write_status_gate(f, self.address, None, self.status_gated)?;
+        match self.status_ttl {
+            Some(ttl) if self.status_gated => write!(f, ", reusing statuses for {}", ttl)?,
+            _ => (),
+        }
+        if let Some(run_for) = self.run_for {
+            write!(f, ", stopping after {}", run_for)?;
+        }
+        if self.retry_interval_secs != 5 {
+            write!(f, ", retrying after {}s", self.retry_interval_secs)?;
+        }
+        if let Some(index) = self.fallback {
+            write!(f, ", showing destination {} while no plan is active", index)?;
+        }
+        match self.default_destination {
+            Some(index) => write!(f, ", showing destination {} when stopped", index),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for Checksum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match (self.ibis, self.record) {
+            (true, false) => "IBIS parity",
+            (false, true) => "record checksum",
+            _ => "IBIS parity and record checksum",
+        };
+        if self.bytes.is_empty() {
+            write!(f, "calculate {} of bytes from stdin", kind)
+        } else {
+            write!(f, "calculate {} of {}", kind, self.bytes.join(" "))
+        }
+    }
+}
+
+impl Display for Active {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "print plans of {} active at {}",
+            self.config.display(),
+            self.at.as_deref().unwrap_or("the current time")
+        )
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "query the software version of the display at address {} on {}",
+            self.address,
+            port(&self.serial)
+        )?;
+        write_timeout(f, self.timeout_secs, 3)
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.address {
+            Some(address) if !self.any_address => write!(
+                f,
+                "query the status of the display at address {} on {}",
+                address,
+                port(&self.serial)
+            )?,
+            _ => write!(
+                f,
+                "query the status of the first display that responds on {}",
+                port(&self.serial)
+            )?,
+        }
+        write_timeout(f, self.timeout_secs, 3)
+    }
+}
+
+impl Display for SendScript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "send script {} on {}",
+            self.script.display(),
+            port(&self.serial)
+        )?;
+        write_timeout(f, self.timeout_secs, 3)
+    }
+}
+
+impl Display for Dump {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("dump ")?;
+        if self.len == 0x1_0000 {
+            f.write_str("the sign database")?;
+        } else {
+            write!(f, "the first 0x{:X} bytes of the sign database", self.len)?;
+        }
+        write!(
+            f,
+            " at address {} to {} on {}",
+            self.address,
+            self.output.display(),
+            port(&self.serial)
+        )?;
+        write_timeout(f, self.timeout_secs, 3)
+    }
+}
+
+impl Display for SendTelegram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let payload = self.payload.join(" ");
+        if self.hex {
+            write!(f, "send telegram with payload {}", payload)?;
+        } else {
+            write!(f, "send telegram {:?}", payload)?;
+        }
+        if self.output.is_some() {
+            return write_target(f, &self.serial, &self.output);
+        }
+        write!(f, " on {}", port(&self.serial))?;
+        write_timeout(f, self.timeout_secs, 3)
+    }
+}
+
+/// Names the serial port, or describes how it will be picked if unspecified.
+fn port(serial: &Option<String>) -> &str {
+    serial
+        .as_deref()
+        .unwrap_or("an automatically picked serial port")
+}
+
+/// Names where telegrams are written to, the output if any, or else the
+/// serial port.
+fn write_target(
+    f: &mut Formatter<'_>,
+    serial: &Option<String>,
+    output: &Option<PathBuf>,
+) -> fmt::Result {
+    match output {
+        Some(path) => write!(f, " to {}", output::name(path)),
+        None => write!(f, " on {}", port(serial)),
+    }
+}
+
+/// Mentions the read timeout if it differs from the default of the command.
+fn write_timeout(f: &mut Formatter<'_>, timeout_secs: u64, default_secs: u64) -> fmt::Result {
+    if timeout_secs == default_secs {
+        Ok(())
+    } else {
+        write!(f, ", waiting up to {}s for each response", timeout_secs)
+    }
+}
+
+fn write_status_gate(
+    f: &mut Formatter<'_>,
+    address: Option<u8>,
+    group: Option<&str>,
+    status_gated: bool,
+) -> fmt::Result {
+    match (status_gated, address, group) {
+        (false, _, _) => Ok(()),
+        (true, Some(address), _) => write!(
+            f,
+            ", waiting until the display at address {} is ready",
+            address
+        ),
+        (true, None, Some(group)) => write!(
+            f,
+            ", waiting until the displays in group {} are ready",
+            group
+        ),
+        (true, None, None) => f.write_str(", waiting until the display is ready (address missing)"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::args::TopLevel;
+    use serde_yaml::from_str;
+
+    #[test]
+    fn flash() {
+        let invocation: Invocation = from_str(
+            "flash:
+  sign_db_hex: mini0.hex
+  address: 1
+  serial: /dev/ttyUSB0
+  timeout: 5
+  data_bits: 7
+  stop_bits: 2
+  parity: e
+  baudrate: 1200
+  flow_control: n
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "flash mini0.hex to address 1 on /dev/ttyUSB0"
+        )
+    }
+
+    #[test]
+    fn flash_from_stdin() {
+        let invocation: Invocation = from_str(
+            "flash:
+  sign_db_hex: \"-\"
+  address: 1
+  serial: /dev/ttyUSB0
+  timeout: 5
+  data_bits: 7
+  stop_bits: 2
+  parity: e
+  baudrate: 1200
+  flow_control: n
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "flash the database from stdin to address 1 on /dev/ttyUSB0"
+        )
+    }
+
+    #[test]
+    fn cycle_example() {
+        let invocation: Invocation = from_str(include_str!("../examples/cycle.yaml")).unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "cycle destinations 0,6 every 9s on /dev/ttyUSB0 with 1 scheduled slot, shown up to 12h ahead"
+        )
+    }
+
+    #[test]
+    fn cycle_options() {
+        let args: TopLevel = argh::FromArgs::from_args(
+            &["ibisibi"],
+            &[
+                "cycle",
+                "0-3",
+                "--default-destination",
+                "999",
+                "--shuffle",
+                "--seed",
+                "1",
+                "-s",
+                "COM5",
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            args.invocation.to_string(),
+            "cycle destinations 0-3 every 5s on COM5, in random order with seed 1, showing destination 999 when stopped"
+        )
+    }
+
+    #[test]
+    fn cycle_retry_interval() {
+        let args: TopLevel = argh::FromArgs::from_args(
+            &["ibisibi"],
+            &["cycle", "0", "--retry-interval-secs", "60", "-s", "COM5"],
+        )
+        .unwrap();
+        assert_eq!(
+            args.invocation.to_string(),
+            "cycle destinations 0 every 5s on COM5, retrying after 60s"
+        )
+    }
+
+    #[test]
+    fn cycle_skip_unchanged() {
+        let args: TopLevel = argh::FromArgs::from_args(
+            &["ibisibi"],
+            &[
+                "cycle",
+                "0",
+                "--skip-unchanged",
+                "--force-refresh",
+                "10m",
+                "-s",
+                "COM5",
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            args.invocation.to_string(),
+            "cycle destinations 0 every 5s on COM5, skipping unchanged destinations for up to 600s"
+        )
+    }
+
+    #[test]
+    fn destination_example() {
+        let invocation: Invocation =
+            from_str(include_str!("../examples/destination.yaml")).unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "show destination 0 for line 6 on COM5"
+        )
+    }
+
+    #[test]
+    fn destination_retry() {
+        let invocation: Invocation = from_str(
+            "destination:
+  index: 4
+  retry: 3
+  retry_delay: 500ms
+  serial: /dev/ttyUSB0
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "show destination 4 on /dev/ttyUSB0, trying again up to 3 times starting after 500ms"
+        )
+    }
+
+    #[test]
+    fn telegrams_to_output() {
+        let summary = |args: &[&str]| {
+            let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], args).unwrap();
+            args.invocation.to_string()
+        };
+        assert_eq!(
+            summary(&["destination", "4", "--output", "dest.bin"]),
+            "show destination 4 to dest.bin"
+        );
+        assert_eq!(
+            summary(&["line", "6", "--output", "-"]),
+            "set line 6 to stdout"
+        );
+        assert_eq!(
+            summary(&["line", "1-3", "--interval", "2s", "--output", "-"]),
+            "set lines 1-3 once every 2s to stdout"
+        );
+        assert_eq!(
+            summary(&["send", "aV1", "--output", "-"]),
+            "send telegram \"aV1\" to stdout"
+        );
+    }
+
+    #[test]
+    fn dump() {
+        let summary = |args: &[&str]| {
+            let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], args).unwrap();
+            args.invocation.to_string()
+        };
+        assert_eq!(
+            summary(&["dump", "backup.hex", "-a", "1", "-s", "COM5"]),
+            "dump the sign database at address 1 to backup.hex on COM5"
+        );
+        assert_eq!(
+            summary(&[
+                "dump",
+                "backup.hex",
+                "-a",
+                "1",
+                "--len",
+                "0x2000",
+                "-s",
+                "COM5"
+            ]),
+            "dump the first 0x2000 bytes of the sign database at address 1 to backup.hex on COM5"
+        );
+    }
+
+    #[test]
+    fn blank_example() {
+        let invocation: Invocation = from_str(include_str!("../examples/blank.yaml")).unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "blank the display with destination 999 on COM5"
+        )
+    }
+
+    #[test]
+    fn destination_range() {
+        let invocation: Invocation = from_str(
+            "destination:
+  index: 1-3
+  line: 6
+  interval: 2s
+  address: 1
+  status_gated: true
+  serial: /dev/ttyUSB0
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "show destinations 1-3 once every 2s for line 6 on /dev/ttyUSB0, waiting until the display at address 1 is ready"
+        )
+    }
+
+    #[test]
+    fn scan_without_port() {
+        let args: TopLevel = argh::FromArgs::from_args(&["ibisibi"], &["scan"]).unwrap();
+        assert_eq!(
+            args.invocation.to_string(),
+            "scan for display devices on an automatically picked serial port"
+        )
+    }
+
+    #[test]
+    fn status() {
+        let invocation: Invocation = from_str(
+            "status:
+  address: 1
+  serial: COM5
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "query the status of the display at address 1 on COM5"
+        );
+        let invocation: Invocation = from_str(
+            "status:
+  any_address: true
+  serial: COM5
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "query the status of the first display that responds on COM5"
+        )
+    }
+
+    #[test]
+    fn version() {
+        let invocation: Invocation = from_str(
+            "version:
+  address: 1
+  serial: COM5
+  timeout_secs: 10
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "query the software version of the display at address 1 on COM5, waiting up to 10s for each response"
+        )
+    }
+
+    #[test]
+    fn list() {
+        let invocation: Invocation = from_str(include_str!("../examples/list.yaml")).unwrap();
+        assert_eq!(invocation.to_string(), "list available serial ports")
+    }
+
+    #[test]
+    fn list_verbose() {
+        let invocation: Invocation = from_str("list:\n  verbose: true").unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "list available serial ports with their types"
+        )
+    }
+
+    #[test]
+    fn list_probe() {
+        let invocation: Invocation = from_str("list:\n  probe: true").unwrap();
+        assert_eq!(
+            invocation.to_string(),
+            "list available serial ports where a display device responds"
+        )
+    }
+}
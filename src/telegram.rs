@@ -6,18 +6,49 @@
 
 use builder::Builder;
 use std::fmt;
+use thiserror::Error;
 
 pub use parse::TelegramParseError;
 
+/// Number of characters in the stop name of a DS009 telegram.
+pub const NEXT_STOP_LEN: usize = 16;
+
 /// A telegram in the IBIS protocol, binary, including trailing carriage return
 /// and checksum. The contained data is guaranteed to be a valid telegram
 /// that can be sent over the bus or that has been received over the bus and is
 /// valid.
 ///
+/// Telegrams produced by any of the constructors parse back into the same
+/// bytes with `TryFrom<&[u8]>`, including ones with a prefix like
+/// [`Telegram::bs_select_address`], since the parity always covers every byte
+/// before it.
+///
 /// For example, [Telegram::destination(u8)][Telegram::destination(u8)]
 /// produces the DS003 telegram.
 pub struct Telegram(Vec<u8>);
 
+/// Kind of a telegram, as recognized by [`Telegram::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelegramKind {
+    /// DS001, selecting a line number.
+    Line,
+    /// DS003, selecting a destination by index.
+    Destination,
+    /// DS009, showing the name of the next stop.
+    NextStop,
+    /// DS020 status query or the response to it, which cannot be told apart
+    /// since both consist of `a` and a single ASCII digit.
+    Status,
+    /// DS120 version query or the response to it.
+    Version,
+    /// Telegram with just a carriage return and a parity byte.
+    Empty,
+    /// Address selection sent before flashing BS210 signs.
+    SelectAddress,
+    /// Anything else.
+    Unknown,
+}
+
 impl fmt::Display for Telegram {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let len_excl_cr_and_parity = self.0.len() - 2;
@@ -36,10 +67,15 @@ impl fmt::Display for Telegram {
 }
 
 impl fmt::Debug for Telegram {
+    /// Formats the telegram as a string, or as hexadecimal bytes if it is not
+    /// valid UTF-8, e.g. when received from a noisy bus.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_tuple("Telegram")
-            .field(&String::from_utf8_lossy(&self.0[..]))
-            .finish()
+        let mut tuple = f.debug_tuple("Telegram");
+        match std::str::from_utf8(&self.0[..]) {
+            Ok(text) => tuple.field(&text),
+            Err(_) => tuple.field(&format_args!("{:02X?}", &self.0[..])),
+        };
+        tuple.finish()
     }
 }
 
@@ -53,14 +89,19 @@ impl Telegram {
     /// three digits, that is, if greater than 999, then the function
     /// will panic.
     pub fn line(line_nr: u16) -> Telegram {
-        assert!(
-            line_nr > 0 && line_nr <= 999,
-            "Line must be in range 1--999 so that it is non-zero and can be represented with three decimal digits"
-          );
-        Builder::with_msg_len(4) // l000 has four bytes
+        Telegram::try_line(line_nr).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Produces a DS001 telegram like [`Telegram::line`], but returns an
+    /// error instead of panicking if the line is out of range.
+    pub fn try_line(line_nr: u16) -> Result<Telegram, TelegramError> {
+        if line_nr == 0 || line_nr > 999 {
+            return Err(TelegramError::LineOutOfRange(line_nr));
+        }
+        Ok(Builder::with_msg_len(4) // l000 has four bytes
             .byte(b'l')
             .three_digits(line_nr)
-            .finish()
+            .finish())
     }
 
     /// Produces a DS003 telegram, selecting a destination by index.
@@ -71,14 +112,45 @@ impl Telegram {
     /// If the destination can not be represented with three digits,
     /// that is, if greater than 999, then this function panics.
     pub fn destination(destination_idx: u16) -> Telegram {
-        assert!(
-          destination_idx <= 999,
-          "Destination must be in range 0--999 so that it can be represented with three decimal digits"
-        );
-        Builder::with_msg_len(4) // z000 has four bytes
+        Telegram::try_destination(destination_idx).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Produces a DS003 telegram like [`Telegram::destination`], but returns
+    /// an error instead of panicking if the index is out of range.
+    pub fn try_destination(destination_idx: u16) -> Result<Telegram, TelegramError> {
+        if destination_idx > 999 {
+            return Err(TelegramError::DestinationOutOfRange(destination_idx));
+        }
+        Ok(Builder::with_msg_len(4) // z000 has four bytes
             .byte(b'z')
             .three_digits(destination_idx)
-            .finish()
+            .finish())
+    }
+
+    /// Produces a DS009 telegram, showing the name of the next stop, e.g.
+    /// `vHauptbahnhof    `, in the default [`Encoding`].
+    ///
+    /// The text is padded with spaces to the fixed length of
+    /// [`NEXT_STOP_LEN`] characters.
+    pub fn next_stop(text: &str) -> Result<Telegram, TextError> {
+        Telegram::next_stop_encoded(text, Encoding::default())
+    }
+
+    /// Produces a DS009 telegram like [`Telegram::next_stop`], but encodes
+    /// the text with the given encoding.
+    pub fn next_stop_encoded(text: &str, encoding: Encoding) -> Result<Telegram, TextError> {
+        let len = text.chars().count();
+        if len > NEXT_STOP_LEN {
+            return Err(TextError::TooLong {
+                len,
+                max: NEXT_STOP_LEN,
+            });
+        }
+        Ok(Builder::with_msg_len(1 + NEXT_STOP_LEN)
+            .byte(b'v')
+            .text(text, encoding)
+            .spaces(NEXT_STOP_LEN - len)
+            .finish())
     }
 
     /// Produces a DS20 telegram, querying the status of a display device. Suitable for
@@ -96,14 +168,19 @@ impl Telegram {
     /// # Panics
     /// This function panics if the address is higher than 15.
     pub fn display_status(address: u8) -> Telegram {
-        assert!(
-            address <= 15,
-            "Address for display status query must be in range 0-15"
-        );
-        Builder::with_msg_len(2) // a0 has two bytes
+        Telegram::try_display_status(address).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Produces a DS20 telegram like [`Telegram::display_status`], but
+    /// returns an error instead of panicking if the address is out of range.
+    pub fn try_display_status(address: u8) -> Result<Telegram, TelegramError> {
+        if address > 15 {
+            return Err(TelegramError::AddressOutOfRange(address));
+        }
+        Ok(Builder::with_msg_len(2) // a0 has two bytes
             .byte(b'a')
             .address(address)
-            .finish()
+            .finish())
     }
 
     /// Produces a DS120 telegram, querying the software version or versionf of a display
@@ -121,24 +198,31 @@ impl Telegram {
     ///
     /// # Panics
     /// This function panics if the address is higher than 15.
-    #[cfg(test)]
     pub fn display_version(address: u8) -> Telegram {
-        assert!(
-            address <= 15,
-            "Address for display version query must be in range 0-15"
-        );
-        Builder::with_msg_len(3) // aV0 has three bytes
+        Telegram::try_display_version(address).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Produces a DS120 telegram like [`Telegram::display_version`], but
+    /// returns an error instead of panicking if the address is out of range.
+    pub fn try_display_version(address: u8) -> Result<Telegram, TelegramError> {
+        if address > 15 {
+            return Err(TelegramError::AddressOutOfRange(address));
+        }
+        Ok(Builder::with_msg_len(3) // aV0 has three bytes
             .byte(b'a')
             .byte(b'V')
             .address(address)
-            .finish()
+            .finish())
     }
 
     /// An empty IBIS telegram, consisting only of the terminating carriage return
-    /// and a checksum of 0x72.
+    /// and a checksum of 0x72, that is, the bytes `[0x0D, 0x72]`.
     ///
-    /// The effect of an empty message is not known, but it has been observed that
-    /// this message is sent right before `bs_select_address` (in the same physical write).
+    /// Before flashing a BS210 sign, this is sent once or more to wake the sign,
+    /// followed by [`Telegram::bs_select_address`] to select the sign to flash.
+    /// The original software sends both in the same physical write. The exact
+    /// effect of the empty telegram is not known, and no response to it has
+    /// been observed.
     pub fn empty() -> Telegram {
         Builder::with_msg_len(0).finish()
     }
@@ -160,8 +244,36 @@ impl Telegram {
             .finish()
     }
 
+    /// Produces a telegram with the given payload, e.g. for telegrams not
+    /// supported with a dedicated function yet, by appending the carriage
+    /// return and the parity byte.
+    ///
+    /// The payload is not checked, so it is possible to produce telegrams that
+    /// devices do not understand.
+    pub fn custom(payload: &[u8]) -> Telegram {
+        payload
+            .iter()
+            .fold(Builder::with_msg_len(payload.len()), |builder, &byte| {
+                builder.byte(byte)
+            })
+            .finish()
+    }
+
+    /// Classifies the telegram by its leading bytes.
+    pub fn kind(&self) -> TelegramKind {
+        match self.payload() {
+            [] => TelegramKind::Empty,
+            [b'l', ..] => TelegramKind::Line,
+            [b'z', ..] => TelegramKind::Destination,
+            [b'v', ..] => TelegramKind::NextStop,
+            [b'a', b'V', ..] => TelegramKind::Version,
+            [b'a', _] => TelegramKind::Status,
+            [0x1B, b'S', ..] => TelegramKind::SelectAddress,
+            _ => TelegramKind::Unknown,
+        }
+    }
+
     /// Gets the telegram payload, that is, the part before CR and the checksum.
-    #[cfg(test)]
     pub fn payload(&self) -> &[u8] {
         &self.0[..self.0.len() - 2]
     }
@@ -173,8 +285,74 @@ impl Telegram {
     }
 }
 
+/// Character set of text in telegrams, e.g. stop names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// The German 7-bit variant of ASCII from DIN 66003 that is common on
+    /// IBIS, e.g. on BS210 signs, where umlauts and ß take the place of
+    /// brackets and braces, e.g. `[` for Ä and `~` for ß.
+    Din66003,
+    /// ISO 8859-1, only for devices that use eight data bits.
+    Latin1,
+}
+
+// deriving with #[default] needs a newer compiler than this crate supports
+#[allow(clippy::derivable_impls)]
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Din66003
+    }
+}
+
+/// Sent in place of characters that the encoding cannot represent.
+pub const REPLACEMENT: u8 = b'?';
+
+impl Encoding {
+    /// Encodes a single character, or returns `None` if the encoding has no
+    /// byte for it.
+    pub fn encode(self, c: char) -> Option<u8> {
+        match self {
+            Encoding::Din66003 => match c {
+                'Ä' => Some(b'['),
+                'Ö' => Some(b'\\'),
+                'Ü' => Some(b']'),
+                'ä' => Some(b'{'),
+                'ö' => Some(b'|'),
+                'ü' => Some(b'}'),
+                'ß' => Some(b'~'),
+                // the replaced characters are not available themselves
+                '[' | '\\' | ']' | '{' | '|' | '}' | '~' => None,
+                ' '..='~' => Some(c as u8),
+                _ => None,
+            },
+            Encoding::Latin1 => match c {
+                ' '..='~' | '\u{A0}'..='\u{FF}' => Some(c as u32 as u8),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Numbers that cannot be represented in a telegram.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TelegramError {
+    #[error("Line {0} is out of range 1-999")]
+    LineOutOfRange(u16),
+    #[error("Destination {0} is out of range 0-999")]
+    DestinationOutOfRange(u16),
+    #[error("Address {0} is out of range 0-15")]
+    AddressOutOfRange(u8),
+}
+
+/// Text that cannot be sent in a telegram.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TextError {
+    #[error("Text has {len} characters, but at most {max} fit into the telegram")]
+    TooLong { len: usize, max: usize },
+}
+
 mod builder {
-    use super::Telegram;
+    use super::{Encoding, Telegram, REPLACEMENT};
     use crate::parity::parity_byte;
 
     pub struct Builder {
@@ -206,6 +384,18 @@ mod builder {
             self.byte(address)
         }
 
+        /// Appends the text in the given encoding, replacing characters
+        /// that it cannot represent with [`REPLACEMENT`].
+        pub fn text(self, text: &str, encoding: Encoding) -> Self {
+            text.chars().fold(self, |builder, c| {
+                builder.byte(encoding.encode(c).unwrap_or(REPLACEMENT))
+            })
+        }
+
+        pub fn spaces(self, count: usize) -> Self {
+            (0..count).fold(self, |builder, _| builder.byte(b' '))
+        }
+
         pub fn three_digits(self, num: u16) -> Self {
             assert!(num <= 999, "digits out of range 0..=999");
             let hundreds = num / 100;
@@ -367,6 +557,92 @@ mod test {
         Telegram::line(std::u16::MAX);
     }
 
+    #[test]
+    fn next_stop() {
+        let telegram = Telegram::next_stop("Hauptbahnhof").unwrap();
+        assert_eq!(telegram.as_bytes(), b"vHauptbahnhof    \r\x38");
+        assert_eq!(telegram.kind(), TelegramKind::NextStop);
+    }
+
+    #[test]
+    fn next_stop_umlauts() {
+        let telegram = Telegram::next_stop("Münchner Straße").unwrap();
+        assert_eq!(telegram.payload(), b"vM}nchner Stra~e ");
+    }
+
+    #[test]
+    fn next_stop_full_length() {
+        let telegram = Telegram::next_stop("Schwedenplatz U1").unwrap();
+        assert_eq!(telegram.payload(), b"vSchwedenplatz U1");
+    }
+
+    #[test]
+    fn next_stop_too_long() {
+        assert_eq!(
+            Telegram::next_stop("Wien Hauptbahnhof").unwrap_err(),
+            TextError::TooLong { len: 17, max: 16 }
+        );
+    }
+
+    #[test]
+    fn next_stop_unsupported_replaced() {
+        let telegram = Telegram::next_stop("Praha hl.n. ✓").unwrap();
+        assert_eq!(telegram.payload(), b"vPraha hl.n. ?   ");
+        let telegram = Telegram::next_stop("A|B").unwrap();
+        assert_eq!(telegram.payload(), b"vA?B             ");
+    }
+
+    #[test]
+    fn umlauts_din_66003() {
+        let encoded: Vec<Option<u8>> = "ÄÖÜäöüß"
+            .chars()
+            .map(|c| Encoding::Din66003.encode(c))
+            .collect();
+        assert_eq!(
+            encoded,
+            vec![
+                Some(0x5B),
+                Some(0x5C),
+                Some(0x5D),
+                Some(0x7B),
+                Some(0x7C),
+                Some(0x7D),
+                Some(0x7E)
+            ]
+        );
+    }
+
+    #[test]
+    fn umlauts_latin_1() {
+        let telegram = Telegram::next_stop_encoded("Ölstraße", Encoding::Latin1).unwrap();
+        assert_eq!(telegram.payload(), b"v\xD6lstra\xDFe        ");
+        assert_eq!(Encoding::Latin1.encode('€'), None);
+    }
+
+    #[test]
+    fn try_out_of_range() {
+        assert_eq!(
+            Telegram::try_line(0).unwrap_err(),
+            TelegramError::LineOutOfRange(0)
+        );
+        assert_eq!(
+            Telegram::try_destination(1000).unwrap_err(),
+            TelegramError::DestinationOutOfRange(1000)
+        );
+        assert_eq!(
+            Telegram::try_display_status(16).unwrap_err(),
+            TelegramError::AddressOutOfRange(16)
+        );
+        assert_eq!(
+            Telegram::try_display_version(16).unwrap_err(),
+            TelegramError::AddressOutOfRange(16)
+        );
+        assert_eq!(
+            Telegram::try_line(26).unwrap().as_bytes(),
+            Telegram::line(26).as_bytes()
+        );
+    }
+
     #[test]
     fn destination_0() {
         let telegram = Telegram::destination(0);
@@ -454,6 +730,18 @@ mod test {
         assert_eq!(telegram, "z523<CR><P:3C>");
     }
 
+    #[test]
+    fn debug_as_string() {
+        let telegram = format!("{:?}", Telegram::destination(4));
+        assert_eq!(telegram, "Telegram(\"z004\\r<\")");
+    }
+
+    #[test]
+    fn debug_non_utf8_as_hex() {
+        let telegram = format!("{:?}", Telegram::custom(&[0xFF, b'a']));
+        assert_eq!(telegram, "Telegram([FF, 61, 0D, EC])");
+    }
+
     #[should_panic]
     #[test]
     fn version_16_panics() {
@@ -492,6 +780,7 @@ mod test {
     #[test]
     fn empty() {
         let telegram = Telegram::empty();
+        assert_eq!(telegram.payload(), b"");
         assert_eq!(telegram.as_bytes(), &[0x0d, 0x72]);
         let telegram = &format!("{}", telegram);
         assert_eq!(telegram, "<CR><P:72>");
@@ -507,4 +796,75 @@ mod test {
         assert_eq!(telegram_dbg, "Telegram(\"\\u{1b}S1\\r\\u{b}\")");
         assert_eq!(telegram_display, ".S1<CR><P:B>");
     }
+
+    #[test]
+    fn custom_matches_status() {
+        let telegram = Telegram::custom(b"a1");
+        assert_eq!(telegram.as_bytes(), Telegram::display_status(1).as_bytes());
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(Telegram::line(6).kind(), TelegramKind::Line);
+        assert_eq!(Telegram::destination(5).kind(), TelegramKind::Destination);
+        assert_eq!(Telegram::display_status(1).kind(), TelegramKind::Status);
+        assert_eq!(Telegram::display_version(1).kind(), TelegramKind::Version);
+        assert_eq!(Telegram::empty().kind(), TelegramKind::Empty);
+        assert_eq!(
+            Telegram::bs_select_address(1).kind(),
+            TelegramKind::SelectAddress
+        );
+        assert_eq!(Telegram::custom(b"xyz").kind(), TelegramKind::Unknown);
+    }
+
+    /// Checks that the telegram parses back into the same bytes, as is
+    /// expected of every telegram we send, since devices validate parity the
+    /// same way we validate responses.
+    fn assert_round_trip(telegram: &Telegram) {
+        use std::convert::TryFrom;
+        match Telegram::try_from(telegram.as_bytes()) {
+            Ok(parsed) => assert_eq!(
+                parsed.as_bytes(),
+                telegram.as_bytes(),
+                "{:?} parsed into different bytes",
+                telegram
+            ),
+            Err(error) => panic!("{:?} does not parse back: {}", telegram, error),
+        }
+    }
+
+    #[test]
+    fn constructors_round_trip() {
+        (1..=999)
+            .map(Telegram::line)
+            .for_each(|t| assert_round_trip(&t));
+        (0..=999)
+            .map(Telegram::destination)
+            .for_each(|t| assert_round_trip(&t));
+        for address in 0..=15 {
+            assert_round_trip(&Telegram::display_status(address));
+            assert_round_trip(&Telegram::display_version(address));
+        }
+        assert_round_trip(&Telegram::next_stop("Hauptbahnhof").unwrap());
+        assert_round_trip(&Telegram::custom(b"aVV2.3RigaB/H7/99"));
+        assert_round_trip(&Telegram::empty());
+    }
+
+    #[test]
+    fn select_address_round_trips_with_prefix() {
+        // the parity covers the leading escape byte too, like in any other
+        // telegram, so the prefix needs no special treatment when parsing
+        for address in 0..=15 {
+            assert_round_trip(&Telegram::bs_select_address(address));
+        }
+    }
+
+    #[test]
+    fn kinds_of_responses() {
+        use std::convert::TryFrom;
+        let status = Telegram::try_from(&b"a3\r "[..]).unwrap();
+        assert_eq!(status.kind(), TelegramKind::Status);
+        let version = Telegram::custom(b"aVV2.3RigaB/H7/99");
+        assert_eq!(version.kind(), TelegramKind::Version);
+    }
 }
@@ -0,0 +1,262 @@
+//! Appending every byte written to or read from a serial port to a file with
+//! `--trace-file`, with a timestamp and in hex, e.g. to attach a protocol
+//! trace to a bug report.
+//!
+//! Unlike log messages, the trace does not depend on the log level, and
+//! unlike `--echo`, it includes what the devices send back.
+
+use crate::hex::format_hex;
+use chrono::{DateTime, Local};
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where trace entries go, shared by all ports and their clones.
+pub type Log = Arc<Mutex<dyn Write + Send>>;
+
+static LOG: Mutex<Option<Log>> = Mutex::new(None);
+
+/// Starts appending the traffic of serial ports opened from now on to the
+/// file at the path, creating it if needed.
+pub fn enable(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let log: Log = Arc::new(Mutex::new(file));
+    *LOG.lock().unwrap_or_else(|e| e.into_inner()) = Some(log);
+    Ok(())
+}
+
+/// Wraps the port so that its traffic is traced, if tracing is enabled.
+#[cfg(not(test))]
+pub fn wrap(serial: Box<dyn SerialPort>, port: &str) -> Box<dyn SerialPort> {
+    match &*LOG.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(log) => Box::new(Traced::new(serial, port, log.clone())),
+        None => serial,
+    }
+}
+
+/// Direction of traced bytes, as seen from this program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Sent,
+    Received,
+}
+
+/// Formats a trace entry as a single line, e.g.
+/// `2021-05-01T12:00:00.000+02:00 /dev/ttyUSB0 > 61 30 0D 23`, with `>` for
+/// sent and `<` for received bytes.
+fn entry(time: &DateTime<Local>, port: &str, direction: Direction, bytes: &[u8]) -> String {
+    let arrow = match direction {
+        Direction::Sent => '>',
+        Direction::Received => '<',
+    };
+    format!(
+        "{} {} {} {}\n",
+        time.format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+        port,
+        arrow,
+        format_hex(bytes)
+    )
+}
+
+/// Port that appends everything written to or read from it to a log.
+pub struct Traced<P> {
+    inner: P,
+    port: String,
+    log: Log,
+}
+
+impl<P> Traced<P> {
+    pub fn new(inner: P, port: &str, log: Log) -> Self {
+        Traced {
+            inner,
+            port: port.to_string(),
+            log,
+        }
+    }
+
+    fn trace(&self, direction: Direction, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let entry = entry(&Local::now(), &self.port, direction, bytes);
+        if let Ok(mut log) = self.log.lock() {
+            // a failing trace must not break the communication it traces
+            let _ = log.write_all(entry.as_bytes()).and_then(|_| log.flush());
+        }
+    }
+}
+
+impl<P: Read> Read for Traced<P> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.trace(Direction::Received, &buf[..len]);
+        Ok(len)
+    }
+}
+
+impl<P: Write> Write for Traced<P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.trace(Direction::Sent, &buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl SerialPort for Traced<Box<dyn SerialPort>> {
+    fn name(&self) -> Option<String> {
+        self.inner.name()
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.inner.baud_rate()
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        self.inner.data_bits()
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        self.inner.flow_control()
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        self.inner.parity()
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        self.inner.stop_bits()
+    }
+
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.inner.set_baud_rate(baud_rate)
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.inner.set_data_bits(data_bits)
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.inner.set_flow_control(flow_control)
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.inner.set_parity(parity)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.inner.set_stop_bits(stop_bits)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.inner.read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.inner.read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.inner.read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.inner.read_carrier_detect()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_read()
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_write()
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.inner.clear(buffer_to_clear)
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(Traced {
+            inner: self.inner.try_clone()?,
+            port: self.port.clone(),
+            log: self.log.clone(),
+        }))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        self.inner.set_break()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.inner.clear_break()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serial::Serial;
+    use chrono::TimeZone;
+
+    #[test]
+    fn entry_format() {
+        let time = Local.ymd(2021, 5, 1).and_hms_milli(12, 0, 0, 5);
+        let line = entry(&time, "COM5", Direction::Sent, b"a0\r#");
+        assert!(
+            line.starts_with("2021-05-01T12:00:00.005"),
+            "Unexpected time in {:?}",
+            line
+        );
+        assert!(
+            line.ends_with(" COM5 > 61 30 0D 23\n"),
+            "Unexpected entry {:?}",
+            line
+        );
+        assert!(
+            entry(&time, "COM5", Direction::Received, b"a3\r ").ends_with(" COM5 < 61 33 0D 20\n")
+        );
+    }
+
+    #[test]
+    fn traces_writes_and_reads() {
+        let serial = Serial::builder()
+            .expect_write(b"a0\r#")
+            .respond(b"a3\r ")
+            .build();
+        let buf = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let mut traced = Traced::new(serial, "/dev/ttyUSB0", buf.clone());
+
+        traced.write_all(b"a0\r#").unwrap();
+        let mut response = [0_u8; 4];
+        traced.read_exact(&mut response).unwrap();
+
+        let log = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2, "Unexpected log {:?}", log);
+        assert!(lines[0].ends_with(" /dev/ttyUSB0 > 61 30 0D 23"));
+        assert!(lines[1].ends_with(" /dev/ttyUSB0 < 61 33 0D 20"));
+    }
+}
@@ -0,0 +1,160 @@
+//! Queries the software version of a display device using DS120.
+
+use crate::{
+    echo,
+    error_kind::ErrorKind,
+    serial::{read_telegram, FrameError, Serial, MAX_TELEGRAM_LEN},
+    telegram::{Telegram, TelegramKind, TelegramParseError},
+};
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Prefixes of software versions reported by signs that are known to accept
+/// sign databases with the flashing procedure in [`crate::flash`].
+///
+/// `V2.3Riga` has been reported by a BS210 flipdot display, see
+/// [`Telegram::display_version`].
+const FLASHABLE_VERSION_PREFIXES: &[&str] = &["V2.3Riga"];
+
+pub fn version(serial: &mut Serial, address: u8) -> Result<Version> {
+    assert!(address < 16, "Expected address in range 0..=15");
+
+    let telegram = Telegram::display_version(address);
+    echo::telegram(&telegram);
+    serial.write_all(telegram.as_bytes())?;
+    serial.flush()?;
+
+    let response = read_telegram(serial)?;
+    Version::from_response(&response)
+}
+
+/// Software version of a display device, as reported in response to DS120,
+/// e.g. `V2.3RigaB/H7/99`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version(String);
+
+impl Version {
+    /// Extracts the version from a response telegram of the form
+    /// `aV<version>`.
+    fn from_response(response: &Telegram) -> Result<Self> {
+        if response.kind() != TelegramKind::Version {
+            return Err(Error::UnexpectedResponse);
+        }
+        // skip the leading aV
+        let version = &response.payload()[2..];
+        Ok(Version(String::from_utf8_lossy(version).into_owned()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0[..]
+    }
+
+    /// Checks whether the version looks like that of a sign that we know
+    /// how to flash.
+    pub fn is_flashable(&self) -> bool {
+        FLASHABLE_VERSION_PREFIXES
+            .iter()
+            .any(|prefix| self.0.starts_with(prefix))
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("could not query software version due to serial port error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("could not query software version, response corrupt: {0}")]
+    Corrupt(#[from] TelegramParseError),
+    #[error(
+        "could not query software version, response did not end after {MAX_TELEGRAM_LEN} bytes"
+    )]
+    TooLong,
+    #[error("could not query software version, response was not a version response")]
+    UnexpectedResponse,
+}
+
+impl Error {
+    /// Category of the error for `--json-errors`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::IO(error) => ErrorKind::of_io(error),
+            Error::Corrupt(_) | Error::TooLong | Error::UnexpectedResponse => ErrorKind::Device,
+        }
+    }
+}
+
+impl From<FrameError> for Error {
+    fn from(error: FrameError) -> Self {
+        match error {
+            FrameError::IO(error) => Error::IO(error),
+            FrameError::TooLong => Error::TooLong,
+            FrameError::Corrupt(error) => Error::Corrupt(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bs210() {
+        let mut serial = Serial::builder()
+            .expect_write(b"aV1\rt")
+            .respond(&[
+                0x61, 0x56, 0x56, 0x32, 0x2e, 0x33, 0x52, 0x69, 0x67, 0x61, 0x42, 0x2f, 0x48, 0x37,
+                0x2f, 0x39, 0x39, 0x0d, 0x3c,
+            ])
+            .build();
+
+        let version = version(&mut serial, 1).unwrap();
+
+        assert_eq!(version.as_str(), "V2.3RigaB/H7/99");
+        assert!(version.is_flashable());
+    }
+
+    #[test]
+    fn unknown_device() {
+        let mut serial = Serial::builder()
+            .expect_write(b"aV1\rt")
+            .respond(b"aVX\r")
+            .respond(&[0x7F ^ b'a' ^ b'V' ^ b'X' ^ b'\r'])
+            .build();
+
+        let version = version(&mut serial, 1).unwrap();
+
+        assert_eq!(version.as_str(), "X");
+        assert!(!version.is_flashable());
+    }
+
+    #[test]
+    fn status_instead_of_version() {
+        let mut serial = Serial::builder()
+            .expect_write(b"aV1\rt")
+            .respond(b"a3\r ")
+            .build();
+
+        match version(&mut serial, 1).unwrap_err() {
+            Error::UnexpectedResponse => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let mut serial = Serial::builder().expect_write(b"aV1\rt").time_out().build();
+
+        match version(&mut serial, 1).unwrap_err() {
+            Error::IO(err) if err.kind() == std::io::ErrorKind::TimedOut => {}
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+}